@@ -0,0 +1,144 @@
+use crate::collections::consts::{AUTOTUNE_FRAMES, AUTOTUNE_FRAME_BUDGET_MS};
+use crate::collections::structs::{RayParams, TimeUniform};
+use crate::updates::param_updates::update_ray_params_buffer;
+use crate::util::time::Instant;
+
+use super::state::State;
+
+// --autotune candidates, cheapest first. High is the init_params ray march
+pub(crate) struct QualityPreset {
+    pub(crate) name: &'static str,
+    max_steps: f32,
+    max_dist: f32,
+    epsilon: f32,
+}
+
+pub(crate) const QUALITY_PRESETS: [QualityPreset; 3] = [
+    QualityPreset {
+        name: "low",
+        max_steps: 256.0,
+        max_dist: 800.0,
+        epsilon: 0.02,
+    },
+    QualityPreset {
+        name: "medium",
+        max_steps: 1000.0,
+        max_dist: 1200.0,
+        epsilon: 0.01,
+    },
+    QualityPreset {
+        name: "high",
+        max_steps: 2500.0,
+        max_dist: 1500.0,
+        epsilon: 0.01,
+    },
+];
+
+impl QualityPreset {
+    pub(crate) fn apply(&self, ray_params: &mut RayParams) {
+        ray_params.max_steps = self.max_steps;
+        ray_params.max_dist = self.max_dist;
+        ray_params.epsilon = self.epsilon;
+    }
+}
+
+impl<'a> State<'a> {
+    // Render the current view offscreen at each preset and keep the best one whose median
+    // frame fits AUTOTUNE_FRAME_BUDGET_MS, the cheapest if none do. Timed with timestamp
+    // queries when the adapter has them, otherwise by the wall clock around each submit
+    pub(crate) fn autotune(&mut self) -> &'static QualityPreset {
+        self.finish_terrain_generation();
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Autotune Terrain Encoder"),
+            });
+        self.encode_terrain_normals(&mut encoder);
+        self.encode_terrain_mips(&mut encoder);
+        self.encode_terrain_conversion(&mut encoder);
+        self.queue.submit(Some(encoder.finish()));
+
+        self.queue.write_buffer(
+            &self.buffers.time_uniform,
+            0,
+            bytemuck::cast_slice(&[TimeUniform {
+                time: self.frame_time(),
+            }]),
+        );
+
+        let target = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Autotune Render Target"),
+            size: wgpu::Extent3d {
+                width: self.size.width.max(1),
+                height: self.size.height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.color_format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut chosen = &QUALITY_PRESETS[0];
+        for preset in &QUALITY_PRESETS {
+            preset.apply(&mut self.params.ray_params);
+            update_ray_params_buffer(self);
+
+            let mut times: Vec<f64> = (0..AUTOTUNE_FRAMES)
+                .map(|_| self.time_frame(&view))
+                .collect();
+            times.sort_by(f64::total_cmp);
+            let median_ms = times[times.len() / 2];
+
+            let fits = median_ms <= AUTOTUNE_FRAME_BUDGET_MS;
+            println!(
+                "autotune: {} {:.2} ms{}",
+                preset.name,
+                median_ms,
+                if fits { "" } else { ", over budget" }
+            );
+            if !fits {
+                break;
+            }
+            chosen = preset;
+        }
+
+        chosen.apply(&mut self.params.ray_params);
+        update_ray_params_buffer(self);
+        chosen
+    }
+
+    // Milliseconds one render pass into `view` takes
+    fn time_frame(&mut self, view: &wgpu::TextureView) -> f64 {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Autotune Encoder"),
+            });
+        self.encode_render_pass(&mut encoder, view);
+        if let Some(timer) = &mut self.gpu_timer {
+            timer.resolve(&mut encoder);
+        }
+
+        let start = Instant::now();
+        self.queue.submit(Some(encoder.finish()));
+        if let Some(timer) = &mut self.gpu_timer {
+            timer.submitted();
+        }
+        self.device.poll(wgpu::Maintain::Wait);
+        let wall_ms = start.elapsed().as_secs_f64() * 1e3;
+
+        match &mut self.gpu_timer {
+            Some(timer) => {
+                timer.poll(&self.device);
+                timer
+                    .last()
+                    .map_or(wall_ms, |timings| timings.render_ns / 1e6)
+            }
+            None => wall_ms,
+        }
+    }
+}
@@ -0,0 +1,550 @@
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI, TAU};
+
+use nalgebra::{Vector2, Vector3};
+
+use crate::collections::consts::{
+    BOOKMARK_TRANSITION_SECS, CAMERA_DAMPING, CAMERA_REST_SPEED, MAX_ZOOM, MIN_ZOOM,
+};
+use crate::collections::structs::ViewParams;
+use crate::util::time::Instant;
+
+// CPU copies of the camera math in frag.wgsl, keep the two in sync
+
+// Camera start position before rotation, and the point it orbits
+const CAM_ORIGIN: Vector3<f32> = Vector3::new(0.0, 20.0, -200.0);
+const LOOK_AT: Vector3<f32> = Vector3::new(0.0, 0.0, 0.0);
+// World units moved per unit of view x_shift/y_shift
+const PAN_SCALE: f32 = 100.0;
+// Orthographic view width in world units per unit of screen uv
+const ORTHO_SCALE: f32 = 200.0;
+// Height of the terrain plane, planeSDF in terrainSDF
+const TERRAIN_PLANE_Y: f32 = -1.0;
+// Side of the world square the terrain texture covers, centred on the origin
+const TERRAIN_WORLD_SIZE: f32 = 400.0;
+
+// CAD style preset views, all orbit the origin with pan reset
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum CanonicalView {
+    Top,
+    Front,
+    Side,
+    Isometric,
+}
+
+impl CanonicalView {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            CanonicalView::Top => "Top",
+            CanonicalView::Front => "Front",
+            CanonicalView::Side => "Side",
+            CanonicalView::Isometric => "Isometric",
+        }
+    }
+
+    // `current` with the rotation and pan replaced by this view's
+    pub(crate) fn snapped(&self, current: &ViewParams) -> ViewParams {
+        let base_elevation = base_elevation();
+
+        let (pitch, yaw) = match self {
+            CanonicalView::Top => (max_pitch(), 0.0),
+            // Keep the default camera's slight elevation so the ground plane stays visible
+            CanonicalView::Front => (0.0, 0.0),
+            CanonicalView::Side => (0.0, FRAC_PI_2),
+            // Elevation of atan(1/sqrt(2)) looking down the diagonal
+            CanonicalView::Isometric => ((0.5f32).sqrt().atan() - base_elevation, FRAC_PI_4),
+        };
+
+        ViewParams {
+            x_shift: 0.0,
+            y_shift: 0.0,
+            cam_pos: [0.0; 3],
+            x_rot: yaw,
+            y_rot: pitch,
+            z_rot: 0.0,
+            ..*current
+        }
+    }
+}
+
+// VIEW mode key movement, each axis a fraction of its top speed. Held keys accelerate
+// it and damp applies the drag, so letting go slows the camera down instead of stopping it
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct CameraVelocity {
+    pub(crate) pan: Vector2<f32>,
+    pub(crate) rot: Vector2<f32>,
+    pub(crate) zoom: f32,
+    // Along the camera's forward and right vectors
+    pub(crate) fly: Vector2<f32>,
+    pub(crate) roll: f32,
+}
+
+impl CameraVelocity {
+    // Implicit so a long frame can't overshoot zero
+    pub(crate) fn damp(&mut self, dt: f32) {
+        let decay = 1.0 / (1.0 + CAMERA_DAMPING * dt);
+        self.pan *= decay;
+        self.rot *= decay;
+        self.zoom *= decay;
+        self.fly *= decay;
+        self.roll *= decay;
+        if !self.is_moving() {
+            *self = Self::default();
+        }
+    }
+
+    pub(crate) fn is_moving(&self) -> bool {
+        self.pan
+            .amax()
+            .max(self.rot.amax())
+            .max(self.zoom.abs())
+            .max(self.fly.amax())
+            .max(self.roll.abs())
+            > CAMERA_REST_SPEED
+    }
+}
+
+// Eased move from one view to another, e.g. to a recalled bookmark
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ViewTransition {
+    from: ViewParams,
+    to: ViewParams,
+    start: Instant,
+    // Last view handed out, view_params differing from it means the user took over
+    last: ViewParams,
+}
+
+impl ViewTransition {
+    pub(crate) fn new(from: ViewParams, to: ViewParams) -> Self {
+        Self {
+            from,
+            to,
+            start: Instant::now(),
+            last: from,
+        }
+    }
+
+    pub(crate) fn interrupted(&self, current: &ViewParams) -> bool {
+        *current != self.last
+    }
+
+    // The view for this frame, and whether the transition has finished
+    pub(crate) fn step(&mut self) -> (ViewParams, bool) {
+        self.step_at(self.start.elapsed().as_secs_f32())
+    }
+
+    // step with the seconds since the transition started given, so the tests can pick them
+    fn step_at(&mut self, elapsed: f32) -> (ViewParams, bool) {
+        let t = (elapsed / BOOKMARK_TRANSITION_SECS).min(1.0);
+        let view = if t < 1.0 {
+            lerp_view(&self.from, &self.to, t * t * (3.0 - 2.0 * t))
+        } else {
+            self.to
+        };
+        self.last = view;
+        (view, t >= 1.0)
+    }
+}
+
+// Rotations take the short way round, zoom is interpolated geometrically
+// so zooming in and out by the same factor take the same time
+fn lerp_view(a: &ViewParams, b: &ViewParams, t: f32) -> ViewParams {
+    let lerp = |a: f32, b: f32| a + (b - a) * t;
+    let lerp_angle = |a: f32, b: f32| wrap_angle(a + wrap_angle(b - a) * t);
+    let (za, zb) = (clamp_zoom(a.zoom), clamp_zoom(b.zoom));
+
+    ViewParams {
+        x_shift: lerp(a.x_shift, b.x_shift),
+        y_shift: lerp(a.y_shift, b.y_shift),
+        zoom: za * (zb / za).powf(t),
+        x_rot: lerp_angle(a.x_rot, b.x_rot),
+        y_rot: lerp_angle(a.y_rot, b.y_rot),
+        time_modifier: lerp(a.time_modifier, b.time_modifier),
+        fov_degrees: lerp(a.fov_degrees, b.fov_degrees),
+        cam_pos: std::array::from_fn(|i| lerp(a.cam_pos[i], b.cam_pos[i])),
+        z_rot: lerp_angle(a.z_rot, b.z_rot),
+        // A flag, switches straight away
+        ortho: b.ortho,
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Ray {
+    pub(crate) origin: Vector3<f32>,
+    pub(crate) dir: Vector3<f32>,
+}
+
+impl Ray {
+    // Where the ray meets the terrain plane, None if it runs parallel or away from it
+    pub(crate) fn terrain_hit(&self) -> Option<Vector3<f32>> {
+        let t = (TERRAIN_PLANE_Y - self.origin.y) / self.dir.y;
+        (t.is_finite() && t >= 0.0).then(|| self.origin + self.dir * t)
+    }
+}
+
+// World x/z -> terrain texture uv, 0.0 -> 1.0 across TERRAIN_WORLD_SIZE. terrain_uv
+// in frag.wgsl
+pub(crate) fn terrain_uv(pos: Vector3<f32>) -> Vector2<f32> {
+    Vector2::new(pos.x, pos.z) / TERRAIN_WORLD_SIZE + Vector2::repeat(0.5)
+}
+
+// scale_aspect + zoom: pixel -> aspect corrected screen uv, x in -1.0 -> 1.0, y up
+pub(crate) fn screen_to_uv(
+    pixel: winit::dpi::PhysicalPosition<f64>,
+    size: winit::dpi::PhysicalSize<u32>,
+    view_params: &ViewParams,
+) -> Vector2<f32> {
+    let (w, h) = (size.width as f32, size.height as f32);
+    let ndc = Vector2::new(
+        2.0 * pixel.x as f32 / w - 1.0,
+        2.0 * pixel.y as f32 / h - 1.0,
+    );
+
+    Vector2::new(ndc.x, -ndc.y * (h / w)) / view_params.zoom
+}
+
+// render(): the world space ray the fragment at `pixel` marches along
+pub(crate) fn screen_to_ray(
+    pixel: winit::dpi::PhysicalPosition<f64>,
+    size: winit::dpi::PhysicalSize<u32>,
+    view_params: &ViewParams,
+) -> Ray {
+    let uv = screen_to_uv(pixel, size, view_params);
+
+    let mut ro = rotate3d(CAM_ORIGIN, view_params.y_rot, view_params.x_rot);
+    let (camr, camu, camf) = roll(cam_basis(ro, LOOK_AT), view_params.z_rot);
+
+    // Panning and flying move the camera and its target together, so the basis is unchanged
+    ro += (camr * view_params.x_shift + camu * view_params.y_shift) * PAN_SCALE;
+    ro += Vector3::from(view_params.cam_pos);
+
+    if view_params.ortho > 0.5 {
+        return Ray {
+            origin: ro + (camr * uv.x + camu * uv.y) * ORTHO_SCALE,
+            dir: camf,
+        };
+    }
+
+    let d = Vector3::new(
+        uv.x * view_params.fov_degrees,
+        uv.y * view_params.fov_degrees,
+        1.0,
+    )
+    .normalize();
+
+    Ray {
+        origin: ro,
+        dir: camr * d.x + camu * d.y + camf * d.z,
+    }
+}
+
+// CAM_ORIGIN already sits this far above the horizon before any pitch
+fn base_elevation() -> f32 {
+    CAM_ORIGIN.y.atan2(-CAM_ORIGIN.z)
+}
+
+// Largest y_rot before the camera passes over the pole. Straight down
+// makes get_cam's basis degenerate, so stop just short
+pub(crate) fn max_pitch() -> f32 {
+    FRAC_PI_2 - base_elevation() - 1e-3
+}
+
+// Keep zoom positive and finite, the pan step divides by it
+pub(crate) fn clamp_zoom(zoom: f32) -> f32 {
+    if zoom.is_nan() {
+        return MIN_ZOOM;
+    }
+    zoom.clamp(MIN_ZOOM, MAX_ZOOM)
+}
+
+// Keep rotations bounded to -PI -> PI as they accumulate
+pub(crate) fn wrap_angle(angle: f32) -> f32 {
+    (angle + PI).rem_euclid(TAU) - PI
+}
+
+// World space right, up and forward of the camera
+pub(crate) fn camera_basis(view_params: &ViewParams) -> (Vector3<f32>, Vector3<f32>, Vector3<f32>) {
+    let ro = rotate3d(CAM_ORIGIN, view_params.y_rot, view_params.x_rot);
+    roll(cam_basis(ro, LOOK_AT), view_params.z_rot)
+}
+
+// get_cam(): right, up, forward
+fn cam_basis(
+    ro: Vector3<f32>,
+    look_at: Vector3<f32>,
+) -> (Vector3<f32>, Vector3<f32>, Vector3<f32>) {
+    let camf = (look_at - ro).normalize();
+    let camr = Vector3::y().cross(&camf).normalize();
+    let camu = camf.cross(&camr);
+
+    (camr, camu, camf)
+}
+
+// roll_cam(): turn right and up around forward
+fn roll(
+    (camr, camu, camf): (Vector3<f32>, Vector3<f32>, Vector3<f32>),
+    angle: f32,
+) -> (Vector3<f32>, Vector3<f32>, Vector3<f32>) {
+    let (s, c) = angle.sin_cos();
+    (camr * c + camu * s, camu * c - camr * s, camf)
+}
+
+// rotate3d(), wgsl's `v * m` multiplies by the transpose of the
+// column-major matrices it builds
+fn rotate3d(v: Vector3<f32>, angle_x: f32, angle_y: f32) -> Vector3<f32> {
+    let (sx, cx) = angle_x.sin_cos();
+    let (sy, cy) = angle_y.sin_cos();
+
+    let rx = Vector3::new(v.x, cx * v.y - sx * v.z, sx * v.y + cx * v.z);
+
+    Vector3::new(cy * rx.x + sy * rx.z, rx.y, -sy * rx.x + cy * rx.z)
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+    use winit::dpi::{PhysicalPosition, PhysicalSize};
+
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::collections::consts::{MAX_MOVE_DT, PAN_SPEED, ZOOM_SPEED};
+    use crate::init::init_functions::init_params;
+
+    const SIZE: PhysicalSize<u32> = PhysicalSize::new(200, 100);
+
+    // The default view with fov 1, so a pixel's uv is its direction before normalizing
+    fn view(ortho: f32) -> ViewParams {
+        ViewParams {
+            fov_degrees: 1.0,
+            ortho,
+            ..init_params().view_params
+        }
+    }
+
+    fn ray_at(x: f64, y: f64, view_params: &ViewParams) -> Ray {
+        screen_to_ray(PhysicalPosition::new(x, y), SIZE, view_params)
+    }
+
+    fn assert_close(a: Vector3<f32>, b: Vector3<f32>) {
+        assert!((a - b).norm() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn center_pixel_looks_forward() {
+        let view_params = view(0.0);
+        let (_, _, camf) = camera_basis(&view_params);
+        let ray = ray_at(100.0, 50.0, &view_params);
+
+        assert_close(ray.origin, CAM_ORIGIN);
+        assert_close(ray.dir, camf);
+        assert_close(camf, (LOOK_AT - CAM_ORIGIN).normalize());
+    }
+
+    #[test]
+    fn corners_spread_by_aspect() {
+        let view_params = view(0.0);
+        let (camr, camu, camf) = camera_basis(&view_params);
+        // A 2:1 window, so the corners are at uv x = -1/1 and y = 0.5/-0.5
+        let corners = [
+            (0.0, 0.0, -1.0, 0.5),
+            (200.0, 0.0, 1.0, 0.5),
+            (0.0, 100.0, -1.0, -0.5),
+            (200.0, 100.0, 1.0, -0.5),
+        ];
+
+        for (x, y, right, up) in corners {
+            let ray = ray_at(x, y, &view_params);
+            let expected = (camr * right + camu * up + camf).normalize();
+            assert_close(ray.origin, CAM_ORIGIN);
+            assert_close(ray.dir, expected);
+        }
+
+        // Top left: (-1, 0.5, 1) normalized is (-2/3, 1/3, 2/3) in the camera basis
+        let dir = ray_at(0.0, 0.0, &view_params).dir;
+        assert!((dir.dot(&camr) + 2.0 / 3.0).abs() < 1e-4);
+        assert!((dir.dot(&camu) - 1.0 / 3.0).abs() < 1e-4);
+        assert!((dir.dot(&camf) - 2.0 / 3.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn ortho_rays_are_parallel() {
+        let view_params = view(1.0);
+        let (camr, camu, camf) = camera_basis(&view_params);
+
+        let center = ray_at(100.0, 50.0, &view_params);
+        assert_close(center.origin, CAM_ORIGIN);
+        assert_close(center.dir, camf);
+
+        let corners = [
+            (0.0, 0.0, -1.0, 0.5),
+            (200.0, 0.0, 1.0, 0.5),
+            (0.0, 100.0, -1.0, -0.5),
+            (200.0, 100.0, 1.0, -0.5),
+        ];
+        for (x, y, right, up) in corners {
+            let ray = ray_at(x, y, &view_params);
+            assert_close(
+                ray.origin,
+                CAM_ORIGIN + (camr * right + camu * up) * ORTHO_SCALE,
+            );
+            assert_close(ray.dir, camf);
+        }
+    }
+
+    #[test]
+    fn center_ray_hits_the_middle_of_the_terrain() {
+        let ray = ray_at(100.0, 50.0, &view(0.0));
+        let hit = ray.terrain_hit().unwrap();
+        // The ray through the origin carries on down to the plane at y = -1, a
+        // twentieth of its drop further on, so z = 200 / 20
+        assert_close(hit, Vector3::new(0.0, -1.0, 10.0));
+        let uv = terrain_uv(hit);
+        assert!(
+            (uv.x - 0.5).abs() < 1e-4 && (uv.y - 0.525).abs() < 1e-4,
+            "{:?}",
+            uv
+        );
+
+        // Pointing up, away from the plane
+        let up = Ray {
+            origin: CAM_ORIGIN,
+            dir: Vector3::y(),
+        };
+        assert!(up.terrain_hit().is_none());
+    }
+
+    #[test]
+    fn x_rot_stays_wrapped_and_continuous() {
+        // A frame's worth of held left then right arrow, enough to go round several times
+        let step = 0.3;
+        let mut x_rot = 0.0f32;
+        for i in 0..400 {
+            let delta = if i < 200 { step } else { -step };
+            let next = wrap_angle(x_rot + delta);
+            assert!((-PI..PI).contains(&next), "{}", next);
+            // Across the wrap the angle jumps by TAU, which wraps back to the step
+            assert!((wrap_angle(next - x_rot) - delta).abs() < 1e-4);
+            x_rot = next;
+        }
+    }
+
+    #[test]
+    fn held_zoom_keys_stay_in_range() {
+        // integrate_camera_velocity's zoom and pan steps at full velocity and the longest frame
+        let zoom_step = |zoom: f32, direction: f32| {
+            clamp_zoom(zoom + direction * ZOOM_SPEED * MAX_MOVE_DT * clamp_zoom(zoom))
+        };
+        for direction in [1.0, -1.0] {
+            let mut zoom = 1.0;
+            for _ in 0..1000 {
+                zoom = zoom_step(zoom, direction);
+                assert!((MIN_ZOOM..=MAX_ZOOM).contains(&zoom), "{}", zoom);
+                assert!((PAN_SPEED * MAX_MOVE_DT / clamp_zoom(zoom)).is_finite());
+            }
+            let limit = if direction > 0.0 { MAX_ZOOM } else { MIN_ZOOM };
+            assert_eq!(zoom, limit);
+        }
+    }
+
+    #[test]
+    fn nan_zoom_clamps_to_the_minimum() {
+        assert_eq!(clamp_zoom(f32::NAN), MIN_ZOOM);
+        assert_eq!(clamp_zoom(f32::INFINITY), MAX_ZOOM);
+        assert_eq!(clamp_zoom(0.0), MIN_ZOOM);
+        assert!((PAN_SPEED * MAX_MOVE_DT / clamp_zoom(f32::NAN)).is_finite());
+    }
+
+    // Somewhere away from the default view on every field
+    fn moved_view() -> ViewParams {
+        ViewParams {
+            x_shift: 0.4,
+            y_shift: -0.2,
+            zoom: 3.0,
+            x_rot: 2.5,
+            y_rot: 0.3,
+            time_modifier: 2.0,
+            fov_degrees: 0.8,
+            cam_pos: [10.0, 5.0, -3.0],
+            z_rot: -0.7,
+            ortho: 1.0,
+        }
+    }
+
+    #[test]
+    fn recalled_bookmark_ends_on_the_stored_view() {
+        // bookmark_controls: Ctrl+digit stores, the digit alone starts a transition to it
+        let mut bookmarks = HashMap::new();
+        bookmarks.insert(6u8, moved_view());
+        let current = init_params().view_params;
+
+        let mut transition = ViewTransition::new(current, bookmarks[&6]);
+        let (view, done) = transition.step_at(BOOKMARK_TRANSITION_SECS * 0.5);
+        assert!(!done);
+        assert!(!transition.interrupted(&view));
+        assert_eq!(
+            transition.step_at(BOOKMARK_TRANSITION_SECS),
+            (moved_view(), true)
+        );
+    }
+
+    #[test]
+    fn lerp_view_ends_on_its_inputs() {
+        let (a, b) = (init_params().view_params, moved_view());
+        let start = lerp_view(&a, &b, 0.0);
+        let end = lerp_view(&a, &b, 1.0);
+
+        for (lerped, expected) in [(start, a), (end, b)] {
+            assert!((lerped.x_shift - expected.x_shift).abs() < 1e-5);
+            assert!((lerped.y_shift - expected.y_shift).abs() < 1e-5);
+            assert!((lerped.zoom - expected.zoom).abs() < 1e-5);
+            assert!((lerped.x_rot - expected.x_rot).abs() < 1e-5);
+            assert!((lerped.y_rot - expected.y_rot).abs() < 1e-5);
+            assert!((lerped.z_rot - expected.z_rot).abs() < 1e-5);
+            assert!((lerped.fov_degrees - expected.fov_degrees).abs() < 1e-5);
+            assert_close(
+                Vector3::from(lerped.cam_pos),
+                Vector3::from(expected.cam_pos),
+            );
+        }
+        assert_eq!(start.ortho, b.ortho);
+    }
+
+    #[test]
+    fn angles_lerp_the_short_way_round() {
+        let a = ViewParams {
+            x_rot: 3.0,
+            ..init_params().view_params
+        };
+        let b = ViewParams { x_rot: -3.0, ..a };
+
+        // 3.0 -> -3.0 is 0.28 across the wrap, not 6.0 back through zero
+        let half = lerp_view(&a, &b, 0.5).x_rot;
+        assert!((half.abs() - PI).abs() < 1e-5, "{}", half);
+        for i in 0..=10 {
+            let x_rot = lerp_view(&a, &b, i as f32 / 10.0).x_rot;
+            assert!(x_rot.abs() >= 3.0 - 1e-5, "{}", x_rot);
+        }
+    }
+
+    #[test]
+    fn damping_reaches_rest() {
+        let mut velocity = CameraVelocity {
+            zoom: 1.0,
+            ..Default::default()
+        };
+        assert!(velocity.is_moving());
+        for _ in 0..1000 {
+            velocity.damp(1.0 / 60.0);
+        }
+        assert_eq!(velocity, CameraVelocity::default());
+
+        // Just under the rest speed on one axis counts as stopped and snaps to Default
+        let mut slow = CameraVelocity {
+            roll: CAMERA_REST_SPEED * 0.5,
+            ..Default::default()
+        };
+        assert!(!slow.is_moving());
+        slow.damp(0.0);
+        assert_eq!(slow, CameraVelocity::default());
+    }
+}
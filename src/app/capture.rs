@@ -0,0 +1,215 @@
+use std::path::Path;
+
+use anyhow::{bail, Context};
+
+use crate::updates::param_updates::update_overlay_params_buffer;
+use crate::updates::readback::map_readback;
+
+use super::state::State;
+
+impl<'a> State<'a> {
+    // Render the current frame, text overlay included, offscreen and save it as a PNG
+    pub(crate) fn capture_frame(&mut self, path: &Path) -> anyhow::Result<()> {
+        let pixels = self.read_frame()?;
+        self.save_frame(path, &pixels)
+    }
+
+    // Save window sized RGBA8 rows as a PNG
+    pub(crate) fn save_frame(&self, path: &Path, pixels: &[u8]) -> anyhow::Result<()> {
+        let width = self.size.width.max(1);
+        let height = self.size.height.max(1);
+        image::save_buffer(path, pixels, width, height, image::ColorType::Rgba8)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    // Render the current frame offscreen and read it back as RGBA8 rows.
+    // The composition guides are for framing on screen and left out
+    fn read_frame(&mut self) -> anyhow::Result<Vec<u8>> {
+        // write_buffer lands before the next submit, so the capture sees the guides off
+        // and the restored value is in place for the next on screen frame
+        let show_guides = std::mem::take(&mut self.params.overlay_params.show_guides);
+        update_overlay_params_buffer(self);
+        let pixels =
+            self.read_target(|state, encoder, view| state.encode_render_pass(encoder, view));
+        self.params.overlay_params.show_guides = show_guides;
+        update_overlay_params_buffer(self);
+        pixels
+    }
+
+    // Draw the frame with `encode` and the text overlay on top into an offscreen target,
+    // and read it back as RGBA8 rows. The target uses the surface format so the
+    // existing pipelines can draw into it
+    pub(crate) fn read_target(
+        &mut self,
+        encode: impl FnOnce(&Self, &mut wgpu::CommandEncoder, &wgpu::TextureView),
+    ) -> anyhow::Result<Vec<u8>> {
+        let width = self.size.width.max(1);
+        let height = self.size.height.max(1);
+        let format = self.color_format;
+
+        let swap_red_blue = swaps_red_blue(format)?;
+
+        let target = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Capture Render Target"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let bytes_per_row = padded_bytes_per_row(width);
+        let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Capture CPU Readable Buffer"),
+            size: (bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Capture Encoder"),
+            });
+        encode(self, &mut encoder, &view);
+        self.encode_text_overlay(&mut encoder, &view);
+
+        encoder.copy_texture_to_buffer(
+            target.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            target.size(),
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        map_readback(&self.device, &readback)?;
+        Ok(take_rgba_rows(&readback, width, swap_red_blue))
+    }
+
+    // Write the full precision terrain texture to an OpenEXR file, one f32 channel
+    // per texture channel. The constant alpha channel is dropped
+    pub(crate) fn export_terrain(&self, path: &Path) -> anyhow::Result<()> {
+        use exr::prelude::*;
+
+        let width = self.terrain_resolution;
+        let height = self.terrain_resolution;
+        // Rgba32Float
+        let unpadded_bytes_per_row = width * 16;
+        let bytes_per_row =
+            unpadded_bytes_per_row.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+
+        let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Terrain Export CPU Readable Buffer"),
+            size: (bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Terrain Export Encoder"),
+            });
+        encoder.copy_texture_to_buffer(
+            self.textures.terrain_tex.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            self.textures.terrain_tex.size(),
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        map_readback(&self.device, &readback)?;
+        let mut texels: Vec<[f32; 4]> = Vec::with_capacity((width * height) as usize);
+        {
+            let data = readback.slice(..).get_mapped_range();
+            for row in data.chunks(bytes_per_row as usize) {
+                texels.extend_from_slice(bytemuck::cast_slice(
+                    &row[..unpadded_bytes_per_row as usize],
+                ));
+            }
+        }
+        readback.unmap();
+
+        let mut attributes = LayerAttributes::named("terrain");
+        attributes.comments = Some(Text::from(
+            "generate_terrain.wgsl fbmD output: height is the fbm value, \
+             grad_x/grad_y its analytic gradient along the texture x/y axes",
+        ));
+
+        let channels = SpecificChannels::build()
+            .with_channel("height")
+            .with_channel("grad_x")
+            .with_channel("grad_y")
+            .with_pixel_fn(|pos: Vec2<usize>| {
+                let [h, gx, gy, _] = texels[pos.y() * width as usize + pos.x()];
+                (h, gx, gy)
+            });
+
+        let layer = Layer::new(
+            (width as usize, height as usize),
+            attributes,
+            Encoding::SMALL_LOSSLESS,
+            channels,
+        );
+
+        Image::from_layer(layer)
+            .write()
+            .to_file(path)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+// Whether an 8 bit frame in `format` is stored BGRA and needs red and blue swapping to
+// come out RGBA
+pub(crate) fn swaps_red_blue(format: wgpu::TextureFormat) -> anyhow::Result<bool> {
+    match format {
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => Ok(true),
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => Ok(false),
+        _ => bail!("Can't capture surface format {:?}", format),
+    }
+}
+
+// copy_texture_to_buffer rows must be padded to COPY_BYTES_PER_ROW_ALIGNMENT
+pub(crate) fn padded_bytes_per_row(width: u32) -> u32 {
+    (width * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+}
+
+// A mapped buffer of padded 8 bit rows as tightly packed RGBA8, then unmapped
+pub(crate) fn take_rgba_rows(buffer: &wgpu::Buffer, width: u32, swap_red_blue: bool) -> Vec<u8> {
+    let unpadded_bytes_per_row = (width * 4) as usize;
+    let mut pixels = Vec::new();
+    {
+        let data = buffer.slice(..).get_mapped_range();
+        pixels.reserve(data.len());
+        for row in data.chunks(padded_bytes_per_row(width) as usize) {
+            pixels.extend_from_slice(&row[..unpadded_bytes_per_row]);
+        }
+    }
+    buffer.unmap();
+
+    if swap_red_blue {
+        for px in pixels.chunks_exact_mut(4) {
+            px.swap(0, 2);
+        }
+    }
+    pixels
+}
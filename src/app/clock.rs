@@ -0,0 +1,207 @@
+use crate::util::time::Instant;
+
+// Where the clock reads the time from. Fixed steps `dt` seconds per update so the
+// output doesn't depend on the frame rate
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum TimeSource {
+    Realtime,
+    Fixed { dt: f32, frame: u64 },
+}
+
+// The scene time behind the time uniform. The source gives unscaled seconds, pausing
+// holds them and time_modifier is applied as a rate on top
+#[derive(Debug, Clone)]
+pub(crate) struct SceneClock {
+    source: TimeSource,
+    start: Instant,
+    paused: bool,
+    // While paused the unscaled time holds `held`, `offset` is the real time spent paused
+    held: f64,
+    offset: f64,
+    rate: f32,
+    // Scaled and unscaled time when the rate last changed or the time was set, so a new
+    // rate carries on from the current time without a jump
+    anchor: (f64, f64),
+}
+
+impl SceneClock {
+    pub(crate) fn new(source: TimeSource) -> Self {
+        Self {
+            source,
+            start: Instant::now(),
+            paused: false,
+            held: 0.0,
+            offset: 0.0,
+            rate: 1.0,
+            anchor: (0.0, 0.0),
+        }
+    }
+
+    pub(crate) fn source(&self) -> TimeSource {
+        self.source
+    }
+
+    // Start over from 0.0 on `source`
+    pub(crate) fn restart(&mut self, source: TimeSource) {
+        self.source = source;
+        self.set_time(0.0);
+    }
+
+    pub(crate) fn time(&self) -> f32 {
+        self.time_at(self.elapsed())
+    }
+
+    // Step a fixed source on by one frame, the real clock moves by itself
+    pub(crate) fn advance(&mut self) {
+        if self.paused {
+            return;
+        }
+        if let TimeSource::Fixed { frame, .. } = &mut self.source {
+            *frame += 1;
+        }
+    }
+
+    pub(crate) fn set_rate(&mut self, rate: f32) {
+        self.set_rate_at(rate, self.elapsed());
+    }
+
+    // Resuming continues from the held time rather than jumping forward. Returns
+    // whether the clock is now paused
+    pub(crate) fn toggle_pause(&mut self) -> bool {
+        self.toggle_pause_at(self.elapsed())
+    }
+
+    // Continue the clock from `time`, keeping it held if paused
+    pub(crate) fn set_time(&mut self, time: f32) {
+        self.set_time_at(time, self.elapsed());
+    }
+
+    fn elapsed(&self) -> f64 {
+        self.start.elapsed().as_secs_f64()
+    }
+
+    // The *_at versions take the real seconds since `start`, so the tests can pick them
+    fn unscaled_at(&self, elapsed: f64) -> f64 {
+        if self.paused {
+            return self.held;
+        }
+        match self.source {
+            TimeSource::Realtime => elapsed - self.offset,
+            TimeSource::Fixed { dt, frame } => frame as f64 * dt as f64,
+        }
+    }
+
+    fn time_at(&self, elapsed: f64) -> f32 {
+        let (scaled, unscaled) = self.anchor;
+        (scaled + (self.unscaled_at(elapsed) - unscaled) * self.rate as f64) as f32
+    }
+
+    fn set_rate_at(&mut self, rate: f32, elapsed: f64) {
+        if rate == self.rate {
+            return;
+        }
+        self.anchor = (self.time_at(elapsed) as f64, self.unscaled_at(elapsed));
+        self.rate = rate;
+    }
+
+    fn toggle_pause_at(&mut self, elapsed: f64) -> bool {
+        if self.paused {
+            self.offset = elapsed - self.held;
+        } else {
+            self.held = self.unscaled_at(elapsed);
+        }
+        self.paused = !self.paused;
+        self.paused
+    }
+
+    fn set_time_at(&mut self, time: f32, elapsed: f64) {
+        self.anchor = (time as f64, self.unscaled_at(elapsed));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // N fixed frames, with the rate changed part way through like the comma/period keys
+    fn run_fixed(frames: u32) -> f32 {
+        let mut clock = SceneClock::new(TimeSource::Fixed {
+            dt: 1.0 / 60.0,
+            frame: 0,
+        });
+        for i in 0..frames {
+            if i == frames / 3 {
+                clock.set_rate(1.25);
+            }
+            clock.advance();
+        }
+        clock.time()
+    }
+
+    #[test]
+    fn fixed_runs_are_bit_identical() {
+        for frames in [1, 60, 10_000, 1_000_000] {
+            assert_eq!(run_fixed(frames).to_bits(), run_fixed(frames).to_bits());
+        }
+    }
+
+    #[test]
+    fn fixed_time_is_frame_times_dt() {
+        let dt = 1.0 / 60.0;
+        let mut clock = SceneClock::new(TimeSource::Fixed { dt, frame: 0 });
+        for _ in 0..1_000_000 {
+            clock.advance();
+        }
+        assert_eq!(clock.time(), (1_000_000.0 * dt as f64) as f32);
+    }
+
+    #[test]
+    fn pause_holds_the_time() {
+        let mut clock = SceneClock::new(TimeSource::Realtime);
+        let before = clock.time_at(2.0);
+        assert!(clock.toggle_pause_at(2.0));
+        assert_eq!(clock.time_at(3.0), before);
+        assert_eq!(clock.time_at(10.0), before);
+    }
+
+    #[test]
+    fn resume_carries_on_from_the_held_time() {
+        let mut clock = SceneClock::new(TimeSource::Realtime);
+        clock.toggle_pause_at(2.0);
+        assert!(!clock.toggle_pause_at(10.0));
+        assert_eq!(clock.time_at(10.0), 2.0);
+        assert_eq!(clock.time_at(11.5), 3.5);
+    }
+
+    #[test]
+    fn paused_fixed_clock_ignores_advance() {
+        let mut clock = SceneClock::new(TimeSource::Fixed { dt: 0.5, frame: 0 });
+        clock.advance();
+        clock.toggle_pause();
+        for _ in 0..10 {
+            clock.advance();
+        }
+        assert_eq!(clock.time(), 0.5);
+        clock.toggle_pause();
+        clock.advance();
+        assert_eq!(clock.time(), 1.0);
+    }
+
+    #[test]
+    fn rate_change_doesnt_jump() {
+        let mut clock = SceneClock::new(TimeSource::Realtime);
+        clock.set_rate_at(2.0, 4.0);
+        assert_eq!(clock.time_at(4.0), 4.0);
+        assert_eq!(clock.time_at(5.0), 6.0);
+    }
+
+    #[test]
+    fn set_time_while_paused_stays_held() {
+        let mut clock = SceneClock::new(TimeSource::Realtime);
+        clock.toggle_pause_at(1.0);
+        clock.set_time_at(7.0, 3.0);
+        assert_eq!(clock.time_at(9.0), 7.0);
+        clock.toggle_pause_at(9.0);
+        assert_eq!(clock.time_at(10.0), 8.0);
+    }
+}
@@ -0,0 +1,293 @@
+use std::f32::consts::PI;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use anyhow::Context;
+
+use crate::collections::consts::{
+    CONFIG_PATH, MAX_EPSILON, MAX_EPSILON_SCALE, MAX_IDLE_FPS, MAX_OCTAVES, MAX_TERRAIN_AMP,
+    MAX_TERRAIN_FREQ, MAX_TIME_MODIFIER, MAX_ZOOM, MIN_EPSILON, MIN_IDLE_FPS, MIN_MAX_STEPS,
+    MIN_OCTAVES, MIN_TERRAIN_FREQ, MIN_TIME_MODIFIER, MIN_ZOOM,
+};
+use crate::collections::key_bindings::KeyBindings;
+use crate::collections::structs::{FrameRateParams, Params, RayParams, TerrainParams, ViewParams};
+use crate::init::init_functions::init_params;
+
+use super::camera::max_pitch;
+
+// The user tweakable part of Params, everything else is derived or session state
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ParamsFile {
+    ray_params: RayParams,
+    view_params: ViewParams,
+    terrain_params: TerrainParams,
+    #[serde(default)]
+    key_bindings: KeyBindings,
+    #[serde(default)]
+    frame_rate: FrameRateParams,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    quality_preset: Option<String>,
+}
+
+impl Params {
+    // Values outside the ranges the controls allow are replaced by their defaults
+    pub(crate) fn load(path: &Path) -> anyhow::Result<Params> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let file: ParamsFile =
+            toml::from_str(&text).with_context(|| format!("Failed to parse {}", path.display()))?;
+
+        let mut params = init_params();
+        params.ray_params = validate_ray_params(file.ray_params, params.ray_params);
+        params.view_params = validate_view_params(file.view_params, params.view_params);
+        params.terrain_params = validate_terrain_params(file.terrain_params, params.terrain_params);
+        params.key_bindings = file.key_bindings;
+        params.frame_rate = validate_frame_rate(file.frame_rate, params.frame_rate);
+        params.quality_preset = file.quality_preset;
+        Ok(params)
+    }
+
+    pub(crate) fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let file = ParamsFile {
+            ray_params: self.ray_params,
+            view_params: self.view_params,
+            terrain_params: self.terrain_params,
+            key_bindings: self.key_bindings.clone(),
+            frame_rate: self.frame_rate,
+            quality_preset: self.quality_preset.clone(),
+        };
+        let text = toml::to_string_pretty(&file)?;
+        std::fs::write(path, text).with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+// An explicit path has to exist, the default config.toml is only loaded if present
+pub(crate) fn load_params(path: Option<&Path>) -> anyhow::Result<Params> {
+    match path {
+        Some(path) => Params::load(path),
+        None if Path::new(CONFIG_PATH).exists() => Params::load(Path::new(CONFIG_PATH)),
+        None => Ok(init_params()),
+    }
+}
+
+fn in_range<T: PartialOrd + Copy + std::fmt::Debug>(
+    name: &str,
+    value: T,
+    range: RangeInclusive<T>,
+    default: T,
+) -> T {
+    if range.contains(&value) {
+        value
+    } else {
+        eprintln!(
+            "config: {} = {:?} is outside {:?}, using {:?}",
+            name, value, range, default
+        );
+        default
+    }
+}
+
+// A TOML integer is an i64. Rather than failing the whole config on a seed outside
+// u32, wrap it the way NextSeed's wrapping_add does
+pub(crate) fn wrapping_seed<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<u32, D::Error> {
+    let seed = <i64 as serde::Deserialize>::deserialize(deserializer)?;
+    let wrapped = seed as u32;
+    if i64::from(wrapped) != seed {
+        eprintln!(
+            "config: terrain_params.seed = {} is outside the u32 range, using {}",
+            seed, wrapped
+        );
+    }
+    Ok(wrapped)
+}
+
+fn validate_ray_params(loaded: RayParams, default: RayParams) -> RayParams {
+    RayParams {
+        epsilon: in_range(
+            "ray_params.epsilon",
+            loaded.epsilon,
+            MIN_EPSILON..=MAX_EPSILON,
+            default.epsilon,
+        ),
+        max_dist: in_range(
+            "ray_params.max_dist",
+            loaded.max_dist,
+            1.0..=f32::MAX,
+            default.max_dist,
+        ),
+        max_steps: in_range(
+            "ray_params.max_steps",
+            loaded.max_steps,
+            MIN_MAX_STEPS..=f32::MAX,
+            default.max_steps,
+        ),
+        epsilon_scale: in_range(
+            "ray_params.epsilon_scale",
+            loaded.epsilon_scale,
+            0.0..=MAX_EPSILON_SCALE,
+            default.epsilon_scale,
+        ),
+    }
+}
+
+fn validate_view_params(loaded: ViewParams, default: ViewParams) -> ViewParams {
+    let finite = -f32::MAX..=f32::MAX;
+    let cam_pos = if loaded.cam_pos.iter().all(|c| c.is_finite()) {
+        loaded.cam_pos
+    } else {
+        eprintln!(
+            "config: view_params.cam_pos = {:?} isn't finite, using {:?}",
+            loaded.cam_pos, default.cam_pos
+        );
+        default.cam_pos
+    };
+
+    ViewParams {
+        x_shift: in_range(
+            "view_params.x_shift",
+            loaded.x_shift,
+            finite.clone(),
+            default.x_shift,
+        ),
+        y_shift: in_range(
+            "view_params.y_shift",
+            loaded.y_shift,
+            finite,
+            default.y_shift,
+        ),
+        zoom: in_range(
+            "view_params.zoom",
+            loaded.zoom,
+            MIN_ZOOM..=MAX_ZOOM,
+            default.zoom,
+        ),
+        x_rot: in_range("view_params.x_rot", loaded.x_rot, -PI..=PI, default.x_rot),
+        y_rot: in_range(
+            "view_params.y_rot",
+            loaded.y_rot,
+            -max_pitch()..=max_pitch(),
+            default.y_rot,
+        ),
+        time_modifier: in_range(
+            "view_params.time_modifier",
+            loaded.time_modifier,
+            MIN_TIME_MODIFIER..=MAX_TIME_MODIFIER,
+            default.time_modifier,
+        ),
+        fov_degrees: in_range(
+            "view_params.fov_degrees",
+            loaded.fov_degrees,
+            1.0..=179.0,
+            default.fov_degrees,
+        ),
+        cam_pos,
+        z_rot: in_range("view_params.z_rot", loaded.z_rot, -PI..=PI, default.z_rot),
+        ortho: in_range("view_params.ortho", loaded.ortho, 0.0..=1.0, default.ortho),
+    }
+}
+
+// An active cap below the idle rate would make activity slow things down, so it's dropped
+fn validate_frame_rate(loaded: FrameRateParams, default: FrameRateParams) -> FrameRateParams {
+    let idle_fps = in_range(
+        "frame_rate.idle_fps",
+        loaded.idle_fps,
+        MIN_IDLE_FPS..=MAX_IDLE_FPS,
+        default.idle_fps,
+    );
+    let active_fps = loaded.active_fps.and_then(|fps| {
+        let capped = (idle_fps..=f64::MAX).contains(&fps);
+        if !capped {
+            eprintln!(
+                "config: frame_rate.active_fps = {} is below idle_fps {}, leaving it uncapped",
+                fps, idle_fps
+            );
+        }
+        capped.then_some(fps)
+    });
+
+    FrameRateParams {
+        idle_fps,
+        active_fps,
+    }
+}
+
+fn validate_terrain_params(loaded: TerrainParams, default: TerrainParams) -> TerrainParams {
+    TerrainParams {
+        f1_octaves: in_range(
+            "terrain_params.f1_octaves",
+            loaded.f1_octaves,
+            MIN_OCTAVES..=MAX_OCTAVES,
+            default.f1_octaves,
+        ),
+        f2_octaves: in_range(
+            "terrain_params.f2_octaves",
+            loaded.f2_octaves,
+            MIN_OCTAVES..=MAX_OCTAVES,
+            default.f2_octaves,
+        ),
+        f3_octaves: in_range(
+            "terrain_params.f3_octaves",
+            loaded.f3_octaves,
+            MIN_OCTAVES..=MAX_OCTAVES,
+            default.f3_octaves,
+        ),
+        // Any u32 is a usable seed, wrapping_seed already brought it into range
+        seed: loaded.seed,
+        f1_freq: in_range(
+            "terrain_params.f1_freq",
+            loaded.f1_freq,
+            MIN_TERRAIN_FREQ..=MAX_TERRAIN_FREQ,
+            default.f1_freq,
+        ),
+        f1_amp: in_range(
+            "terrain_params.f1_amp",
+            loaded.f1_amp,
+            0.0..=MAX_TERRAIN_AMP,
+            default.f1_amp,
+        ),
+        f2_freq: in_range(
+            "terrain_params.f2_freq",
+            loaded.f2_freq,
+            MIN_TERRAIN_FREQ..=MAX_TERRAIN_FREQ,
+            default.f2_freq,
+        ),
+        f2_amp: in_range(
+            "terrain_params.f2_amp",
+            loaded.f2_amp,
+            0.0..=MAX_TERRAIN_AMP,
+            default.f2_amp,
+        ),
+        f3_freq: in_range(
+            "terrain_params.f3_freq",
+            loaded.f3_freq,
+            MIN_TERRAIN_FREQ..=MAX_TERRAIN_FREQ,
+            default.f3_freq,
+        ),
+        f3_amp: in_range(
+            "terrain_params.f3_amp",
+            loaded.f3_amp,
+            0.0..=MAX_TERRAIN_AMP,
+            default.f3_amp,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seed(toml: &str) -> u32 {
+        toml::from_str::<TerrainParams>(toml).unwrap().seed
+    }
+
+    #[test]
+    fn seeds_outside_u32_wrap() {
+        assert_eq!(seed("seed = 42"), 42);
+        assert_eq!(seed("seed = 4294967295"), u32::MAX);
+        assert_eq!(seed("seed = -1"), u32::MAX);
+        assert_eq!(seed("seed = 4294967296"), 0);
+        assert_eq!(seed("f1_octaves = 3"), 0);
+    }
+}
@@ -1,34 +1,83 @@
 use std::collections::HashSet;
-use std::thread;
-use std::time;
 
+use nalgebra::{Rotation3, Unit, Vector2, Vector3};
 use winit::keyboard::{KeyCode, PhysicalKey};
 
+use crate::collections::consts::{
+    AMBIENT_SPEED, CAMERA_ACCELERATION, CONFIG_PATH, EPSILON_SCALE_STEP, EPSILON_STEP,
+    EXPOSURE_SPEED, FLY_SPEED, FOG_DENSITY_SPEED, FOG_START_SPEED, HORIZON_BLEND_SPEED,
+    HUE_ROTATE_SPEED, MAX_EPSILON, MAX_EPSILON_SCALE, MAX_EXPOSURE, MAX_FOG_DENSITY, MAX_MOVE_DT,
+    MAX_OCTAVES, MAX_SUN_ELEVATION, MAX_TERRAIN_AMP, MAX_TERRAIN_FREQ, MAX_TIME_MODIFIER,
+    MIN_EPSILON, MIN_EXPOSURE, MIN_MAX_STEPS, MIN_OCTAVES, MIN_SUN_ELEVATION, MIN_TERRAIN_FREQ,
+    MIN_TIME_MODIFIER, MOUSE_SENSITIVITY, PAN_SPEED, PIXELS_PER_LINE, RECORD_FRAMES, ROLL_SPEED,
+    ROTATE_SPEED, SUN_ROTATE_SPEED, TERRAIN_AMP_STEP, TERRAIN_ANIM_SPEED_STEP, TERRAIN_FREQ_STEP,
+    TIME_MODIFIER_STEP, ZOOM_SPEED, ZOOM_STEP,
+};
+use crate::collections::key_bindings::Action;
+use crate::collections::structs::{Params, ViewParams, SDF_MODE_NAMES, TONEMAP_NAMES};
+use crate::init::init_functions::init_params;
+use crate::updates::param_updates::update_brush_params_buffer;
+use crate::updates::param_updates::update_clip_params_buffer;
+use crate::updates::param_updates::update_fog_params_buffer;
+use crate::updates::param_updates::update_light_params_buffer;
+use crate::updates::param_updates::update_overlay_params_buffer;
+use crate::updates::param_updates::update_precision_params_buffer;
 use crate::updates::param_updates::update_ray_params_buffer;
+use crate::updates::param_updates::update_sdf_params_buffer;
+use crate::updates::param_updates::update_sky_params_buffer;
+use crate::updates::param_updates::update_terrain_params_buffer;
+use crate::updates::param_updates::update_texture_view_params_buffer;
+use crate::updates::param_updates::update_tone_params_buffer;
 use crate::updates::param_updates::update_view_params_buffer;
+use crate::util::time;
 
+#[cfg(feature = "gamepad")]
+use crate::collections::consts::GAMEPAD_LOOK_SPEED;
+
+#[cfg(feature = "gamepad")]
+use super::gamepad::GamepadInput;
+
+use super::camera::{
+    camera_basis, clamp_zoom, max_pitch, screen_to_ray, terrain_uv, wrap_angle, CameraVelocity,
+    CanonicalView, ViewTransition,
+};
+use super::debug_readback::DebugReadback;
 use super::state::State;
+use super::view_token::{copy_to_clipboard, encode_view_token};
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Copy, Clone)]
 pub(crate) enum KeyboardMode {
     DEBUG,
     VIEW,
     TERRAIN,
     RAY,
+    PAINT,
+    LIGHT,
     PRINT,
 }
 
 #[derive(Debug, Clone)]
 pub(crate) struct KeyboardState {
     keys: HashSet<winit::keyboard::PhysicalKey>,
+    // Keys pressed since the last call to update_controls,
+    // used for toggles that should only fire once per press
+    just_pressed: HashSet<winit::keyboard::PhysicalKey>,
     mode: KeyboardMode,
+    // Index of the terrain octave count the arrows adjust in TERRAIN mode
+    selected_layer: usize,
+    // Multiplier on the VIEW mode movement and rotation rates
+    pub(crate) speed: f32,
 }
 
 impl KeyboardState {
     pub(crate) fn new() -> Self {
         Self {
             keys: HashSet::new(),
+            just_pressed: HashSet::new(),
             mode: KeyboardMode::PRINT,
+            selected_layer: 0,
+            speed: 1.0,
         }
     }
 
@@ -36,9 +85,26 @@ impl KeyboardState {
         self.keys.contains(&key)
     }
 
+    pub(crate) fn shift_pressed(&self) -> bool {
+        self.key_pressed(PhysicalKey::Code(KeyCode::ShiftLeft))
+            || self.key_pressed(PhysicalKey::Code(KeyCode::ShiftRight))
+    }
+
+    pub(crate) fn ctrl_pressed(&self) -> bool {
+        self.key_pressed(PhysicalKey::Code(KeyCode::ControlLeft))
+            || self.key_pressed(PhysicalKey::Code(KeyCode::ControlRight))
+    }
+
+    pub(crate) fn key_just_pressed(&self, key: winit::keyboard::PhysicalKey) -> bool {
+        self.just_pressed.contains(&key)
+    }
+
     pub(crate) fn handle_keyboard_input(&mut self, input: &winit::event::KeyEvent) {
         let key = input.physical_key;
         if input.state == winit::event::ElementState::Pressed {
+            if !input.repeat {
+                self.just_pressed.insert(key);
+            }
             self.keys.insert(key);
         } else {
             self.keys.remove(&key);
@@ -47,6 +113,11 @@ impl KeyboardState {
 
     pub(crate) fn clear_keys(&mut self) {
         self.keys.clear();
+        self.just_pressed.clear();
+    }
+
+    pub(crate) fn clear_just_pressed(&mut self) {
+        self.just_pressed.clear();
     }
 
     pub(crate) fn get_keys(&self) -> &HashSet<winit::keyboard::PhysicalKey> {
@@ -62,276 +133,1657 @@ impl KeyboardState {
     }
 }
 
-pub(crate) fn print_gpu_data<T: bytemuck::Pod + std::fmt::Debug>(
-    device: &wgpu::Device,
-    buffer: &wgpu::Buffer,
-    obj_label: &str,
-) {
-    // Map the buffer for reading
-    let buffer_slice = buffer.slice(..);
-    let (tx, rx) = futures::channel::oneshot::channel();
-
-    buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
-        tx.send(result).unwrap();
-    });
-
-    println!("buffer size: {:?}", buffer.size());
-    // Wait for the GPU to finish executing the commands
-    device.poll(wgpu::Maintain::Wait);
-    // Wait for the buffer to be mapped
-    let result = futures::executor::block_on(rx);
-
-    match result {
-        Ok(_) => {
-            let buf_view = buffer_slice.get_mapped_range();
-            let data: &[T] = bytemuck::cast_slice(&buf_view);
-
-            // Print the boids current properties
-            for (i, obj) in data.iter().enumerate() {
-                println!("{} {}:\n{:?}", obj_label, i, obj);
-            }
+#[derive(Debug, Clone)]
+pub(crate) struct MouseState {
+    // None while the cursor is outside the window
+    cursor: Option<winit::dpi::PhysicalPosition<f64>>,
+    left_down: bool,
+    // Mouse-look, with the raw motion accumulated since the last update
+    look: bool,
+    look_delta: (f64, f64),
+    pub(crate) sensitivity: f32,
+    // Wheel lines scrolled since the last update
+    scroll: f32,
+}
 
-            drop(buf_view);
-            buffer.unmap();
+impl MouseState {
+    pub(crate) fn new() -> Self {
+        Self {
+            cursor: None,
+            left_down: false,
+            look: false,
+            look_delta: (0.0, 0.0),
+            sensitivity: MOUSE_SENSITIVITY,
+            scroll: 0.0,
         }
-        Err(e) => eprintln!("Error retrieving gpu data: {:?}", e),
     }
-}
-
-pub(crate) fn print_gpu_interleave_two_buffers<
-    T: bytemuck::Pod + std::fmt::Debug + std::iter::IntoIterator,
->(
-    device: &wgpu::Device,
-    buffer1: &wgpu::Buffer,
-    buffer2: &wgpu::Buffer,
-) where
-    <T as IntoIterator>::Item: std::fmt::Debug,
-{
-    // Map the buffer for reading
-    let buffer_slice1 = buffer1.slice(..);
-    let (tx1, rx1) = futures::channel::oneshot::channel();
-
-    buffer_slice1.map_async(wgpu::MapMode::Read, move |result| {
-        tx1.send(result).unwrap();
-    });
 
-    // Wait for the GPU to finish executing the commands
-    device.poll(wgpu::Maintain::Wait);
-    // Wait for the buffer to be mapped
-    let result1 = futures::executor::block_on(rx1);
-
-    // Map the buffer for reading
-    let buffer_slice2 = buffer2.slice(..);
-    let (tx2, rx2) = futures::channel::oneshot::channel();
+    pub(crate) fn handle_cursor_moved(&mut self, position: winit::dpi::PhysicalPosition<f64>) {
+        self.cursor = Some(position);
+    }
 
-    buffer_slice2.map_async(wgpu::MapMode::Read, move |result| {
-        tx2.send(result).unwrap();
-    });
+    pub(crate) fn handle_cursor_left(&mut self) {
+        self.cursor = None;
+    }
 
-    // Wait for the GPU to finish executing the commands
-    device.poll(wgpu::Maintain::Wait);
-    // Wait for the buffer to be mapped
-    let result2 = futures::executor::block_on(rx2);
+    pub(crate) fn handle_mouse_input(
+        &mut self,
+        button: winit::event::MouseButton,
+        state: winit::event::ElementState,
+    ) {
+        if button == winit::event::MouseButton::Left {
+            self.left_down = state == winit::event::ElementState::Pressed;
+        }
+    }
 
-    match (result1, result2) {
-        (Ok(_), Ok(_)) => {
-            let buf_view1 = buffer_slice1.get_mapped_range();
-            let data1: &[T] = bytemuck::cast_slice(&buf_view1);
-            let buf_view2 = buffer_slice2.get_mapped_range();
-            let data2: &[T] = bytemuck::cast_slice(&buf_view2);
+    pub(crate) fn handle_mouse_motion(&mut self, delta: (f64, f64)) {
+        if self.look {
+            self.look_delta.0 += delta.0;
+            self.look_delta.1 += delta.1;
+        }
+    }
 
-            let mut flattened_data1 = Vec::new();
-            let mut flattened_data2 = Vec::new();
+    pub(crate) fn take_look_delta(&mut self) -> (f64, f64) {
+        std::mem::take(&mut self.look_delta)
+    }
 
-            for i in data1.into_iter() {
-                flattened_data1.extend(i.to_owned());
-            }
+    pub(crate) fn handle_mouse_wheel(&mut self, delta: winit::event::MouseScrollDelta) {
+        self.scroll += match delta {
+            winit::event::MouseScrollDelta::LineDelta(_, y) => y,
+            winit::event::MouseScrollDelta::PixelDelta(pos) => pos.y as f32 / PIXELS_PER_LINE,
+        };
+    }
 
-            for i in data2.into_iter() {
-                flattened_data2.extend(i.to_owned());
-            }
+    pub(crate) fn take_scroll(&mut self) -> f32 {
+        std::mem::take(&mut self.scroll)
+    }
 
-            for (idx, item) in flattened_data1
-                .iter()
-                .zip(flattened_data2.iter())
-                .enumerate()
-            {
-                println!("\n{idx}:\n{:?}", item.0);
-                println!("{:?}", item.1);
-            }
+    pub(crate) fn is_pressed(&self) -> bool {
+        self.left_down
+    }
 
-            drop(buf_view1);
-            drop(buf_view2);
-            buffer1.unmap();
-            buffer2.unmap();
-        }
-        (Err(e), Ok(_)) => eprintln!("Error retrieving gpu data from buffer1: {:?}", e),
-        (Ok(_), Err(e)) => eprintln!("Error retrieving gpu data from buffer2: {:?}", e),
-        (Err(e1), Err(e2)) => {
-            eprintln!("Error retrieving gpu data from buffer1: {:?}", e1);
-            eprintln!("Error retrieving gpu data from buffer2: {:?}", e2);
-        }
+    pub(crate) fn clear_buttons(&mut self) {
+        self.left_down = false;
     }
 }
 
 pub(crate) fn update_controls(state: &mut State) {
-    if state.controls.key_pressed(PhysicalKey::Code(KeyCode::KeyD)) {
+    // Shift+digits are left for the modes to use
+    let digits_free = !state.controls.shift_pressed();
+
+    // The mode switch is ignored in VIEW mode, its default D strafes there
+    let in_view_mode = matches!(state.controls.get_mode(), KeyboardMode::VIEW);
+
+    if !in_view_mode && action_pressed(state, Action::ModeDebug) {
         state.controls.set_mode(KeyboardMode::DEBUG);
-    } else if state
-        .controls
-        .key_pressed(PhysicalKey::Code(KeyCode::Digit1))
-    {
+    } else if digits_free && action_pressed(state, Action::ModeTerrain) {
         state.controls.set_mode(KeyboardMode::TERRAIN);
-    } else if state
-        .controls
-        .key_pressed(PhysicalKey::Code(KeyCode::Digit2))
-    {
+    } else if digits_free && action_pressed(state, Action::ModeView) {
         state.controls.set_mode(KeyboardMode::VIEW);
-    } else if state
-        .controls
-        .key_pressed(PhysicalKey::Code(KeyCode::Digit3))
-    {
+    } else if digits_free && action_pressed(state, Action::ModeRay) {
         state.controls.set_mode(KeyboardMode::RAY);
-    } else if state.controls.key_pressed(PhysicalKey::Code(KeyCode::KeyP)) {
+    } else if digits_free && action_pressed(state, Action::ModePaint) {
+        state.controls.set_mode(KeyboardMode::PAINT);
+    } else if digits_free && action_pressed(state, Action::ModeLight) {
+        state.controls.set_mode(KeyboardMode::LIGHT);
+    } else if action_pressed(state, Action::ModePrint) {
         state.controls.set_mode(KeyboardMode::PRINT);
     }
 
+    if !matches!(state.controls.get_mode(), KeyboardMode::VIEW) {
+        set_mouse_look(state, false);
+    }
+
+    view_transition_controls(state);
+
+    if action_just_pressed(state, Action::TogglePause) {
+        state.toggle_pause();
+    }
+
+    if action_just_pressed(state, Action::ToggleFullscreen) {
+        state.toggle_fullscreen();
+    }
+
+    if action_just_pressed(state, Action::Screenshot) {
+        let secs = time::SystemTime::now()
+            .duration_since(time::SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let path = std::path::PathBuf::from(format!("screenshot-{}.png", secs));
+        match state.capture_frame(&path) {
+            Ok(()) => println!("Saved {}", path.display()),
+            Err(e) => eprintln!("Screenshot failed: {:#}", e),
+        }
+    }
+
+    // Starts a RECORD_FRAMES frame recording, or stops the current one early
+    if action_just_pressed(state, Action::Record) {
+        if state.recording.is_some() {
+            state.stop_recording();
+        } else {
+            let secs = time::SystemTime::now()
+                .duration_since(time::SystemTime::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default();
+            let dir = std::path::PathBuf::from(format!("recording-{}", secs));
+            match state.start_recording(&dir, RECORD_FRAMES) {
+                Ok(()) => println!("Recording {} frames to {}", RECORD_FRAMES, dir.display()),
+                Err(e) => eprintln!("Recording failed: {:#}", e),
+            }
+        }
+    }
+
+    if action_just_pressed(state, Action::SaveConfig) {
+        match state.params.save(std::path::Path::new(CONFIG_PATH)) {
+            Ok(()) => println!("Saved {}", CONFIG_PATH),
+            Err(e) => eprintln!("Saving params failed: {:#}", e),
+        }
+    }
+
+    if action_just_pressed(state, Action::CyclePresentMode) {
+        state.cycle_present_mode();
+    }
+
+    if action_just_pressed(state, Action::ToggleGuides) {
+        set_param(
+            state,
+            "overlay_params.show_guides",
+            |p| &mut p.overlay_params.show_guides,
+            |v| 1 - v,
+        );
+        update_overlay_params_buffer(state);
+    }
+
+    if action_just_pressed(state, Action::ToggleParamOverlay) {
+        state.show_param_overlay = !state.show_param_overlay;
+    }
+
+    if action_just_pressed(state, Action::ToggleFrameStats) {
+        state.show_frame_stats = !state.show_frame_stats;
+    }
+
+    if action_just_pressed(state, Action::ToggleSupersampling) {
+        let factor = if state.ss_factor == 1 { 2 } else { 1 };
+        state.set_ss_factor(factor);
+    }
+
+    if action_just_pressed(state, Action::CycleSdf) {
+        let sdf = &mut state.params.sdf_params;
+        sdf.mode = (sdf.mode + 1) % SDF_MODE_NAMES.len() as u32;
+        println!("SDF: {}", SDF_MODE_NAMES[sdf.mode as usize]);
+        update_sdf_params_buffer(state);
+    }
+
+    if action_just_pressed(state, Action::ResetParams) {
+        reset_params(state);
+    }
+
     match state.controls.get_mode() {
         KeyboardMode::DEBUG => debug_controls(state),
         KeyboardMode::VIEW => view_controls(state),
         KeyboardMode::TERRAIN => terrain_controls(state),
         KeyboardMode::RAY => ray_controls(state),
+        KeyboardMode::PAINT => paint_controls(state),
+        KeyboardMode::LIGHT => light_controls(state),
         KeyboardMode::PRINT => print_controls(state),
     }
+
+    state.controls.clear_just_pressed();
+    // Scrolling outside VIEW mode shouldn't zoom later on
+    state.mouse.take_scroll();
 }
 
-fn debug_controls(state: &mut State) {
-    let pressed = state.controls.get_keys();
+// Home puts the active mode's params back to their init_params values
+fn reset_params(state: &mut State) {
+    let mode = *state.controls.get_mode();
+    if !reset_mode_params(&mut state.params, state.log_param_changes, mode) {
+        return;
+    }
 
-    if pressed.contains(&PhysicalKey::Code(KeyCode::KeyS)) {
-        print_gpu_data::<[f32; 4]>(
-            &state.device,
-            &state.buffers.cpu_read_generic_debug,
-            "Debug",
-        );
-        thread::sleep(time::Duration::from_millis(50));
-        state.controls.set_mode(KeyboardMode::VIEW);
-    } else if pressed.contains(&PhysicalKey::Code(KeyCode::Digit1)) {
-        print_gpu_data::<[[f32; 4]; 512]>(
-            &state.device,
-            &state.buffers.cpu_read_debug_array1,
-            "Debug",
+    match mode {
+        KeyboardMode::VIEW => {
+            state.view_transition = None;
+            state.canonical_view = None;
+            state.camera_velocity = CameraVelocity::default();
+            println!("Reset view params");
+            update_view_params_buffer(state);
+        }
+        KeyboardMode::RAY => {
+            println!("Reset ray params");
+            update_ray_params_buffer(state);
+        }
+        KeyboardMode::LIGHT => {
+            println!("Reset light, fog, sky and tone params");
+            update_light_params_buffer(state);
+            update_fog_params_buffer(state);
+            update_sky_params_buffer(state);
+            update_tone_params_buffer(state);
+        }
+        _ => {}
+    }
+}
+
+// The params half of reset_params, false for modes without a reset
+fn reset_mode_params(params: &mut Params, log: bool, mode: KeyboardMode) -> bool {
+    let defaults = init_params();
+    match mode {
+        KeyboardMode::VIEW => {
+            set_field(
+                params,
+                log,
+                "view_params",
+                |p| &mut p.view_params,
+                |_| defaults.view_params,
+            );
+        }
+        KeyboardMode::RAY => {
+            set_field(
+                params,
+                log,
+                "ray_params",
+                |p| &mut p.ray_params,
+                |_| defaults.ray_params,
+            );
+        }
+        KeyboardMode::LIGHT => {
+            set_field(
+                params,
+                log,
+                "light_params",
+                |p| &mut p.light_params,
+                |_| defaults.light_params,
+            );
+            set_field(
+                params,
+                log,
+                "fog_params",
+                |p| &mut p.fog_params,
+                |_| defaults.fog_params,
+            );
+            set_field(
+                params,
+                log,
+                "sky_params",
+                |p| &mut p.sky_params,
+                |_| defaults.sky_params,
+            );
+            // encode_srgb belongs to the color target, not the user
+            set_field(
+                params,
+                log,
+                "tone_params.exposure",
+                |p| &mut p.tone_params.exposure,
+                |_| defaults.tone_params.exposure,
+            );
+            set_field(
+                params,
+                log,
+                "tone_params.tonemap",
+                |p| &mut p.tone_params.tonemap,
+                |_| defaults.tone_params.tonemap,
+            );
+        }
+        _ => return false,
+    }
+    true
+}
+
+fn debug_controls(state: &mut State) {
+    if action_just_pressed(state, Action::ToggleTextureView) {
+        set_param(
+            state,
+            "texture_view_params.enabled",
+            |p| &mut p.texture_view_params.enabled,
+            |v| 1 - v,
         );
-        thread::sleep(time::Duration::from_millis(50));
-        state.controls.set_mode(KeyboardMode::VIEW);
-    } else if pressed.contains(&PhysicalKey::Code(KeyCode::Digit2)) {
-        print_gpu_data::<[[f32; 4]; 512]>(
-            &state.device,
-            &state.buffers.cpu_read_debug_array2,
-            "Debug",
+        update_texture_view_params_buffer(state);
+    } else if action_just_pressed(state, Action::ToggleColormap) {
+        set_param(
+            state,
+            "texture_view_params.colormap",
+            |p| &mut p.texture_view_params.colormap,
+            |v| 1 - v,
         );
-        thread::sleep(time::Duration::from_millis(50));
-        state.controls.set_mode(KeyboardMode::VIEW);
-    } else if pressed.contains(&PhysicalKey::Code(KeyCode::Digit3)) {
-        print_gpu_interleave_two_buffers::<[[f32; 4]; 512]>(
-            &state.device,
-            &state.buffers.cpu_read_debug_array1,
-            &state.buffers.cpu_read_debug_array2,
+        update_texture_view_params_buffer(state);
+    } else if action_just_pressed(state, Action::ToggleStepHeatmap) {
+        set_param(
+            state,
+            "texture_view_params.step_heatmap",
+            |p| &mut p.texture_view_params.step_heatmap,
+            |v| 1 - v,
         );
-        thread::sleep(time::Duration::from_millis(50));
+        update_texture_view_params_buffer(state);
+        if state.params.texture_view_params.step_heatmap != 0 {
+            println!(
+                "Step heatmap: blue 1 step -> red {} steps, scale along the bottom",
+                state.params.ray_params.max_steps
+            );
+        }
+    } else if action_just_pressed(state, Action::ExportTerrain) {
+        let path = std::path::Path::new("terrain.exr");
+        match state.export_terrain(path) {
+            Ok(()) => println!("Saved {}", path.display()),
+            Err(e) => eprintln!("Terrain export failed: {:#}", e),
+        }
+    }
+
+    // Clicking picks the pixel the generic dump reads
+    if state.mouse.left_down {
+        if let Some(cursor) = state.mouse.cursor {
+            let pixel = winit::dpi::PhysicalPosition::new(
+                (cursor.x.max(0.0) as u32).min(state.size.width.saturating_sub(1)),
+                (cursor.y.max(0.0) as u32).min(state.size.height.saturating_sub(1)),
+            );
+            if state.debug_pixel != Some(pixel) {
+                state.set_debug_pixel(Some(pixel));
+            }
+        }
+    }
+
+    let readback = if action_pressed(state, Action::DumpGeneric) {
+        Some(DebugReadback::Generic)
+    } else if action_pressed(state, Action::DumpArray1) {
+        Some(DebugReadback::Array1)
+    } else if action_pressed(state, Action::DumpArray2) {
+        Some(DebugReadback::Array2)
+    } else if action_pressed(state, Action::DumpInterleaved) {
+        Some(DebugReadback::Interleaved)
+    } else {
+        None
+    };
+
+    // Printed from State::update once the buffers are mapped, or with Shift
+    // held written to debug-<buffer>-<secs>.csv
+    if let Some(readback) = readback {
+        let csv_path = state.controls.shift_pressed().then(|| {
+            let secs = time::SystemTime::now()
+                .duration_since(time::SystemTime::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default();
+            std::path::PathBuf::from(format!("debug-{}-{}.csv", readback.name(), secs))
+        });
+        state.request_debug_readback(readback, csv_path);
         state.controls.set_mode(KeyboardMode::VIEW);
     }
 }
 
+type ParamField<T> = fn(&mut Params) -> &mut T;
+
+// Apply `change` to a single param, logging `name: old -> new` when
+// param change logging (--log-params) is enabled
+fn set_param<T: Copy + PartialEq + std::fmt::Debug>(
+    state: &mut State,
+    name: &str,
+    field: ParamField<T>,
+    change: impl FnOnce(T) -> T,
+) {
+    let log = state.log_param_changes;
+    set_field(&mut state.params, log, name, field, change);
+}
+
+// set_param on bare Params, for the logic that doesn't need the rest of State
+fn set_field<T: Copy + PartialEq + std::fmt::Debug>(
+    params: &mut Params,
+    log: bool,
+    name: &str,
+    field: ParamField<T>,
+    change: impl FnOnce(T) -> T,
+) {
+    let value = field(params);
+    let old = *value;
+    *value = change(old);
+
+    if log && *value != old {
+        println!("{}: {:?} -> {:?}", name, old, *value);
+    }
+}
+
+// Whether the key bound to `action` is held
+fn action_pressed(state: &State, action: Action) -> bool {
+    let key = state.params.key_bindings.key(action);
+    state.controls.key_pressed(PhysicalKey::Code(key))
+}
+
+// Whether the key bound to `action` went down since the last update_controls
+fn action_just_pressed(state: &State, action: Action) -> bool {
+    let key = state.params.key_bindings.key(action);
+    state.controls.key_just_pressed(PhysicalKey::Code(key))
+}
+
+// Warns on the step that reaches the floor rather than every frame the key stays held
+fn step_with_floor(name: &str, value: f32, delta: f32, min: f32) -> f32 {
+    let stepped = value + delta;
+    if stepped >= min {
+        return stepped;
+    }
+    if value > min {
+        eprintln!("{} can't go below {}", name, min);
+    }
+    min
+}
+
+// A frame of held ArrowUp/ArrowDown with E. Multiplicative since useful values span
+// orders of magnitude, one frame changes it by at most EPSILON_STEP
+fn step_epsilon(epsilon: f32, direction: f32) -> f32 {
+    (epsilon * EPSILON_STEP.powf(direction)).clamp(MIN_EPSILON, MAX_EPSILON)
+}
+
 fn ray_controls(state: &mut State) {
-    let pressed = state.controls.get_keys();
     let mut dval_f = 0.0f32;
 
-    if pressed.contains(&PhysicalKey::Code(KeyCode::ArrowUp)) {
+    if action_pressed(state, Action::Up) {
         dval_f = 1.0f32;
-    } else if pressed.contains(&PhysicalKey::Code(KeyCode::ArrowDown)) {
+    } else if action_pressed(state, Action::Down) {
         dval_f = -1.0f32;
     }
 
-    if pressed.contains(&PhysicalKey::Code(KeyCode::KeyE)) {
-        let maxv = &mut state.params.ray_params.epsilon;
-        *maxv = f32::max(0f32, *maxv + (1.0 * dval_f));
+    if action_pressed(state, Action::Epsilon) {
+        set_param(
+            state,
+            "ray_params.epsilon",
+            |p| &mut p.ray_params.epsilon,
+            |v| step_epsilon(v, dval_f),
+        );
         update_ray_params_buffer(state);
-    } else if pressed.contains(&PhysicalKey::Code(KeyCode::KeyS)) {
-        let maxv = &mut state.params.ray_params.max_steps;
-        *maxv = f32::max(0f32, *maxv + (1.0 * dval_f));
+    } else if action_pressed(state, Action::MaxSteps) {
+        set_param(
+            state,
+            "ray_params.max_steps",
+            |p| &mut p.ray_params.max_steps,
+            |v| step_with_floor("ray_params.max_steps", v, dval_f, MIN_MAX_STEPS),
+        );
+        update_ray_params_buffer(state);
+    } else if action_pressed(state, Action::MaxDist) {
+        let epsilon = state.params.ray_params.epsilon;
+        set_param(
+            state,
+            "ray_params.max_dist",
+            |p| &mut p.ray_params.max_dist,
+            |v| step_with_floor("ray_params.max_dist", v, dval_f, epsilon),
+        );
         update_ray_params_buffer(state);
-    } else if pressed.contains(&PhysicalKey::Code(KeyCode::KeyW)) {
-        let maxv = &mut state.params.ray_params.max_dist;
-        *maxv = f32::max(0f32, *maxv + (1.0 * dval_f));
+    } else if action_pressed(state, Action::EpsilonScale) {
+        set_param(
+            state,
+            "ray_params.epsilon_scale",
+            |p| &mut p.ray_params.epsilon_scale,
+            |v| (v + EPSILON_SCALE_STEP * dval_f).clamp(0.0, MAX_EPSILON_SCALE),
+        );
         update_ray_params_buffer(state);
+    } else if action_pressed(state, Action::SdfParam) {
+        // Tune the parameter of whichever SDF is active
+        match state.params.sdf_params.mode {
+            1 => set_param(
+                state,
+                "sdf_params.mandelbulb_power",
+                |p| &mut p.sdf_params.mandelbulb_power,
+                |v| f32::max(1.0, v + 0.05 * dval_f),
+            ),
+            2 => set_param(
+                state,
+                "sdf_params.gyroid_scale",
+                |p| &mut p.sdf_params.gyroid_scale,
+                |v| f32::max(0.001, v + 0.001 * dval_f),
+            ),
+            3 => set_param(
+                state,
+                "sdf_params.sphere_spacing",
+                |p| &mut p.sdf_params.sphere_spacing,
+                |v| f32::max(1.0, v + 0.5 * dval_f),
+            ),
+            _ => {}
+        }
+        update_sdf_params_buffer(state);
     }
 }
 
 fn terrain_controls(state: &mut State) {
-    let pressed = state.controls.get_keys();
     let mut dval_f = 0.0f32;
 
-    if pressed.contains(&PhysicalKey::Code(KeyCode::ArrowUp)) {
+    if action_pressed(state, Action::Up) {
         dval_f = 1.0f32;
-    } else if pressed.contains(&PhysicalKey::Code(KeyCode::ArrowDown)) {
+    } else if action_pressed(state, Action::Down) {
         dval_f = -1.0f32;
     }
 
-    println!("terrain controls not done yet");
+    if action_just_pressed(state, Action::ToggleTerrainAnimation) {
+        set_param(
+            state,
+            "terrain_anim_params.animated",
+            |p| &mut p.terrain_anim_params.animated,
+            |v| !v,
+        );
+        println!(
+            "Animated terrain: {}",
+            state.params.terrain_anim_params.animated
+        );
+    } else if action_just_pressed(state, Action::ToggleClip) {
+        set_param(
+            state,
+            "clip_params.enabled",
+            |p| &mut p.clip_params.enabled,
+            |v| 1 - v,
+        );
+        update_clip_params_buffer(state);
+    } else if action_just_pressed(state, Action::ToggleLossView) {
+        set_param(
+            state,
+            "precision_params.show_loss",
+            |p| &mut p.precision_params.show_loss,
+            |v| 1 - v,
+        );
+        update_precision_params_buffer(state);
+    } else if action_pressed(state, Action::LossThreshold) {
+        set_param(
+            state,
+            "precision_params.loss_threshold",
+            |p| &mut p.precision_params.loss_threshold,
+            |v| v * 1.1f32.powf(dval_f),
+        );
+        update_precision_params_buffer(state);
+    } else if action_pressed(state, Action::ClipHeight) {
+        set_param(
+            state,
+            "clip_params.height",
+            |p| &mut p.clip_params.height,
+            |v| v + 0.5 * dval_f,
+        );
+        update_clip_params_buffer(state);
+    } else if action_just_pressed(state, Action::NextSeed) {
+        set_param(
+            state,
+            "terrain_params.seed",
+            |p| &mut p.terrain_params.seed,
+            |v| v.wrapping_add(1),
+        );
+        update_terrain_params_buffer(state);
+        state.regenerate_terrain();
+    } else if action_just_pressed(state, Action::RandomSeed) {
+        let seed = state.rng.next_u32();
+        set_param(
+            state,
+            "terrain_params.seed",
+            |p| &mut p.terrain_params.seed,
+            |_| seed,
+        );
+        println!("Terrain seed: {}", seed);
+        update_terrain_params_buffer(state);
+        state.regenerate_terrain();
+    } else if action_pressed(state, Action::AnimationSpeed) {
+        set_param(
+            state,
+            "terrain_anim_params.speed",
+            |p| &mut p.terrain_anim_params.speed,
+            |v| step_animation_speed(v, dval_f),
+        );
+    } else {
+        layer_controls(state);
+    }
+}
+
+// Which setting of the selected noise layer ArrowUp/ArrowDown steps
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LayerField {
+    Octaves,
+    Frequency,
+    Amplitude,
 }
 
-fn view_controls(state: &mut State) {
-    let pressed = state.controls.get_keys();
-    let mz = state.params.view_params.zoom;
+// Shift+1/2/3 selects noise layer f1/f2/f3, ArrowUp/ArrowDown steps its octaves,
+// or its frequency with F held or its amplitude with E held
+fn layer_controls(state: &mut State) {
+    let selects = [
+        Action::SelectLayer1,
+        Action::SelectLayer2,
+        Action::SelectLayer3,
+    ];
 
-    if pressed.contains(&PhysicalKey::Code(KeyCode::ArrowLeft)) {
-        if pressed.contains(&PhysicalKey::Code(KeyCode::ShiftLeft)) {
-            state.params.view_params.x_rot = f32::max(0.0, state.params.view_params.x_rot + 0.1);
-            update_view_params_buffer(state);
-        } else {
-            state.params.view_params.x_shift -= 0.01 / mz;
-            update_view_params_buffer(state);
+    if state.controls.shift_pressed() {
+        for (i, select) in selects.into_iter().enumerate() {
+            if action_just_pressed(state, select) {
+                state.controls.selected_layer = i;
+                println!("Selected terrain layer f{}", i + 1);
+            }
         }
-    } else if pressed.contains(&PhysicalKey::Code(KeyCode::ArrowRight)) {
-        if pressed.contains(&PhysicalKey::Code(KeyCode::ShiftLeft)) {
-            state.params.view_params.x_rot -= 0.1;
-            update_view_params_buffer(state);
-        } else {
-            state.params.view_params.x_shift += 0.01 / mz;
-            update_view_params_buffer(state);
+    }
+
+    let step = if action_just_pressed(state, Action::Up) {
+        1
+    } else if action_just_pressed(state, Action::Down) {
+        -1
+    } else {
+        return;
+    };
+
+    let field = if action_pressed(state, Action::LayerFrequency) {
+        LayerField::Frequency
+    } else if action_pressed(state, Action::LayerAmplitude) {
+        LayerField::Amplitude
+    } else {
+        LayerField::Octaves
+    };
+    let layer = state.controls.selected_layer;
+    let log = state.log_param_changes;
+    if step_layer(&mut state.params, log, layer, field, step) {
+        update_terrain_params_buffer(state);
+        state.regenerate_terrain();
+    }
+}
+
+// One press on `layer`'s `field`. Whether the terrain needs regenerating, false when
+// it was already at the limit and nothing changed
+fn step_layer(params: &mut Params, log: bool, layer: usize, field: LayerField, step: i32) -> bool {
+    let octaves: [(&str, ParamField<i32>); 3] = [
+        ("terrain_params.f1_octaves", |p| {
+            &mut p.terrain_params.f1_octaves
+        }),
+        ("terrain_params.f2_octaves", |p| {
+            &mut p.terrain_params.f2_octaves
+        }),
+        ("terrain_params.f3_octaves", |p| {
+            &mut p.terrain_params.f3_octaves
+        }),
+    ];
+    let freqs: [(&str, ParamField<f32>); 3] = [
+        ("terrain_params.f1_freq", |p| &mut p.terrain_params.f1_freq),
+        ("terrain_params.f2_freq", |p| &mut p.terrain_params.f2_freq),
+        ("terrain_params.f3_freq", |p| &mut p.terrain_params.f3_freq),
+    ];
+    let amps: [(&str, ParamField<f32>); 3] = [
+        ("terrain_params.f1_amp", |p| &mut p.terrain_params.f1_amp),
+        ("terrain_params.f2_amp", |p| &mut p.terrain_params.f2_amp),
+        ("terrain_params.f3_amp", |p| &mut p.terrain_params.f3_amp),
+    ];
+
+    match field {
+        LayerField::Frequency => {
+            let (name, field) = freqs[layer];
+            let old = *field(params);
+            set_field(params, log, name, field, |v| step_layer_freq(v, step));
+            *field(params) != old
         }
-    } else if pressed.contains(&PhysicalKey::Code(KeyCode::ArrowUp)) {
-        if pressed.contains(&PhysicalKey::Code(KeyCode::ShiftLeft)) {
-            state.params.view_params.y_rot = f32::max(0.0, state.params.view_params.y_rot + 0.1);
-            update_view_params_buffer(state);
-        } else {
-            state.params.view_params.y_shift -= 0.01 / mz;
-            update_view_params_buffer(state);
+        LayerField::Amplitude => {
+            let (name, field) = amps[layer];
+            let old = *field(params);
+            set_field(params, log, name, field, |v| step_layer_amp(v, step));
+            *field(params) != old
         }
-    } else if pressed.contains(&PhysicalKey::Code(KeyCode::ArrowDown)) {
-        if pressed.contains(&PhysicalKey::Code(KeyCode::ShiftLeft)) {
-            state.params.view_params.y_rot -= 0.1;
-            update_view_params_buffer(state);
+        LayerField::Octaves => {
+            let (name, field) = octaves[layer];
+            let old = *field(params);
+            set_field(params, log, name, field, |v| step_octaves(v, step));
+            *field(params) != old
+        }
+    }
+}
+
+// One ArrowUp/ArrowDown press on the selected layer, `step` is 1 or -1. Each stays in
+// the range validate_terrain_params accepts from a config
+fn step_octaves(octaves: i32, step: i32) -> i32 {
+    (octaves + step).clamp(MIN_OCTAVES, MAX_OCTAVES)
+}
+
+// Multiplicative so each press is the same relative change at any frequency
+fn step_layer_freq(freq: f32, step: i32) -> f32 {
+    (freq * TERRAIN_FREQ_STEP.powi(step)).clamp(MIN_TERRAIN_FREQ, MAX_TERRAIN_FREQ)
+}
+
+fn step_layer_amp(amp: f32, step: i32) -> f32 {
+    (amp + TERRAIN_AMP_STEP * step as f32).clamp(0.0, MAX_TERRAIN_AMP)
+}
+
+// A frame of held ArrowUp/ArrowDown with the animation speed key, never below stopped
+fn step_animation_speed(speed: f32, direction: f32) -> f32 {
+    f32::max(0.0, speed + TERRAIN_ANIM_SPEED_STEP * direction)
+}
+
+fn paint_controls(state: &mut State) {
+    let mut dval_f = 0.0f32;
+
+    if action_pressed(state, Action::Up) {
+        dval_f = 1.0f32;
+    } else if action_pressed(state, Action::Down) {
+        dval_f = -1.0f32;
+    }
+    let lower = state.controls.shift_pressed();
+
+    if action_pressed(state, Action::BrushRadius) {
+        set_param(
+            state,
+            "brush_params.radius",
+            |p| &mut p.brush_params.radius,
+            |v| (v + dval_f).clamp(1.0, 256.0),
+        );
+    } else if action_pressed(state, Action::BrushStrength) {
+        set_param(
+            state,
+            "brush_params.strength",
+            |p| &mut p.brush_params.strength,
+            |v| f32::max(0.0, v + 0.001 * dval_f),
+        );
+    }
+
+    if !state.mouse.left_down {
+        return;
+    }
+    let Some(cursor) = state.mouse.cursor else {
+        return;
+    };
+    let Some(center) = cursor_to_texel(
+        cursor,
+        state.size,
+        &state.params.view_params,
+        state.terrain_resolution,
+    ) else {
+        return;
+    };
+
+    let brush = &mut state.params.brush_params;
+    brush.center = center;
+    brush.origin = [
+        (center[0] - brush.radius).max(0.0) as u32,
+        (center[1] - brush.radius).max(0.0) as u32,
+    ];
+    brush.direction = if lower { -1.0 } else { 1.0 };
+    update_brush_params_buffer(state);
+    state.brush_pending = true;
+}
+
+// The texel under the cursor, where its ray meets the terrain plane. None when the
+// ray misses the plane or lands outside the area the terrain texture covers
+fn cursor_to_texel(
+    cursor: winit::dpi::PhysicalPosition<f64>,
+    size: winit::dpi::PhysicalSize<u32>,
+    view_params: &ViewParams,
+    terrain_resolution: u32,
+) -> Option<[f32; 2]> {
+    let ray = screen_to_ray(cursor, size, view_params);
+    let uv = terrain_uv(ray.terrain_hit()?);
+
+    if !(0.0..=1.0).contains(&uv.x) || !(0.0..=1.0).contains(&uv.y) {
+        return None;
+    }
+
+    Some([
+        uv.x * terrain_resolution as f32,
+        uv.y * terrain_resolution as f32,
+    ])
+}
+
+// Grab and hide the cursor while mouse-look is on, so it can't leave the window
+pub(crate) fn set_mouse_look(state: &mut State, look: bool) {
+    if state.mouse.look == look {
+        return;
+    }
+
+    let Some(window) = state.window() else {
+        return;
+    };
+    if look {
+        let grabbed = window
+            .set_cursor_grab(winit::window::CursorGrabMode::Locked)
+            .or_else(|_| window.set_cursor_grab(winit::window::CursorGrabMode::Confined));
+        if let Err(e) = grabbed {
+            eprintln!("Could not grab the cursor: {}", e);
+        }
+    } else if let Err(e) = window.set_cursor_grab(winit::window::CursorGrabMode::None) {
+        eprintln!("Could not release the cursor: {}", e);
+    }
+    window.set_cursor_visible(!look);
+
+    state.mouse.look = look;
+    state.mouse.look_delta = (0.0, 0.0);
+    println!("Mouse-look: {}", look);
+}
+
+fn look_controls(state: &mut State) {
+    let (dx, dy) = state.mouse.take_look_delta();
+    if dx == 0.0 && dy == 0.0 {
+        return;
+    }
+
+    let sensitivity = state.mouse.sensitivity;
+    set_param(
+        state,
+        "view_params.x_rot",
+        |p| &mut p.view_params.x_rot,
+        |v| wrap_angle(v - dx as f32 * sensitivity),
+    );
+    // Pitch is clamped rather than wrapped so the view never flips over the poles
+    set_param(
+        state,
+        "view_params.y_rot",
+        |p| &mut p.view_params.y_rot,
+        |v| (v + dy as f32 * sensitivity).clamp(-max_pitch(), max_pitch()),
+    );
+    update_view_params_buffer(state);
+}
+
+// Left stick flies like WASD, right stick turns like mouse-look and the right/left
+// triggers zoom in/out. Works in every mode
+#[cfg(feature = "gamepad")]
+pub(crate) fn gamepad_controls(state: &mut State) {
+    let Some(gamepad) = &mut state.gamepad else {
+        return;
+    };
+    let input = gamepad.poll();
+    if input == GamepadInput::default() {
+        return;
+    }
+    // Keep frames coming at the active rate while a stick is held
+    state.last_input = time::Instant::now();
+
+    let dt = state.frame_dt.min(MAX_MOVE_DT) * state.controls.speed;
+    let (camr, _, camf) = camera_basis(&state.params.view_params);
+    let step = (camf * input.left.1 + camr * input.left.0) * FLY_SPEED * dt;
+    let turn = GAMEPAD_LOOK_SPEED * dt;
+    let zoom = ZOOM_SPEED * dt * clamp_zoom(state.params.view_params.zoom) * input.zoom;
+
+    set_param(
+        state,
+        "view_params.cam_pos",
+        |p| &mut p.view_params.cam_pos,
+        |v| [v[0] + step.x, v[1] + step.y, v[2] + step.z],
+    );
+    set_param(
+        state,
+        "view_params.x_rot",
+        |p| &mut p.view_params.x_rot,
+        |v| wrap_angle(v - input.right.0 * turn),
+    );
+    set_param(
+        state,
+        "view_params.y_rot",
+        |p| &mut p.view_params.y_rot,
+        |v| (v - input.right.1 * turn).clamp(-max_pitch(), max_pitch()),
+    );
+    set_param(
+        state,
+        "view_params.zoom",
+        |p| &mut p.view_params.zoom,
+        |v| clamp_zoom(v + zoom),
+    );
+    update_view_params_buffer(state);
+}
+
+// Ctrl+digit stores the view, the digit alone glides back to it. 1-5 switch
+// modes, so only 6-9 and 0 are slots
+fn bookmark_controls(state: &mut State) {
+    let slots = [
+        (Action::Bookmark6, 6),
+        (Action::Bookmark7, 7),
+        (Action::Bookmark8, 8),
+        (Action::Bookmark9, 9),
+        (Action::Bookmark0, 0),
+    ];
+    for (action, slot) in slots {
+        if !action_just_pressed(state, action) {
+            continue;
+        }
+
+        if state.controls.ctrl_pressed() {
+            state.bookmarks.insert(slot, state.params.view_params);
+            println!("Stored view bookmark {}", slot);
+        } else if let Some(&view) = state.bookmarks.get(&slot) {
+            state.view_transition = Some(ViewTransition::new(state.params.view_params, view));
+            state.canonical_view = None;
+            state.camera_velocity = CameraVelocity::default();
+            println!("Recalled view bookmark {}", slot);
         } else {
-            state.params.view_params.y_shift += 0.01 / mz;
+            println!("No view bookmark in slot {}", slot);
+        }
+    }
+}
+
+// Moves the camera along an in-progress transition, any other view change cancels it
+fn view_transition_controls(state: &mut State) {
+    let Some(transition) = &mut state.view_transition else {
+        return;
+    };
+    if transition.interrupted(&state.params.view_params) {
+        state.view_transition = None;
+        return;
+    }
+
+    let (view, done) = transition.step();
+    if done {
+        state.view_transition = None;
+    }
+    set_param(state, "view_params", |p| &mut p.view_params, |_| view);
+    update_view_params_buffer(state);
+}
+
+// WASD moves the camera along its forward/right vectors, diagonals at the same speed
+fn fly_controls(state: &mut State) {
+    let direction = held_direction(
+        |action| action_pressed(state, action),
+        (Action::MoveForward, Action::MoveBack),
+        (Action::MoveRight, Action::MoveLeft),
+    );
+
+    let accel = CAMERA_ACCELERATION * state.frame_dt.min(MAX_MOVE_DT);
+    state.camera_velocity.fly += direction * accel;
+}
+
+// Q/E tilt the horizon
+fn roll_controls(state: &mut State) {
+    let roll = action_pressed(state, Action::RollLeft) as i32
+        - action_pressed(state, Action::RollRight) as i32;
+
+    let accel = CAMERA_ACCELERATION * state.frame_dt.min(MAX_MOVE_DT);
+    state.camera_velocity.roll += roll as f32 * accel;
+}
+
+fn view_controls(state: &mut State) {
+    if action_just_pressed(state, Action::CopyViewToken) {
+        let token = encode_view_token(&state.params.view_params);
+        println!("View token: {}", token);
+        copy_to_clipboard(token);
+    }
+
+    let presets = [
+        (Action::ViewTop, CanonicalView::Top),
+        (Action::ViewFront, CanonicalView::Front),
+        (Action::ViewSide, CanonicalView::Side),
+        (Action::ViewIsometric, CanonicalView::Isometric),
+    ];
+    for (action, view) in presets {
+        if action_just_pressed(state, action) {
+            set_param(
+                state,
+                "view_params",
+                |p| &mut p.view_params,
+                |v| view.snapped(&v),
+            );
+            state.canonical_view = Some(view);
+            state.camera_velocity = CameraVelocity::default();
+            println!("View: {}", view.name());
             update_view_params_buffer(state);
         }
-    } else if pressed.contains(&PhysicalKey::Code(KeyCode::KeyX)) {
-        state.params.view_params.zoom -= 0.1 * mz;
+    }
+
+    if action_just_pressed(state, Action::ToggleMouseLook) {
+        set_mouse_look(state, !state.mouse.look);
+    }
+
+    if action_just_pressed(state, Action::ToggleOrtho) {
+        set_param(
+            state,
+            "view_params.ortho",
+            |p| &mut p.view_params.ortho,
+            |v| if v > 0.5 { 0.0 } else { 1.0 },
+        );
+        update_view_params_buffer(state);
+    }
+
+    // Comma/period slow down/speed up the animation
+    if action_just_pressed(state, Action::SlowerTime) {
+        set_param(
+            state,
+            "view_params.time_modifier",
+            |p| &mut p.view_params.time_modifier,
+            |v| step_time_modifier(v, false),
+        );
+        update_view_params_buffer(state);
+    } else if action_just_pressed(state, Action::FasterTime) {
+        set_param(
+            state,
+            "view_params.time_modifier",
+            |p| &mut p.view_params.time_modifier,
+            |v| step_time_modifier(v, true),
+        );
         update_view_params_buffer(state);
-    } else if pressed.contains(&PhysicalKey::Code(KeyCode::KeyZ)) {
-        state.params.view_params.zoom += 0.1 * mz;
+    }
+
+    bookmark_controls(state);
+    look_controls(state);
+    fly_controls(state);
+    roll_controls(state);
+
+    let scroll = state.mouse.take_scroll();
+    if scroll != 0.0 {
+        let mz = state.params.view_params.zoom;
+        set_param(
+            state,
+            "view_params.zoom",
+            |p| &mut p.view_params.zoom,
+            |v| clamp_zoom(v + scroll * ZOOM_STEP * mz),
+        );
+        update_view_params_buffer(state);
+    }
+
+    // Speeds the camera up along each held direction, integrate_camera_velocity moves it
+    let accel = CAMERA_ACCELERATION * state.frame_dt.min(MAX_MOVE_DT);
+    let mut velocity = state.camera_velocity;
+    accelerate_arrows(
+        &mut velocity,
+        |action| action_pressed(state, action),
+        state.controls.shift_pressed(),
+        accel,
+    );
+    state.camera_velocity = velocity;
+}
+
+// Unit direction of the held keys on two axes, each given as (positive, negative).
+// Opposite keys cancel and a diagonal is no faster than a single key
+fn held_direction(
+    pressed: impl Fn(Action) -> bool,
+    x: (Action, Action),
+    y: (Action, Action),
+) -> Vector2<f32> {
+    let axis =
+        |(pos, neg): (Action, Action)| pressed(pos) as i32 as f32 - pressed(neg) as i32 as f32;
+    Vector2::new(axis(x), axis(y))
+        .try_normalize(0.0)
+        .unwrap_or_else(Vector2::zeros)
+}
+
+// VIEW mode arrows and zoom keys. Every held arrow counts, so two at once move
+// diagonally. Shift turns the arrows to rotation
+fn accelerate_arrows(
+    velocity: &mut CameraVelocity,
+    pressed: impl Fn(Action) -> bool,
+    rotate: bool,
+    accel: f32,
+) {
+    let arrows = held_direction(
+        &pressed,
+        (Action::Right, Action::Left),
+        (Action::Down, Action::Up),
+    );
+    let zoom = pressed(Action::ZoomIn) as i32 as f32 - pressed(Action::ZoomOut) as i32 as f32;
+
+    if rotate {
+        velocity.rot -= arrows * accel;
+    } else {
+        velocity.pan += arrows * accel;
+    }
+    velocity.zoom += zoom * accel;
+}
+
+// One comma/period press. Multiplicative so each press is the same relative change,
+// clamped so the clock never stops or runs backwards
+fn step_time_modifier(time_modifier: f32, faster: bool) -> f32 {
+    let stepped = if faster {
+        time_modifier * TIME_MODIFIER_STEP
+    } else {
+        time_modifier / TIME_MODIFIER_STEP
+    };
+    stepped.clamp(MIN_TIME_MODIFIER, MAX_TIME_MODIFIER)
+}
+
+// Moves the camera by its velocity then lets the drag slow it, every frame and in
+// every mode so a coast carries on after leaving VIEW mode
+pub(crate) fn integrate_camera_velocity(state: &mut State) {
+    let mut velocity = state.camera_velocity;
+    let moved = step_camera(
+        &mut state.params,
+        state.log_param_changes,
+        &mut velocity,
+        state.frame_dt,
+        state.controls.speed,
+    );
+    state.camera_velocity = velocity;
+    if moved {
         update_view_params_buffer(state);
     }
 }
 
+// One frame of integrate_camera_velocity on bare params. False when the camera is at
+// rest, leaving the params alone so the view buffer isn't rewritten
+fn step_camera(
+    params: &mut Params,
+    log: bool,
+    velocity: &mut CameraVelocity,
+    frame_dt: f32,
+    speed: f32,
+) -> bool {
+    let v = *velocity;
+    if v == CameraVelocity::default() {
+        return false;
+    }
+
+    // An imported view may still carry a zoom outside the range
+    let mz = clamp_zoom(params.view_params.zoom);
+    let dt = frame_dt.min(MAX_MOVE_DT) * speed;
+    let pan = v.pan * PAN_SPEED * dt / mz;
+    let rot = v.rot * ROTATE_SPEED * dt;
+    let zoom = v.zoom * ZOOM_SPEED * dt * mz;
+    let (camr, _, camf) = camera_basis(&params.view_params);
+    let step = (camf * v.fly.x + camr * v.fly.y) * FLY_SPEED * dt;
+    let roll = v.roll * ROLL_SPEED * dt;
+
+    set_field(
+        params,
+        log,
+        "view_params.x_shift",
+        |p| &mut p.view_params.x_shift,
+        |x| x + pan.x,
+    );
+    set_field(
+        params,
+        log,
+        "view_params.y_shift",
+        |p| &mut p.view_params.y_shift,
+        |y| y + pan.y,
+    );
+    set_field(
+        params,
+        log,
+        "view_params.x_rot",
+        |p| &mut p.view_params.x_rot,
+        |x| wrap_angle(x + rot.x),
+    );
+    set_field(
+        params,
+        log,
+        "view_params.y_rot",
+        |p| &mut p.view_params.y_rot,
+        |y| (y + rot.y).clamp(-max_pitch(), max_pitch()),
+    );
+    set_field(
+        params,
+        log,
+        "view_params.zoom",
+        |p| &mut p.view_params.zoom,
+        |z| clamp_zoom(z + zoom),
+    );
+    set_field(
+        params,
+        log,
+        "view_params.cam_pos",
+        |p| &mut p.view_params.cam_pos,
+        |c| [c[0] + step.x, c[1] + step.y, c[2] + step.z],
+    );
+    set_field(
+        params,
+        log,
+        "view_params.z_rot",
+        |p| &mut p.view_params.z_rot,
+        |z| wrap_angle(z + roll),
+    );
+
+    velocity.damp(frame_dt.min(MAX_MOVE_DT));
+    true
+}
+
+// Arrows move the sun, left/right around the vertical axis and up/down in
+// elevation. Held keys redirect them: A ambient, F fog density and S fog start
+// with up/down, C the fog colour's hue with left/right
+fn light_controls(state: &mut State) {
+    let axis = |pos: Action, neg: Action| {
+        action_pressed(state, pos) as i32 as f32 - action_pressed(state, neg) as i32 as f32
+    };
+    let vertical = axis(Action::Up, Action::Down);
+    let horizontal = axis(Action::Right, Action::Left);
+    let dt = state.frame_dt.min(MAX_MOVE_DT) * state.controls.speed;
+
+    if action_just_pressed(state, Action::CycleTonemap) {
+        set_param(
+            state,
+            "tone_params.tonemap",
+            |p| &mut p.tone_params.tonemap,
+            |v| (v + 1) % TONEMAP_NAMES.len() as u32,
+        );
+        let tonemap = state.params.tone_params.tonemap;
+        println!("Tone mapping: {}", TONEMAP_NAMES[tonemap as usize]);
+        update_tone_params_buffer(state);
+    }
+
+    if action_pressed(state, Action::Ambient) {
+        if vertical != 0.0 {
+            set_param(
+                state,
+                "light_params.ambient",
+                |p| &mut p.light_params.ambient,
+                |v| (v + AMBIENT_SPEED * vertical * dt).clamp(0.0, 1.0),
+            );
+            update_light_params_buffer(state);
+        }
+    } else if action_pressed(state, Action::FogDensity) {
+        if vertical != 0.0 {
+            set_param(
+                state,
+                "fog_params.fog_density",
+                |p| &mut p.fog_params.fog_density,
+                |v| (v + FOG_DENSITY_SPEED * vertical * dt).clamp(0.0, MAX_FOG_DENSITY),
+            );
+            update_fog_params_buffer(state);
+        }
+    } else if action_pressed(state, Action::FogStart) {
+        if vertical != 0.0 {
+            set_param(
+                state,
+                "fog_params.fog_start",
+                |p| &mut p.fog_params.fog_start,
+                |v| (v + FOG_START_SPEED * vertical * dt).clamp(0.0, 1.0),
+            );
+            update_fog_params_buffer(state);
+        }
+    } else if action_pressed(state, Action::FogHue) {
+        if horizontal != 0.0 {
+            set_param(
+                state,
+                "fog_params.fog_color",
+                |p| &mut p.fog_params.fog_color,
+                |v| rotate_hue(v, horizontal * HUE_ROTATE_SPEED * dt),
+            );
+            update_fog_params_buffer(state);
+        }
+    } else if action_pressed(state, Action::Exposure) {
+        if vertical != 0.0 {
+            set_param(
+                state,
+                "tone_params.exposure",
+                |p| &mut p.tone_params.exposure,
+                |v| (v * (EXPOSURE_SPEED * vertical * dt).exp2()).clamp(MIN_EXPOSURE, MAX_EXPOSURE),
+            );
+            update_tone_params_buffer(state);
+        }
+    } else if action_pressed(state, Action::SkyHorizon) {
+        if horizontal != 0.0 {
+            set_param(
+                state,
+                "sky_params.horizon_color",
+                |p| &mut p.sky_params.horizon_color,
+                |v| rotate_hue(v, horizontal * HUE_ROTATE_SPEED * dt),
+            );
+            update_sky_params_buffer(state);
+        }
+        if vertical != 0.0 {
+            set_param(
+                state,
+                "sky_params.horizon_blend",
+                |p| &mut p.sky_params.horizon_blend,
+                |v| (v + HORIZON_BLEND_SPEED * vertical * dt).clamp(0.01, 1.0),
+            );
+            update_sky_params_buffer(state);
+        }
+    } else if action_pressed(state, Action::SkyZenith) {
+        if horizontal != 0.0 {
+            set_param(
+                state,
+                "sky_params.zenith_color",
+                |p| &mut p.sky_params.zenith_color,
+                |v| rotate_hue(v, horizontal * HUE_ROTATE_SPEED * dt),
+            );
+            update_sky_params_buffer(state);
+        }
+    } else if vertical != 0.0 || horizontal != 0.0 {
+        let rot = SUN_ROTATE_SPEED * dt;
+        set_param(
+            state,
+            "light_params.sun_dir",
+            |p| &mut p.light_params.sun_dir,
+            |v| rotate_sun(v, horizontal * rot, vertical * rot),
+        );
+        update_light_params_buffer(state);
+    }
+}
+
+// Rotates the colour around the grey axis, keeping its brightness
+fn rotate_hue(rgb: [f32; 3], angle: f32) -> [f32; 3] {
+    let grey = Unit::new_normalize(Vector3::new(1.0, 1.0, 1.0));
+    let rotated = Rotation3::from_axis_angle(&grey, angle) * Vector3::from(rgb);
+    rotated.map(|c| c.clamp(0.0, 1.0)).into()
+}
+
+// Azimuth is measured around +y, elevation above the horizon
+fn rotate_sun(dir: [f32; 3], d_azimuth: f32, d_elevation: f32) -> [f32; 3] {
+    let [x, y, z] = dir;
+    let azimuth = z.atan2(x) + d_azimuth;
+    let elevation =
+        (y.clamp(-1.0, 1.0).asin() + d_elevation).clamp(MIN_SUN_ELEVATION, MAX_SUN_ELEVATION);
+    [
+        elevation.cos() * azimuth.cos(),
+        elevation.sin(),
+        elevation.cos() * azimuth.sin(),
+    ]
+}
+
 fn print_controls(state: &mut State) {
     // PRINT CURRENT PARAMETER VALUES ----------------------------------------------
     println!("\n------------------------------------------------------");
     println!("\n{:#?}", state.params.terrain_params);
+    println!("\n{:#?}", state.params.terrain_anim_params);
     println!("\n{:#?}", state.params.view_params);
     println!("\n{:#?}", state.params.ray_params);
+    println!("\n{:#?}", state.params.sdf_params);
+    println!("\n{:#?}", state.params.clip_params);
+    println!("\n{:#?}", state.params.brush_params);
+    println!("\n{:#?}", state.params.precision_params);
+    println!("\n{:#?}", state.params.light_params);
+    println!("\n{:#?}", state.params.fog_params);
+    println!("\n{:#?}", state.params.sky_params);
+    println!("\n{:#?}", state.params.tone_params);
     println!("------------------------------------------------------\n");
     state.controls.mode = KeyboardMode::VIEW;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collections::consts::CAMERA_DAMPING;
+
+    fn held(keys: &[Action]) -> impl Fn(Action) -> bool + '_ {
+        move |action| keys.contains(&action)
+    }
+
+    #[test]
+    fn left_and_up_pan_both_axes() {
+        let mut velocity = CameraVelocity::default();
+        accelerate_arrows(&mut velocity, held(&[Action::Left, Action::Up]), false, 1.0);
+        assert!(velocity.pan.x < 0.0);
+        assert!(velocity.pan.y < 0.0);
+        assert_eq!(velocity.rot, Vector2::zeros());
+        assert_eq!(velocity.zoom, 0.0);
+    }
+
+    #[test]
+    fn left_and_up_rotate_both_axes_with_shift() {
+        let mut velocity = CameraVelocity::default();
+        accelerate_arrows(&mut velocity, held(&[Action::Left, Action::Up]), true, 1.0);
+        assert!(velocity.rot.x > 0.0);
+        assert!(velocity.rot.y > 0.0);
+        assert_eq!(velocity.pan, Vector2::zeros());
+    }
+
+    #[test]
+    fn diagonals_are_normalized() {
+        let mut velocity = CameraVelocity::default();
+        accelerate_arrows(&mut velocity, held(&[Action::Left, Action::Up]), false, 2.0);
+        assert!((velocity.pan.norm() - 2.0).abs() < 1e-6);
+
+        let wasd = held_direction(
+            held(&[Action::MoveForward, Action::MoveRight]),
+            (Action::MoveForward, Action::MoveBack),
+            (Action::MoveRight, Action::MoveLeft),
+        );
+        assert!((wasd.norm() - 1.0).abs() < 1e-6);
+        assert_eq!(wasd.x, wasd.y);
+    }
+
+    #[test]
+    fn opposite_keys_cancel() {
+        let direction = held_direction(
+            held(&[Action::Left, Action::Right]),
+            (Action::Right, Action::Left),
+            (Action::Down, Action::Up),
+        );
+        assert_eq!(direction, Vector2::zeros());
+    }
+
+    #[test]
+    fn time_modifier_steps_change_the_rate() {
+        assert!(step_time_modifier(1.0, true) > 1.0);
+        assert!(step_time_modifier(1.0, false) < 1.0);
+    }
+
+    #[test]
+    fn time_modifier_stays_positive_and_bounded() {
+        let mut slow = 1.0;
+        let mut fast = 1.0;
+        for _ in 0..1000 {
+            slow = step_time_modifier(slow, false);
+            fast = step_time_modifier(fast, true);
+            assert!(slow > 0.0);
+        }
+        assert_eq!(slow, MIN_TIME_MODIFIER);
+        assert_eq!(fast, MAX_TIME_MODIFIER);
+    }
+
+    #[test]
+    fn octaves_stay_in_range() {
+        assert_eq!(step_octaves(4, 1), 5);
+        assert_eq!(step_octaves(4, -1), 3);
+
+        let (mut up, mut down) = (MIN_OCTAVES, MAX_OCTAVES);
+        for _ in 0..100 {
+            up = step_octaves(up, 1);
+            down = step_octaves(down, -1);
+            assert!((MIN_OCTAVES..=MAX_OCTAVES).contains(&up));
+            assert!((MIN_OCTAVES..=MAX_OCTAVES).contains(&down));
+        }
+        assert_eq!(up, MAX_OCTAVES);
+        assert_eq!(down, MIN_OCTAVES);
+    }
+
+    #[test]
+    fn layer_frequency_and_amplitude_stay_in_range() {
+        let (mut freq_up, mut freq_down) = (1.0, 1.0);
+        let (mut amp_up, mut amp_down) = (1.0, 1.0);
+        for _ in 0..10_000 {
+            freq_up = step_layer_freq(freq_up, 1);
+            freq_down = step_layer_freq(freq_down, -1);
+            amp_up = step_layer_amp(amp_up, 1);
+            amp_down = step_layer_amp(amp_down, -1);
+            assert!((MIN_TERRAIN_FREQ..=MAX_TERRAIN_FREQ).contains(&freq_down));
+            assert!((0.0..=MAX_TERRAIN_AMP).contains(&amp_down));
+        }
+        assert_eq!(freq_up, MAX_TERRAIN_FREQ);
+        assert_eq!(freq_down, MIN_TERRAIN_FREQ);
+        assert_eq!(amp_up, MAX_TERRAIN_AMP);
+        assert_eq!(amp_down, 0.0);
+    }
+
+    #[test]
+    fn animation_speed_never_goes_negative() {
+        let mut speed = 0.02;
+        for _ in 0..100 {
+            speed = step_animation_speed(speed, -1.0);
+            assert!(speed >= 0.0);
+        }
+        assert_eq!(speed, 0.0);
+        assert!(step_animation_speed(speed, 1.0) > 0.0);
+    }
+
+    #[test]
+    fn one_epsilon_step_is_one_bounded_factor() {
+        let epsilon = 0.001;
+        let up = step_epsilon(epsilon, 1.0);
+        let down = step_epsilon(epsilon, -1.0);
+        assert!((up / epsilon - EPSILON_STEP).abs() < 1e-5);
+        assert!((epsilon / down - EPSILON_STEP).abs() < 1e-5);
+        assert_eq!(step_epsilon(epsilon, 0.0), epsilon);
+    }
+
+    #[test]
+    fn epsilon_stays_in_range() {
+        let (mut up, mut down) = (0.001, 0.001);
+        for _ in 0..10_000 {
+            let (last_up, last_down) = (up, down);
+            up = step_epsilon(up, 1.0);
+            down = step_epsilon(down, -1.0);
+            assert!(down >= MIN_EPSILON && up <= MAX_EPSILON);
+            assert!(up / last_up <= EPSILON_STEP * (1.0 + 1e-5));
+            assert!(last_down / down <= EPSILON_STEP * (1.0 + 1e-5));
+        }
+        assert_eq!(up, MAX_EPSILON);
+        assert_eq!(down, MIN_EPSILON);
+    }
+
+    #[test]
+    fn held_arrow_down_stays_at_the_floor() {
+        let mut max_steps = MIN_MAX_STEPS + 2.5;
+        for _ in 0..100 {
+            max_steps = step_with_floor("ray_params.max_steps", max_steps, -1.0, MIN_MAX_STEPS);
+            assert!(max_steps >= MIN_MAX_STEPS);
+        }
+        assert_eq!(max_steps, MIN_MAX_STEPS);
+        assert_eq!(
+            step_with_floor("ray_params.max_steps", max_steps, 1.0, MIN_MAX_STEPS),
+            MIN_MAX_STEPS + 1.0
+        );
+    }
+
+    // init_params with the view, ray and terrain all moved away from their defaults
+    fn edited_params() -> Params {
+        let mut params = init_params();
+        params.view_params.x_rot = 1.5;
+        params.view_params.zoom = 4.0;
+        params.view_params.cam_pos = [3.0, 2.0, 1.0];
+        params.ray_params.epsilon = 0.25;
+        params.ray_params.max_steps += 10.0;
+        params.terrain_params.f1_octaves += 1;
+        params
+    }
+
+    #[test]
+    fn view_reset_restores_only_the_view() {
+        let mut params = edited_params();
+        assert!(reset_mode_params(&mut params, false, KeyboardMode::VIEW));
+
+        let defaults = init_params();
+        assert_eq!(params.view_params, defaults.view_params);
+        assert_eq!(params.ray_params, edited_params().ray_params);
+        assert_eq!(
+            bytemuck::bytes_of(&params.terrain_params),
+            bytemuck::bytes_of(&edited_params().terrain_params)
+        );
+    }
+
+    #[test]
+    fn ray_reset_restores_only_the_ray_params() {
+        let mut params = edited_params();
+        assert!(reset_mode_params(&mut params, false, KeyboardMode::RAY));
+
+        let defaults = init_params();
+        assert_eq!(params.ray_params, defaults.ray_params);
+        assert_eq!(params.view_params, edited_params().view_params);
+    }
+
+    #[test]
+    fn terrain_mode_has_no_reset() {
+        let mut params = edited_params();
+        assert!(!reset_mode_params(
+            &mut params,
+            false,
+            KeyboardMode::TERRAIN
+        ));
+        assert_eq!(
+            bytemuck::bytes_of(&params.terrain_params),
+            bytemuck::bytes_of(&edited_params().terrain_params)
+        );
+    }
+
+    // x_shift after coasting from the default view for `frames` frames of `dt`
+    fn coast_x_shift(frames: u32, dt: f32) -> f32 {
+        let mut params = init_params();
+        let mut velocity = CameraVelocity {
+            pan: Vector2::new(1.0, 0.0),
+            ..Default::default()
+        };
+        for _ in 0..frames {
+            step_camera(&mut params, false, &mut velocity, dt, 1.0);
+        }
+        params.view_params.x_shift - init_params().view_params.x_shift
+    }
+
+    #[test]
+    fn equal_frames_move_the_camera_equally() {
+        let dt = 1.0 / 60.0;
+        let mut params = init_params();
+        let start = params.view_params.x_shift;
+        let mut velocity = CameraVelocity {
+            pan: Vector2::new(1.0, 0.0),
+            ..Default::default()
+        };
+        step_camera(&mut params, false, &mut velocity, dt, 1.0);
+        let first = params.view_params.x_shift - start;
+        assert!((first - PAN_SPEED * dt).abs() < 1e-6);
+
+        // Damping took the same fraction off, so each frame moves that much less again
+        let decay = velocity.pan.x;
+        let before = params.view_params.x_shift;
+        step_camera(&mut params, false, &mut velocity, dt, 1.0);
+        let second = params.view_params.x_shift - before;
+        assert!((second - first * decay).abs() < 1e-6);
+
+        assert_eq!(coast_x_shift(5, dt), coast_x_shift(5, dt));
+    }
+
+    #[test]
+    fn one_long_frame_matches_two_short_ones() {
+        let dt = 1.0 / 30.0;
+        let long = coast_x_shift(1, dt);
+        let short = coast_x_shift(2, dt / 2.0);
+        // The second half frame is slowed by one half frame of drag
+        let tolerance = long * CAMERA_DAMPING * dt / 2.0;
+        assert!((long - short).abs() <= tolerance, "{} vs {}", long, short);
+    }
+
+    #[test]
+    fn camera_at_rest_leaves_the_params_alone() {
+        let mut params = edited_params();
+        let mut velocity = CameraVelocity::default();
+        assert!(!step_camera(
+            &mut params,
+            false,
+            &mut velocity,
+            1.0 / 60.0,
+            1.0
+        ));
+        assert_eq!(params.view_params, edited_params().view_params);
+        assert_eq!(velocity, CameraVelocity::default());
+    }
+
+    #[test]
+    fn released_camera_coasts_to_rest() {
+        let mut params = init_params();
+        let mut velocity = CameraVelocity {
+            pan: Vector2::new(1.0, -1.0),
+            rot: Vector2::new(0.5, 0.0),
+            zoom: 1.0,
+            fly: Vector2::new(0.0, 1.0),
+            roll: -1.0,
+        };
+        let mut frames = 0;
+        while step_camera(&mut params, false, &mut velocity, 1.0 / 60.0, 1.0) {
+            frames += 1;
+            assert!(frames < 1000, "still moving: {:?}", velocity);
+        }
+        // damp snaps to Default once it's below the rest speed, so the guard kicks in
+        assert_eq!(velocity, CameraVelocity::default());
+        assert!(!velocity.is_moving());
+
+        let stopped = params.view_params;
+        assert!(!step_camera(
+            &mut params,
+            false,
+            &mut velocity,
+            1.0 / 60.0,
+            1.0
+        ));
+        assert_eq!(params.view_params, stopped);
+    }
+
+    #[test]
+    fn layer_edit_asks_for_regeneration_once_per_change() {
+        let mut params = init_params();
+        params.terrain_params.f2_octaves = MAX_OCTAVES - 2;
+
+        // Two presses change the octaves, the rest find it at the limit
+        let regenerations = (0..5)
+            .filter(|_| step_layer(&mut params, false, 1, LayerField::Octaves, 1))
+            .count();
+        assert_eq!(regenerations, 2);
+        assert_eq!(params.terrain_params.f2_octaves, MAX_OCTAVES);
+        assert_eq!(
+            params.terrain_params.f1_octaves,
+            init_params().terrain_params.f1_octaves
+        );
+
+        assert!(step_layer(&mut params, false, 1, LayerField::Octaves, -1));
+        assert!(step_layer(&mut params, false, 0, LayerField::Frequency, 1));
+        assert!(step_layer(&mut params, false, 2, LayerField::Amplitude, 1));
+
+        params.terrain_params.f3_amp = 0.0;
+        assert!(!step_layer(
+            &mut params,
+            false,
+            2,
+            LayerField::Amplitude,
+            -1
+        ));
+    }
+}
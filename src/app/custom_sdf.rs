@@ -0,0 +1,122 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{bail, Context};
+
+use crate::collections::consts::{ACCUMULATE_BLEND, ACCUMULATION_FORMAT};
+use crate::init::init_functions::{create_shader_module, init_render_pipeline, FRAG_SOURCE};
+
+use super::state::State;
+
+const BEGIN_MARKER: &str = "// CUSTOM SDF BEGIN";
+const END_MARKER: &str = "// CUSTOM SDF END";
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// A user WGSL file defining `fn map(p: vec3<f32>) -> f32`,
+// spliced into frag.wgsl and reloaded whenever the file changes
+#[derive(Debug)]
+pub(crate) struct CustomSdf {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    last_poll: Instant,
+}
+
+impl CustomSdf {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+        Self {
+            path,
+            modified,
+            last_poll: Instant::now(),
+        }
+    }
+}
+
+// The snippet's map is renamed to custom_sdf, so it must not call itself
+fn splice_snippet(snippet: &str) -> anyhow::Result<String> {
+    if !snippet.contains("fn map(") {
+        bail!("expected a `fn map(p: vec3<f32>) -> f32` in the snippet");
+    }
+    let snippet = snippet.replacen("fn map(", "fn custom_sdf(", 1);
+
+    let begin = FRAG_SOURCE
+        .find(BEGIN_MARKER)
+        .context("frag.wgsl is missing the custom SDF begin marker")?;
+    let end = FRAG_SOURCE
+        .find(END_MARKER)
+        .context("frag.wgsl is missing the custom SDF end marker")?;
+
+    Ok(format!(
+        "{}{}\n{}\n{}",
+        &FRAG_SOURCE[..begin],
+        BEGIN_MARKER,
+        snippet,
+        &FRAG_SOURCE[end..]
+    ))
+}
+
+impl<'a> State<'a> {
+    // Rebuild the render pipelines from the snippet, leaving the current
+    // pipelines in place if it fails to compile
+    pub(crate) fn load_custom_sdf(&mut self) -> anyhow::Result<()> {
+        let Some(custom_sdf) = &self.custom_sdf else {
+            return Ok(());
+        };
+
+        let snippet = std::fs::read_to_string(&custom_sdf.path)
+            .with_context(|| format!("Failed to read {}", custom_sdf.path.display()))?;
+        let source = splice_snippet(&snippet)?;
+
+        let v_shader = create_shader_module(
+            &self.device,
+            "Vertex Shader",
+            include_str!("../shaders/v2.wgsl"),
+        )?;
+        let f_shader = create_shader_module(&self.device, "Custom SDF Fragment Shader", &source)?;
+        let render = init_render_pipeline(
+            &self.device,
+            &self.bind_groups,
+            &v_shader,
+            &f_shader,
+            self.color_format,
+            self.sample_count,
+            wgpu::BlendState::REPLACE,
+        )?;
+        let accumulate = init_render_pipeline(
+            &self.device,
+            &self.bind_groups,
+            &v_shader,
+            &f_shader,
+            ACCUMULATION_FORMAT,
+            self.sample_count,
+            ACCUMULATE_BLEND,
+        )?;
+        self.pipelines.render = render;
+        self.pipelines.accumulate = accumulate;
+        Ok(())
+    }
+
+    // Called every frame, reloads the snippet when its modified time changes
+    pub(crate) fn poll_custom_sdf(&mut self) {
+        let Some(custom_sdf) = &mut self.custom_sdf else {
+            return;
+        };
+        if custom_sdf.last_poll.elapsed() < POLL_INTERVAL {
+            return;
+        }
+        custom_sdf.last_poll = Instant::now();
+
+        let modified = std::fs::metadata(&custom_sdf.path)
+            .and_then(|m| m.modified())
+            .ok();
+        if modified.is_none() || modified == custom_sdf.modified {
+            return;
+        }
+        custom_sdf.modified = modified;
+
+        match self.load_custom_sdf() {
+            Ok(()) => println!("Reloaded custom SDF"),
+            Err(e) => eprintln!("Custom SDF failed, keeping the last good shader: {:#}", e),
+        }
+    }
+}
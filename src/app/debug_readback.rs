@@ -0,0 +1,212 @@
+use std::fmt;
+use std::fs::File;
+use std::future::Future;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use anyhow::Context as _;
+use futures::future::join_all;
+use futures::FutureExt;
+
+use crate::collections::consts::DEBUG_PIXEL_CENTER;
+use crate::updates::param_updates::update_texture_view_params_buffer;
+use crate::updates::readback::read_buffer;
+
+use super::state::State;
+
+// The DEBUG mode buffer dumps, write_debug in frag.wgsl documents each slot
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum DebugReadback {
+    // The debug pixel's ray march and final color
+    Generic,
+    // Hit position and step count along the screen's center row
+    Array1,
+    // Terrain texels along the texture's center row
+    Array2,
+    // Array1 and Array2 side by side
+    Interleaved,
+}
+
+// Each buffer's contents, or why it couldn't be read
+type DebugReads = Vec<anyhow::Result<Vec<[f32; 4]>>>;
+
+// A dump waiting on its read_buffer calls, polled each frame so the render loop never
+// blocks on it
+pub(crate) struct PendingReadback {
+    kind: DebugReadback,
+    // All of them run to the end, so none is left mapped when another fails
+    reads: Pin<Box<dyn Future<Output = DebugReads>>>,
+    // Written here as CSV instead of printed
+    csv_path: Option<PathBuf>,
+}
+
+impl fmt::Debug for PendingReadback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PendingReadback")
+            .field("kind", &self.kind)
+            .field("csv_path", &self.csv_path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl DebugReadback {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            DebugReadback::Generic => "generic",
+            DebugReadback::Array1 => "array1",
+            DebugReadback::Array2 => "array2",
+            DebugReadback::Interleaved => "interleaved",
+        }
+    }
+}
+
+impl<'a> State<'a> {
+    // The shader works in render pixels, so the window pixel is scaled by the
+    // supersampling factor. Called again whenever the render size changes
+    pub(crate) fn set_debug_pixel(&mut self, pixel: Option<winit::dpi::PhysicalPosition<u32>>) {
+        self.debug_pixel = pixel;
+        self.params.texture_view_params.debug_pixel = match pixel {
+            Some(p) => [p.x * self.ss_factor, p.y * self.ss_factor],
+            None => [DEBUG_PIXEL_CENTER; 2],
+        };
+        update_texture_view_params_buffer(self);
+    }
+
+    fn debug_readback_buffers(&self, kind: DebugReadback) -> Vec<Arc<wgpu::Buffer>> {
+        let buffers = match kind {
+            DebugReadback::Generic => vec![&self.buffers.cpu_read_generic_debug],
+            DebugReadback::Array1 => vec![&self.buffers.cpu_read_debug_array1],
+            DebugReadback::Array2 => vec![&self.buffers.cpu_read_debug_array2],
+            DebugReadback::Interleaved => vec![
+                &self.buffers.cpu_read_debug_array1,
+                &self.buffers.cpu_read_debug_array2,
+            ],
+        };
+        buffers.into_iter().map(Arc::clone).collect()
+    }
+
+    // Ignored while an earlier dump is still pending. With a csv_path the dump
+    // goes to that file rather than stdout
+    pub(crate) fn request_debug_readback(
+        &mut self,
+        kind: DebugReadback,
+        csv_path: Option<PathBuf>,
+    ) {
+        if self.debug_readback.is_some() {
+            return;
+        }
+
+        let device = Arc::clone(&self.device);
+        let buffers = self.debug_readback_buffers(kind);
+        let reads = async move {
+            join_all(buffers.iter().map(|buffer| read_buffer(&device, buffer))).await
+        };
+        self.debug_readback = Some(PendingReadback {
+            kind,
+            reads: Box::pin(reads),
+            csv_path,
+        });
+    }
+
+    // Called every frame, prints the pending dump once all its buffers are mapped
+    pub(crate) fn poll_debug_readback(&mut self) {
+        let Some(pending) = &mut self.debug_readback else {
+            return;
+        };
+
+        let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+        let Poll::Ready(reads) = pending.reads.poll_unpin(&mut cx) else {
+            return;
+        };
+        let kind = pending.kind;
+        let csv_path = pending.csv_path.take();
+        self.debug_readback = None;
+
+        let data = match reads.into_iter().collect::<anyhow::Result<Vec<_>>>() {
+            Ok(data) => data,
+            Err(e) => {
+                eprintln!("Error retrieving gpu data: {:#}", e);
+                return;
+            }
+        };
+
+        if let Some(path) = &csv_path {
+            match dump_gpu_data_csv(&data, path) {
+                Ok(()) => println!("Saved {}", path.display()),
+                Err(e) => eprintln!("Debug dump failed: {:#}", e),
+            }
+        } else {
+            match (kind, data.as_slice()) {
+                (DebugReadback::Interleaved, [data1, data2]) => {
+                    print_gpu_interleave_two_buffers(data1, data2)
+                }
+                (DebugReadback::Generic, [data]) => print_debug_pixel(self.debug_pixel, data),
+                (_, [data]) => print_gpu_data(data, "Debug"),
+                _ => {}
+            }
+        }
+    }
+}
+
+fn print_gpu_data<T: std::fmt::Debug>(data: &[T], obj_label: &str) {
+    println!("buffer size: {:?}", std::mem::size_of_val(data));
+    for (i, obj) in data.iter().enumerate() {
+        println!("{} {}:\n{:?}", obj_label, i, obj);
+    }
+}
+
+// Slot layout from write_debug and write_debug_color in frag.wgsl
+fn print_debug_pixel(pixel: Option<winit::dpi::PhysicalPosition<u32>>, data: &[[f32; 4]]) {
+    let [march, hit, color] = data else {
+        return;
+    };
+    match pixel {
+        Some(p) => println!("Debug pixel ({}, {}):", p.x, p.y),
+        None => println!("Debug pixel (center):"),
+    }
+    println!(
+        "  ray origin    {:?}\n  hit distance  {}",
+        &march[..3],
+        march[3]
+    );
+    println!(
+        "  hit point     {:?}\n  steps         {}",
+        &hit[..3],
+        hit[3]
+    );
+    println!("  color         {:?}", &color[..3]);
+}
+
+fn print_gpu_interleave_two_buffers<T: std::fmt::Debug>(data1: &[T], data2: &[T]) {
+    for (idx, item) in data1.iter().zip(data2.iter()).enumerate() {
+        println!("\n{idx}:\n{:?}", item.0);
+        println!("{:?}", item.1);
+    }
+}
+
+// One row per element: its index, then the element's values from each buffer in turn,
+// so the interleaved dump puts both arrays side by side
+fn dump_gpu_data_csv<T>(data: &[Vec<T>], path: &Path) -> anyhow::Result<()>
+where
+    T: Copy + IntoIterator,
+    T::Item: std::fmt::Display,
+{
+    let file =
+        File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut writer = BufWriter::new(file);
+    let rows = data.iter().map(|d| d.len()).min().unwrap_or(0);
+    for i in 0..rows {
+        write!(writer, "{}", i)?;
+        for d in data {
+            for value in d[i] {
+                write!(writer, ",{}", value)?;
+            }
+        }
+        writeln!(writer)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
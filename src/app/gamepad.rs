@@ -0,0 +1,57 @@
+use gilrs::{Axis, Button, Gilrs};
+
+use crate::collections::consts::GAMEPAD_DEAD_ZONE;
+
+// Stick and trigger positions of the first connected gamepad, past the dead zone
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub(crate) struct GamepadInput {
+    pub(crate) left: (f32, f32),
+    pub(crate) right: (f32, f32),
+    // Right trigger minus left trigger
+    pub(crate) zoom: f32,
+}
+
+#[derive(Debug)]
+pub(crate) struct Gamepad {
+    gilrs: Gilrs,
+}
+
+impl Gamepad {
+    // None if the platform backend fails to start, the app then runs without it
+    pub(crate) fn new() -> Option<Self> {
+        Gilrs::new().ok().map(|gilrs| Self { gilrs })
+    }
+
+    // All zero when no controller is connected
+    pub(crate) fn poll(&mut self) -> GamepadInput {
+        // gilrs only updates the axis state it reports as its events are consumed
+        while self.gilrs.next_event().is_some() {}
+
+        let Some((_, pad)) = self.gilrs.gamepads().next() else {
+            return GamepadInput::default();
+        };
+        let axis = |axis| pad.axis_data(axis).map_or(0.0, |d| d.value());
+        let trigger = |button| pad.button_data(button).map_or(0.0, |d| d.value());
+
+        let zoom = trigger(Button::RightTrigger2) - trigger(Button::LeftTrigger2);
+        GamepadInput {
+            left: dead_zone(axis(Axis::LeftStickX), axis(Axis::LeftStickY)),
+            right: dead_zone(axis(Axis::RightStickX), axis(Axis::RightStickY)),
+            zoom: if zoom.abs() > GAMEPAD_DEAD_ZONE {
+                zoom
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+// Radial dead zone, rescaled so the output still ramps up from 0.0 at its edge
+fn dead_zone(x: f32, y: f32) -> (f32, f32) {
+    let len = (x * x + y * y).sqrt();
+    if len <= GAMEPAD_DEAD_ZONE {
+        return (0.0, 0.0);
+    }
+    let scale = ((len - GAMEPAD_DEAD_ZONE) / (1.0 - GAMEPAD_DEAD_ZONE)).min(1.0) / len;
+    (x * scale, y * scale)
+}
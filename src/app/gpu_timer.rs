@@ -0,0 +1,161 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+// Query indices, begin/end of the terrain generation pass then of the render pass
+const GEN_BEGIN: u32 = 0;
+const GEN_END: u32 = 1;
+const RENDER_BEGIN: u32 = 2;
+const RENDER_END: u32 = 3;
+const QUERY_COUNT: u32 = 4;
+const QUERY_BYTES: u64 = QUERY_COUNT as u64 * std::mem::size_of::<u64>() as u64;
+
+// GPU time of the last measured frame, in nanoseconds
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct GpuTimings {
+    // None if the terrain wasn't generated that frame
+    pub(crate) terrain_gen_ns: Option<f64>,
+    pub(crate) render_ns: f64,
+}
+
+// Timestamp queries around the terrain generation and render passes. Only one frame
+// is measured at a time, frames rendered while its readback is in flight aren't timed
+#[derive(Debug)]
+pub(crate) struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    // Nanoseconds per timestamp tick
+    period: f32,
+    gen_recorded: bool,
+    gen_in_flight: bool,
+    in_flight: bool,
+    needs_map: bool,
+    // Set by the map_async callback
+    mapped: Arc<AtomicBool>,
+    last: Option<GpuTimings>,
+}
+
+impl GpuTimer {
+    // None if the device was created without TIMESTAMP_QUERY
+    pub(crate) fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("GPU Timer Query Set"),
+            ty: wgpu::QueryType::Timestamp,
+            count: QUERY_COUNT,
+        });
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("GPU Timer Resolve Buffer"),
+            size: QUERY_BYTES,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("GPU Timer CPU Readable Buffer"),
+            size: QUERY_BYTES,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Some(Self {
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+            period: queue.get_timestamp_period(),
+            gen_recorded: false,
+            gen_in_flight: false,
+            in_flight: false,
+            needs_map: false,
+            mapped: Arc::new(AtomicBool::new(false)),
+            last: None,
+        })
+    }
+
+    pub(crate) fn terrain_gen_writes(&mut self) -> Option<wgpu::ComputePassTimestampWrites<'_>> {
+        if self.in_flight {
+            return None;
+        }
+        self.gen_recorded = true;
+        Some(wgpu::ComputePassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(GEN_BEGIN),
+            end_of_pass_write_index: Some(GEN_END),
+        })
+    }
+
+    pub(crate) fn render_writes(&self) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        if self.in_flight {
+            return None;
+        }
+        Some(wgpu::RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(RENDER_BEGIN),
+            end_of_pass_write_index: Some(RENDER_END),
+        })
+    }
+
+    // Call after the timed passes are encoded, then `submitted` once the encoder is submitted
+    pub(crate) fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if self.in_flight {
+            return;
+        }
+        encoder.resolve_query_set(&self.query_set, 0..QUERY_COUNT, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &self.readback_buffer,
+            0,
+            QUERY_BYTES,
+        );
+        self.gen_in_flight = std::mem::take(&mut self.gen_recorded);
+        self.in_flight = true;
+        self.needs_map = true;
+    }
+
+    pub(crate) fn submitted(&mut self) {
+        if !std::mem::take(&mut self.needs_map) {
+            return;
+        }
+        let mapped = Arc::clone(&self.mapped);
+        self.readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                if result.is_ok() {
+                    mapped.store(true, Ordering::Release);
+                }
+            });
+    }
+
+    // Pick up the measured frame once its readback has been mapped
+    pub(crate) fn poll(&mut self, device: &wgpu::Device) {
+        if !self.in_flight {
+            return;
+        }
+        device.poll(wgpu::Maintain::Poll);
+        if !self.mapped.swap(false, Ordering::Acquire) {
+            return;
+        }
+
+        let ticks: [u64; QUERY_COUNT as usize] = {
+            let data = self.readback_buffer.slice(..).get_mapped_range();
+            bytemuck::pod_read_unaligned(&data)
+        };
+        self.readback_buffer.unmap();
+        self.in_flight = false;
+
+        let elapsed_ns = |begin: u32, end: u32| {
+            ticks[end as usize].wrapping_sub(ticks[begin as usize]) as f64 * self.period as f64
+        };
+        self.last = Some(GpuTimings {
+            terrain_gen_ns: self.gen_in_flight.then(|| elapsed_ns(GEN_BEGIN, GEN_END)),
+            render_ns: elapsed_ns(RENDER_BEGIN, RENDER_END),
+        });
+    }
+
+    pub(crate) fn last(&self) -> Option<GpuTimings> {
+        self.last
+    }
+}
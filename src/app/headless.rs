@@ -0,0 +1,73 @@
+use std::path::Path;
+
+use crate::collections::consts::HEADLESS_FPS;
+use crate::collections::structs::TimeUniform;
+
+use super::clock::TimeSource;
+use super::motion_blur::MotionBlur;
+use super::state::State;
+
+impl<'a> State<'a> {
+    // Run `frames` frames without a window and save the last one as a PNG. Time steps
+    // at HEADLESS_FPS from 0.0 unless pinned or already fixed, so the output doesn't
+    // depend on how fast the GPU is. Only the last frame needs its render pass, the
+    // earlier ones just advance the time and animated terrain. With more than one
+    // motion blur sample the last frame is rendered by capture_motion_blur instead
+    pub(crate) fn render_headless(
+        &mut self,
+        frames: u32,
+        blur: MotionBlur,
+        path: &Path,
+    ) -> anyhow::Result<()> {
+        self.finish_terrain_generation();
+        if self.clock.source() == TimeSource::Realtime {
+            self.clock.restart(TimeSource::Fixed {
+                dt: 1.0 / HEADLESS_FPS,
+                frame: 0,
+            });
+        }
+
+        // The texture and step views skip the tone curve, so there's nothing to resolve
+        let view_params = &self.params.texture_view_params;
+        let debug_view = view_params.enabled != 0 || view_params.step_heatmap != 0;
+        if blur.samples > 1 && debug_view {
+            eprintln!("Motion blur is skipped for the texture and step views");
+        }
+        let blurred = blur.samples > 1 && !debug_view;
+
+        for _ in 0..frames - blurred as u32 {
+            let mut encoder = self
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Headless Encoder"),
+                });
+            self.encode_headless_terrain(&mut encoder, self.frame_time());
+            self.queue.submit(Some(encoder.finish()));
+            self.advance_time();
+        }
+
+        if blurred {
+            self.capture_motion_blur(path, blur)
+        } else {
+            self.capture_frame(path)
+        }
+    }
+
+    // Write `time` to the time uniform, landing before `encoder` is submitted, and
+    // encode the terrain passes for it
+    pub(crate) fn encode_headless_terrain(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        time: f32,
+    ) {
+        self.queue.write_buffer(
+            &self.buffers.time_uniform,
+            0,
+            bytemuck::cast_slice(&[TimeUniform { time }]),
+        );
+        self.encode_terrain_generation(encoder);
+        self.encode_terrain_normals(encoder);
+        self.encode_terrain_mips(encoder);
+        self.encode_terrain_conversion(encoder);
+    }
+}
@@ -0,0 +1,68 @@
+use std::path::Path;
+
+use anyhow::Context;
+use image::imageops::FilterType;
+
+use super::state::{GenerationState, State};
+
+impl<'a> State<'a> {
+    // Replace the terrain with a grayscale image, black -> -1.0 and white -> 1.0 to match
+    // the fbm height range. Images with a different aspect ratio are center-cropped
+    // rather than letterboxed, so there's no flat border around the terrain
+    pub(crate) fn load_heightmap(&mut self, path: &Path) -> anyhow::Result<()> {
+        let img = image::open(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?
+            .into_luma16();
+
+        // The terrain texture is square
+        let (w, h) = img.dimensions();
+        let (crop_w, crop_h) = (w.min(h), w.min(h));
+        let cropped =
+            image::imageops::crop_imm(&img, (w - crop_w) / 2, (h - crop_h) / 2, crop_w, crop_h)
+                .to_image();
+        let resized = image::imageops::resize(
+            &cropped,
+            self.terrain_resolution,
+            self.terrain_resolution,
+            FilterType::Triangle,
+        );
+
+        let width = self.terrain_resolution as usize;
+        let height = self.terrain_resolution as usize;
+        let heights: Vec<f32> = resized
+            .pixels()
+            .map(|p| p.0[0] as f32 / u16::MAX as f32 * 2.0 - 1.0)
+            .collect();
+        let at = |x: usize, y: usize| heights[y.min(height - 1) * width + x.min(width - 1)];
+
+        // Central differences in the coordinates generate_terrain.wgsl samples fbmD at,
+        // which span 8.0 units across the texture
+        let scale_x = width as f32 / 8.0;
+        let scale_y = height as f32 / 8.0;
+        let mut texels: Vec<[f32; 4]> = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let gx = (at(x + 1, y) - at(x.saturating_sub(1), y)) * 0.5 * scale_x;
+                let gy = (at(x, y + 1) - at(x, y.saturating_sub(1))) * 0.5 * scale_y;
+                texels.push([at(x, y), gx, gy, 1.0]);
+            }
+        }
+
+        self.queue.write_texture(
+            self.textures.terrain_tex.as_image_copy(),
+            bytemuck::cast_slice(&texels),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(self.terrain_resolution * 16),
+                rows_per_image: Some(self.terrain_resolution),
+            },
+            self.textures.terrain_tex.size(),
+        );
+
+        // Stop any pending generation from overwriting the upload
+        self.terrain_gen = GenerationState::Done;
+        self.terrain_ready = true;
+        self.terrain_changed();
+        Ok(())
+    }
+}
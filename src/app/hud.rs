@@ -0,0 +1,100 @@
+use crate::collections::structs::Params;
+
+use super::gpu_timer::GpuTimings;
+
+// Live values of the params being tuned, for the param overlay
+pub(crate) fn param_overlay_text(params: &Params) -> String {
+    let rp = &params.ray_params;
+    let vp = &params.view_params;
+    let tp = &params.terrain_params;
+
+    format!(
+        "RAY\n\
+         epsilon       {:.5}\n\
+         epsilon_scale {:.5}\n\
+         max_dist      {:.1}\n\
+         max_steps     {:.0}\n\
+         \n\
+         VIEW\n\
+         x_shift       {:.3}\n\
+         y_shift       {:.3}\n\
+         zoom          {:.3}\n\
+         x_rot         {:.3}\n\
+         y_rot         {:.3}\n\
+         z_rot         {:.3}\n\
+         time_modifier {:.3}\n\
+         fov_degrees   {:.1}\n\
+         ortho         {}\n\
+         cam_pos       {:.1} {:.1} {:.1}\n\
+         \n\
+         TERRAIN\n\
+         f1_octaves    {}\n\
+         f2_octaves    {}\n\
+         f3_octaves    {}\n\
+         f1 freq/amp   {:.2} {:.2}\n\
+         f2 freq/amp   {:.2} {:.2}\n\
+         f3 freq/amp   {:.2} {:.2}\n\
+         seed          {}",
+        rp.epsilon,
+        rp.epsilon_scale,
+        rp.max_dist,
+        rp.max_steps,
+        vp.x_shift,
+        vp.y_shift,
+        vp.zoom,
+        vp.x_rot,
+        vp.y_rot,
+        vp.z_rot,
+        vp.time_modifier,
+        vp.fov_degrees,
+        vp.ortho > 0.5,
+        vp.cam_pos[0],
+        vp.cam_pos[1],
+        vp.cam_pos[2],
+        tp.f1_octaves,
+        tp.f2_octaves,
+        tp.f3_octaves,
+        tp.f1_freq,
+        tp.f1_amp,
+        tp.f2_freq,
+        tp.f2_amp,
+        tp.f3_freq,
+        tp.f3_amp,
+        tp.seed,
+    )
+}
+
+// Frame rate averaged over FRAME_STATS_WINDOW seconds, so the readout is steady
+const FRAME_STATS_WINDOW: f32 = 0.5;
+
+#[derive(Debug, Default)]
+pub(crate) struct FrameStats {
+    frames: u32,
+    elapsed: f32,
+    fps: f32,
+    avg_frame_ms: f32,
+}
+
+impl FrameStats {
+    pub(crate) fn record(&mut self, frame_dt: f32) {
+        self.frames += 1;
+        self.elapsed += frame_dt;
+        if self.elapsed >= FRAME_STATS_WINDOW {
+            self.fps = self.frames as f32 / self.elapsed;
+            self.avg_frame_ms = self.elapsed * 1000.0 / self.frames as f32;
+            self.frames = 0;
+            self.elapsed = 0.0;
+        }
+    }
+
+    pub(crate) fn text(&self, gpu: Option<GpuTimings>) -> String {
+        let mut text = format!("{:.0} fps  {:.2} ms", self.fps, self.avg_frame_ms);
+        if let Some(gpu) = gpu {
+            if let Some(gen_ns) = gpu.terrain_gen_ns {
+                text += &format!("\ngpu terrain {:.2} ms", gen_ns / 1e6);
+            }
+            text += &format!("\ngpu render  {:.2} ms", gpu.render_ns / 1e6);
+        }
+        text
+    }
+}
@@ -0,0 +1,62 @@
+use std::fmt;
+
+// Why State couldn't be created. main adds a hint on what to try for each
+#[derive(Debug)]
+pub(crate) enum InitError {
+    Surface(wgpu::CreateSurfaceError),
+    // No adapter for the requested backends
+    Adapter,
+    // The adapter has no formats for the window's surface
+    Present,
+    TerrainResolution { requested: u32, min: u32, max: u32 },
+    // Each limit the adapter falls short on, with what's needed and what it has
+    Limits(Vec<String>),
+    Device(wgpu::RequestDeviceError),
+    // Shader and pipeline creation, already carrying their own context
+    Resources(anyhow::Error),
+}
+
+impl fmt::Display for InitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InitError::Surface(_) => {
+                write!(f, "Failed to create a rendering surface for the window")
+            }
+            InitError::Adapter => write!(f, "No compatible GPU adapter found"),
+            InitError::Present => {
+                write!(f, "The GPU adapter can't present to this window's surface")
+            }
+            InitError::TerrainResolution {
+                requested,
+                min,
+                max,
+            } => write!(
+                f,
+                "Terrain resolution {} is outside the {}..={} this GPU supports",
+                requested, min, max
+            ),
+            InitError::Limits(exceeded) => write!(
+                f,
+                "The GPU adapter's limits are too low: {}",
+                exceeded.join(", ")
+            ),
+            InitError::Device(_) => write!(f, "Failed to acquire a device from the GPU adapter"),
+            InitError::Resources(_) => write!(f, "Failed to create the GPU resources"),
+        }
+    }
+}
+
+// The wrapped error is the source rather than part of the message, so {:#} prints each once
+impl std::error::Error for InitError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            InitError::Surface(e) => Some(e),
+            InitError::Device(e) => Some(e),
+            InitError::Resources(e) => Some(e.as_ref()),
+            InitError::Adapter
+            | InitError::Present
+            | InitError::TerrainResolution { .. }
+            | InitError::Limits(_) => None,
+        }
+    }
+}
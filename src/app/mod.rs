@@ -1,2 +1,26 @@
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) mod autotune;
+pub(crate) mod camera;
+pub(crate) mod capture;
+pub(crate) mod clock;
+pub(crate) mod config;
 pub(crate) mod controls;
+pub(crate) mod custom_sdf;
+pub(crate) mod debug_readback;
+#[cfg(feature = "gamepad")]
+pub(crate) mod gamepad;
+pub(crate) mod gpu_timer;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) mod headless;
+pub(crate) mod heightmap;
+pub(crate) mod hud;
+pub(crate) mod init_error;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) mod motion_blur;
+pub(crate) mod recording;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) mod selftest;
 pub(crate) mod state;
+pub(crate) mod surface_error;
+pub(crate) mod text_overlay;
+pub(crate) mod view_token;
@@ -0,0 +1,309 @@
+use std::path::Path;
+
+use crate::collections::consts::ACCUMULATION_FORMAT;
+use crate::init::init_functions::{
+    create_shader_module, init_msaa_texture, validation_scope, MOTION_BLUR_SOURCE,
+};
+#[cfg(feature = "validate-bindings")]
+use crate::init::validate_bindings::validate_bindings;
+use crate::updates::param_updates::update_tone_params_buffer;
+
+use super::state::State;
+
+// Sub-frames averaged into the --headless frame. The shutter is the fraction of the
+// frame interval they cover, opening at the frame's own time
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct MotionBlur {
+    pub(crate) samples: u32,
+    pub(crate) shutter: f32,
+}
+
+impl MotionBlur {
+    // Evenly spaced over the open shutter, for a frame at `start` with the next one at
+    // `end`. A single sample is the unblurred frame
+    fn subframe_times(self, start: f32, end: f32) -> impl Iterator<Item = f32> {
+        let open = (end - start) * self.shutter;
+        (0..self.samples).map(move |i| start + open * i as f32 / self.samples as f32)
+    }
+}
+
+// Tone maps the summed sub-frames into the frame, see motion_blur.wgsl
+struct MotionBlurResolve {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+}
+
+impl MotionBlurResolve {
+    fn new(state: &State, accum_view: &wgpu::TextureView) -> anyhow::Result<Self> {
+        let device = &state.device;
+        let entries = [
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ];
+        #[cfg(feature = "validate-bindings")]
+        validate_bindings("Motion Blur Shader", MOTION_BLUR_SOURCE, &[&entries]);
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &entries,
+            label: Some("motion_blur_bgl"),
+        });
+        // The tone params without linear_output, the shader only reads the curve
+        let tone_params = wgpu::util::DeviceExt::create_buffer_init(
+            device.as_ref(),
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Motion Blur Tone Params Buffer"),
+                contents: bytemuck::bytes_of(&state.params.tone_params),
+                usage: wgpu::BufferUsages::UNIFORM,
+            },
+        );
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(accum_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&state.textures.ss_sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: tone_params.as_entire_binding(),
+                },
+            ],
+            label: Some("motion_blur_bg"),
+        });
+
+        let shader = create_shader_module(device, "Motion Blur Shader", MOTION_BLUR_SOURCE)?;
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Motion Blur Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = validation_scope(device, "Motion Blur Pipeline", || {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Motion Blur Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: state.color_format,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        })?;
+
+        Ok(Self {
+            pipeline,
+            bind_group,
+        })
+    }
+
+    fn encode(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let mut resolve_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Motion Blur Resolve Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    // Every pixel is overwritten
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+
+        resolve_pass.set_pipeline(&self.pipeline);
+        resolve_pass.set_bind_group(0, &self.bind_group, &[]);
+        resolve_pass.draw(0..3, 0..1);
+    }
+}
+
+impl<'a> State<'a> {
+    // Render the frame at the current time as the average of the sub-frames across the
+    // shutter and save it as a PNG. The sub-frames are summed in linear light and only
+    // then tone mapped, each one re-running the terrain passes at its own time so
+    // animated terrain blurs too. Leaves the clock a frame on, like an unblurred frame
+    pub(crate) fn capture_motion_blur(
+        &mut self,
+        path: &Path,
+        blur: MotionBlur,
+    ) -> anyhow::Result<()> {
+        let start = self.frame_time();
+        self.advance_time();
+        let end = self.frame_time();
+
+        let render_size = self.render_size();
+        let accum_tex = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Motion Blur Accumulation Texture"),
+            size: wgpu::Extent3d {
+                width: render_size.width,
+                height: render_size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: ACCUMULATION_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let accum_view = accum_tex.create_view(&wgpu::TextureViewDescriptor::default());
+        let msaa_view = init_msaa_texture(
+            &self.device,
+            ACCUMULATION_FORMAT,
+            render_size.width,
+            render_size.height,
+            self.sample_count,
+        );
+
+        self.params.tone_params.linear_output = 1;
+        update_tone_params_buffer(self);
+        let weight = 1.0 / blur.samples as f64;
+        for (i, time) in blur.subframe_times(start, end).enumerate() {
+            let mut encoder = self
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Motion Blur Encoder"),
+                });
+            self.encode_headless_terrain(&mut encoder, time);
+            self.encode_accumulate_pass(
+                &mut encoder,
+                &accum_view,
+                msaa_view.as_ref(),
+                i == 0,
+                weight,
+            );
+            self.queue.submit(Some(encoder.finish()));
+        }
+        self.params.tone_params.linear_output = 0;
+        update_tone_params_buffer(self);
+
+        let resolve = MotionBlurResolve::new(self, &accum_view)?;
+        let pixels = self.read_target(|_, encoder, view| resolve.encode(encoder, view))?;
+        self.save_frame(path, &pixels)
+    }
+
+    // Adds one sub-frame at `weight` to the accumulation target. The MSAA samples are
+    // summed across passes and resolved after each one, so the last leaves the total
+    fn encode_accumulate_pass(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        accum_view: &wgpu::TextureView,
+        msaa_view: Option<&wgpu::TextureView>,
+        first: bool,
+        weight: f64,
+    ) {
+        let (color_view, resolve_target) = match msaa_view {
+            Some(msaa_view) => (msaa_view, Some(accum_view)),
+            None => (accum_view, None),
+        };
+
+        let mut accumulate_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Motion Blur Accumulate Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: color_view,
+                resolve_target,
+                ops: wgpu::Operations {
+                    load: if first {
+                        wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT)
+                    } else {
+                        wgpu::LoadOp::Load
+                    },
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.textures.depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            ..Default::default()
+        });
+
+        accumulate_pass.set_blend_constant(wgpu::Color {
+            r: weight,
+            g: weight,
+            b: weight,
+            a: weight,
+        });
+        self.draw_scene(&mut accumulate_pass, &self.pipelines.accumulate);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_sample_is_the_unblurred_frame() {
+        let blur = MotionBlur {
+            samples: 1,
+            shutter: 0.5,
+        };
+        assert_eq!(blur.subframe_times(2.0, 2.5).collect::<Vec<_>>(), [2.0]);
+    }
+
+    #[test]
+    fn subframes_cover_the_open_shutter() {
+        let blur = MotionBlur {
+            samples: 4,
+            shutter: 0.5,
+        };
+        assert_eq!(
+            blur.subframe_times(1.0, 2.0).collect::<Vec<_>>(),
+            [1.0, 1.125, 1.25, 1.375]
+        );
+    }
+
+    // A pinned or paused clock doesn't move between frames, so neither do the sub-frames
+    #[test]
+    fn held_time_gives_identical_subframes() {
+        let blur = MotionBlur {
+            samples: 8,
+            shutter: 1.0,
+        };
+        assert!(blur.subframe_times(3.0, 3.0).all(|time| time == 3.0));
+    }
+}
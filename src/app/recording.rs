@@ -0,0 +1,292 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, SyncSender};
+use std::thread::JoinHandle;
+
+use anyhow::Context;
+
+use crate::collections::consts::{RECORD_FPS, RECORD_QUEUE_FRAMES, RECORD_READBACK_SLOTS};
+use crate::updates::readback::{map_buffer, MapReceiver};
+
+use super::capture::{padded_bytes_per_row, swaps_red_blue, take_rgba_rows};
+use super::state::State;
+
+struct RecordedFrame {
+    path: PathBuf,
+    pixels: Vec<u8>,
+    width: u32,
+    height: u32,
+}
+
+// A frame sequence being written to disk. Each frame is copied out of the one shown in
+// the window, in the same encoder, into a ring of readback buffers that are mapped
+// without waiting and collected a frame or two later. Only the PNG encoding and writing
+// happens on a separate thread, the bounded queue only holds up rendering if the disk
+// falls behind
+#[derive(Debug)]
+pub(crate) struct Recording {
+    dir: PathBuf,
+    frame_count: u32,
+    // Frames copied so far, and how many of those have been handed to the writer
+    frames_captured: u32,
+    frames_sent: u32,
+    // Shader time of the first frame, later frames step by 1 / RECORD_FPS
+    start_time: f32,
+    size: winit::dpi::PhysicalSize<u32>,
+    swap_red_blue: bool,
+    readback: Vec<wgpu::Buffer>,
+    // Slots holding a submitted copy, oldest first, with their pending map
+    in_flight: VecDeque<(usize, Option<MapReceiver>)>,
+    // Rendered into alongside the window when the surface can't be copied from
+    target: Option<(wgpu::Texture, wgpu::TextureView)>,
+    // Taken on drop so the writer sees the queue close
+    sender: Option<SyncSender<RecordedFrame>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl Recording {
+    // Time for the next frame, independent of the real frame rate
+    pub(crate) fn time(&self) -> f32 {
+        self.start_time + self.frames_captured as f32 / RECORD_FPS
+    }
+
+    fn free_slot(&self) -> Option<usize> {
+        (0..self.readback.len()).find(|slot| !self.in_flight.iter().any(|(s, _)| s == slot))
+    }
+
+    // Hand every mapped frame at the front of the ring to the writer, in order. With
+    // `wait` the device is waited on until the ring is empty. False once the writer
+    // has gone
+    fn collect(&mut self, device: &wgpu::Device, wait: bool) -> anyhow::Result<bool> {
+        while let Some((slot, map)) = self.in_flight.front_mut() {
+            let Some(map) = map else {
+                break;
+            };
+            device.poll(if wait {
+                wgpu::Maintain::Wait
+            } else {
+                wgpu::Maintain::Poll
+            });
+            let mapped = match map.try_recv() {
+                Ok(None) => break,
+                Ok(Some(mapped)) => mapped.context("Recording readback failed to map"),
+                Err(_) => Err(anyhow::anyhow!("Recording readback was cancelled")),
+            };
+            let slot = *slot;
+            self.in_flight.pop_front();
+            mapped?;
+
+            let pixels = take_rgba_rows(&self.readback[slot], self.size.width, self.swap_red_blue);
+            if !self.send(pixels) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn send(&mut self, pixels: Vec<u8>) -> bool {
+        self.frames_sent += 1;
+        let frame = RecordedFrame {
+            path: self.dir.join(format!("frame_{:05}.png", self.frames_sent)),
+            pixels,
+            width: self.size.width,
+            height: self.size.height,
+        };
+        self.sender
+            .as_ref()
+            .is_some_and(|sender| sender.send(frame).is_ok())
+    }
+}
+
+// Waits for the queued frames to be written
+impl Drop for Recording {
+    fn drop(&mut self) {
+        self.sender.take();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+impl<'a> State<'a> {
+    // Write the next `frame_count` rendered frames to `dir` as frame_00001.png, ...
+    pub(crate) fn start_recording(&mut self, dir: &Path, frame_count: u32) -> anyhow::Result<()> {
+        let swap_red_blue = swaps_red_blue(self.color_format)?;
+        let Some(window_surface) = &self.window_surface else {
+            anyhow::bail!("Recording needs a window");
+        };
+        let copy_surface = window_surface
+            .config
+            .usage
+            .contains(wgpu::TextureUsages::COPY_SRC);
+
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+
+        let size = winit::dpi::PhysicalSize::new(self.size.width.max(1), self.size.height.max(1));
+        let readback = (0..RECORD_READBACK_SLOTS)
+            .map(|_| {
+                self.device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Recording CPU Readable Buffer"),
+                    size: (padded_bytes_per_row(size.width) * size.height) as wgpu::BufferAddress,
+                    usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                    mapped_at_creation: false,
+                })
+            })
+            .collect();
+        let target = (!copy_surface).then(|| {
+            let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("Recording Render Target"),
+                size: wgpu::Extent3d {
+                    width: size.width,
+                    height: size.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: self.color_format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            (texture, view)
+        });
+
+        let (sender, receiver) = mpsc::sync_channel::<RecordedFrame>(RECORD_QUEUE_FRAMES);
+        let writer = std::thread::Builder::new()
+            .name("recording writer".to_string())
+            .spawn(move || {
+                for frame in receiver {
+                    let saved = image::save_buffer(
+                        &frame.path,
+                        &frame.pixels,
+                        frame.width,
+                        frame.height,
+                        image::ColorType::Rgba8,
+                    );
+                    if let Err(e) = saved {
+                        eprintln!("Failed to write {}: {}", frame.path.display(), e);
+                    }
+                }
+            })
+            .context("Failed to start the recording writer thread")?;
+
+        self.recording = Some(Recording {
+            dir: dir.to_path_buf(),
+            frame_count,
+            frames_captured: 0,
+            frames_sent: 0,
+            start_time: self.frame_time(),
+            size,
+            swap_red_blue,
+            readback,
+            in_flight: VecDeque::new(),
+            target,
+            sender: Some(sender),
+            writer: Some(writer),
+        });
+        Ok(())
+    }
+
+    // Copies the frame about to be presented into a free readback slot. Only waits on
+    // the GPU when every slot is still in flight
+    pub(crate) fn encode_recording(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        output: &wgpu::Texture,
+    ) {
+        let Some(recording) = &mut self.recording else {
+            return;
+        };
+        if recording.size != self.size {
+            eprintln!("Recording stopped: the window was resized");
+            self.stop_recording();
+            return;
+        }
+
+        // Every slot in flight means the GPU is RECORD_READBACK_SLOTS frames behind,
+        // waiting for it lands them all
+        let slot = match recording.free_slot() {
+            Some(slot) => Some(slot),
+            None => match recording.collect(&self.device, true) {
+                Ok(true) => recording.free_slot(),
+                _ => None,
+            },
+        };
+        let Some(slot) = slot else {
+            eprintln!("Recording stopped: a frame couldn't be read back");
+            self.stop_recording();
+            return;
+        };
+
+        // Without a copyable surface the same frame is drawn again into the target
+        let Some(mut recording) = self.recording.take() else {
+            return;
+        };
+        if let Some((_, view)) = &recording.target {
+            self.encode_render_pass(encoder, view);
+            self.encode_text_overlay(encoder, view);
+        }
+        let source = recording
+            .target
+            .as_ref()
+            .map_or(output, |(texture, _)| texture);
+        encoder.copy_texture_to_buffer(
+            source.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &recording.readback[slot],
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row(recording.size.width)),
+                    rows_per_image: Some(recording.size.height),
+                },
+            },
+            wgpu::Extent3d {
+                width: recording.size.width,
+                height: recording.size.height,
+                depth_or_array_layers: 1,
+            },
+        );
+        recording.in_flight.push_back((slot, None));
+        recording.frames_captured += 1;
+        self.recording = Some(recording);
+    }
+
+    // Called after each submitted frame while recording. Maps the frame just copied and
+    // writes out the earlier ones that have landed since
+    pub(crate) fn record_frame(&mut self) {
+        let Some(recording) = &mut self.recording else {
+            return;
+        };
+        for (slot, map) in recording.in_flight.iter_mut() {
+            if map.is_none() {
+                *map = Some(map_buffer(&recording.readback[*slot]));
+            }
+        }
+
+        match recording.collect(&self.device, false) {
+            Ok(true) if recording.frames_captured < recording.frame_count => {}
+            Ok(_) => self.stop_recording(),
+            Err(e) => {
+                eprintln!("Recording stopped: {:#}", e);
+                self.stop_recording();
+            }
+        }
+    }
+
+    // Finishes writing the frames already copied
+    pub(crate) fn stop_recording(&mut self) {
+        let Some(mut recording) = self.recording.take() else {
+            return;
+        };
+        if let Err(e) = recording.collect(&self.device, true) {
+            eprintln!("Recording readback failed: {:#}", e);
+        }
+        // Carry on from the recording's time rather than jumping back to the real clock
+        self.set_time(recording.time());
+        let (frames, dir) = (recording.frames_sent, recording.dir.clone());
+        drop(recording);
+        println!("Recorded {} frames to {}", frames, dir.display());
+    }
+}
@@ -0,0 +1,224 @@
+use anyhow::bail;
+
+use crate::collections::structs::TimeUniform;
+use crate::updates::readback::{map_readback, read_buffer};
+
+use super::state::State;
+
+// Side of the square read_terrain_block copies out
+const BLOCK: u32 = 64;
+
+impl<'a> State<'a> {
+    // Render a single frame offscreen and check it isn't uniformly black,
+    // which would mean the compute pass didn't run, a format mismatch or a blank SDF
+    pub(crate) fn self_test(&mut self) -> anyhow::Result<()> {
+        let width = self.size.width.max(1);
+        let height = self.size.height.max(1);
+        // Has to match the render pipeline
+        let format = self.color_format;
+
+        let target = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("selftest - Render Target"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let unpadded_bytes_per_row = width * 4;
+        let bytes_per_row =
+            unpadded_bytes_per_row.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+
+        let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("selftest - CPU Readable Buffer"),
+            size: (bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        self.queue.write_buffer(
+            &self.buffers.time_uniform,
+            0,
+            bytemuck::cast_slice(&[TimeUniform {
+                time: self.frame_time(),
+            }]),
+        );
+
+        self.finish_terrain_generation();
+
+        self.check_terrain_generated()?;
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("selftest normals encoder"),
+            });
+        self.encode_terrain_normals(&mut encoder);
+        self.queue.submit(Some(encoder.finish()));
+
+        self.check_terrain_normals()?;
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("selftest encoder"),
+            });
+        self.encode_terrain_mips(&mut encoder);
+        self.encode_terrain_conversion(&mut encoder);
+        self.encode_render_pass(&mut encoder, &view);
+
+        encoder.copy_texture_to_buffer(
+            target.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            target.size(),
+        );
+
+        self.queue.submit(Some(encoder.finish()));
+
+        let buffer_slice = readback.slice(..);
+        map_readback(&self.device, &readback)?;
+
+        let lit_pixels = {
+            let data = buffer_slice.get_mapped_range();
+            data.chunks(bytes_per_row as usize)
+                .flat_map(|row| row[..unpadded_bytes_per_row as usize].chunks(4))
+                .filter(|px| px[..3].iter().any(|c| *c != 0))
+                .count()
+        };
+        readback.unmap();
+
+        let total_pixels = (width * height) as usize;
+        println!(
+            "selftest: {} of {} pixels non-black ({}x{} {:?})",
+            lit_pixels, total_pixels, width, height, format
+        );
+
+        if lit_pixels == 0 {
+            bail!("selftest: rendered frame is uniformly black");
+        }
+
+        Ok(())
+    }
+
+    // Check the compute pass wrote something other than the zeroed initial data
+    fn check_terrain_generated(&self) -> anyhow::Result<()> {
+        let values = self.read_terrain_block(&self.textures.terrain_tex)?;
+        let non_zero = values.iter().filter(|v| **v != 0.0).count();
+
+        println!(
+            "selftest: {} of {} terrain values non-zero",
+            non_zero,
+            values.len()
+        );
+
+        if non_zero == 0 {
+            bail!("selftest: terrain texture is still zeroed after generation");
+        }
+
+        Ok(())
+    }
+
+    // Every normal should be unit length and point up out of the heightfield, and away
+    // from the block's edges match terrain_normals.wgsl redone on the CPU from the heights
+    fn check_terrain_normals(&self) -> anyhow::Result<()> {
+        let heights = self.read_terrain_block(&self.textures.terrain_tex)?;
+        let values = self.read_terrain_block(&self.textures.terrain_normal_tex)?;
+        let block = BLOCK as usize;
+        let scale = 0.5 * self.terrain_resolution as f32;
+        let height = |x: usize, y: usize| heights[(y * block + x) * 4];
+
+        let bad = values
+            .chunks_exact(4)
+            .enumerate()
+            .filter(|(i, n)| {
+                let length = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+                if !((length - 1.0).abs() < 1e-3 && n[1] > 0.0) {
+                    return true;
+                }
+
+                let (x, y) = (i % block, i / block);
+                if x == 0 || y == 0 || x == block - 1 || y == block - 1 {
+                    return false;
+                }
+                let dx = (height(x + 1, y) - height(x - 1, y)) * scale;
+                let dy = (height(x, y + 1) - height(x, y - 1)) * scale;
+                let expected = nalgebra::Vector3::new(-dx, 1.0, -dy).normalize();
+                (0..3).any(|c| (n[c] - expected[c]).abs() > 1e-3)
+            })
+            .count();
+
+        println!(
+            "selftest: {} of {} terrain normals invalid",
+            bad,
+            values.len() / 4
+        );
+
+        if bad > 0 {
+            bail!("selftest: normal texture isn't populated from the terrain");
+        }
+
+        Ok(())
+    }
+
+    // A BLOCK x BLOCK square from the middle of a terrain sized Rgba32Float texture
+    fn read_terrain_block(&self, texture: &wgpu::Texture) -> anyhow::Result<Vec<f32>> {
+        // Rgba32Float
+        let bytes_per_row = BLOCK * 16;
+
+        let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("selftest - Terrain CPU Readable Buffer"),
+            size: (bytes_per_row * BLOCK) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("selftest terrain readback encoder"),
+            });
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: (self.terrain_resolution - BLOCK) / 2,
+                    y: (self.terrain_resolution - BLOCK) / 2,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: Some(BLOCK),
+                },
+            },
+            wgpu::Extent3d {
+                width: BLOCK,
+                height: BLOCK,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        futures::executor::block_on(read_buffer(&self.device, &readback))
+    }
+}
@@ -1,131 +1,429 @@
 use crate::{
     collections::{
-        structs::{BindGroups, Buffers, Params, Pipelines},
+        consts::{
+            dispatch_size, terrain_gen_tile_groups, terrain_gen_tiles_per_side,
+            ACCUMULATION_FORMAT, BRUSH_WORKGROUP_SIZE, DEFAULT_TERRAIN_GEN_TILES, DEPTH_FORMAT,
+            IDLE_DELAY_SECS, LOADING_CLEAR_COLOR, MIN_TERRAIN_RESOLUTION,
+            TERRAIN_MIP_WORKGROUP_SIZE, TERRAIN_WORKGROUP_SIZE, WINDOW_TITLE,
+        },
+        structs::{
+            BindGroups, Buffers, Params, Pipelines, RayParams, Resources, ScreenParams, TerrainGen,
+            Textures, ViewParams,
+        },
         vertices::VERTICES,
     },
     init::init_functions::{
-        init_bind_groups, init_buffers, init_params, init_pipelines, init_shader_modules,
-        init_textures,
+        build_resources, init_depth_texture, init_downsample_bind_group, init_msaa_texture,
+        init_ss_texture,
     },
-    updates::param_updates::{update_cpu_read_buffers, update_view_params_buffer},
+    updates::param_updates::{update_cpu_read_buffers, update_screen_params_buffer},
+    util::{rng::Rng, time},
 };
+use std::collections::HashMap;
 use std::sync::Arc;
 
-use super::controls::{update_controls, KeyboardState};
+use super::camera::{CameraVelocity, CanonicalView, ViewTransition};
+use super::clock::{SceneClock, TimeSource};
+use super::controls::{integrate_camera_velocity, update_controls, KeyboardState, MouseState};
+use super::custom_sdf::CustomSdf;
+use super::debug_readback::PendingReadback;
+use super::gpu_timer::GpuTimer;
+use super::hud::{param_overlay_text, FrameStats};
+use super::init_error::InitError;
+use super::recording::Recording;
+use super::surface_error::SurfaceErrorPolicy;
+use super::text_overlay::{TextOverlay, TextPanel};
+#[cfg(feature = "gamepad")]
+use super::{controls::gamepad_controls, gamepad::Gamepad};
+
+// Progress of the tiled terrain generation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum GenerationState {
+    // Queued, no tile dispatched yet
+    Pending,
+    // Holds the next tile to dispatch, row major
+    InProgress(u32),
+    Done,
+}
+
+impl GenerationState {
+    fn next_tile(self) -> Option<u32> {
+        match self {
+            Self::Pending => Some(0),
+            Self::InProgress(tile) => Some(tile),
+            Self::Done => None,
+        }
+    }
+}
+
+// The window and the surface presenting to it
+#[derive(Debug)]
+pub(crate) struct WindowSurface<'a> {
+    pub(crate) surface: wgpu::Surface<'a>,
+    pub(crate) config: wgpu::SurfaceConfiguration,
+    pub(crate) present_modes: Vec<wgpu::PresentMode>,
+    // Must be dropped after surface
+    pub(crate) window: Arc<winit::window::Window>,
+}
 
 #[derive(Debug)]
 pub(crate) struct State<'a> {
-    pub(crate) device: wgpu::Device,
+    // Shared with pending readbacks, which poll it from their own futures
+    pub(crate) device: Arc<wgpu::Device>,
     pub(crate) queue: wgpu::Queue,
-    pub(crate) surface: wgpu::Surface<'a>,
-    pub(crate) surface_config: wgpu::SurfaceConfiguration,
+    // The adapter the device was requested from, for bug reports
+    adapter_info: wgpu::AdapterInfo,
+    // Format of the frames rendered, the surface's or the headless render target's
+    pub(crate) color_format: wgpu::TextureFormat,
     pub(crate) size: winit::dpi::PhysicalSize<u32>,
+    // MSAA samples of the render pass, 1 when disabled
+    pub(crate) sample_count: u32,
+    // Supersampling, the scene is rendered at this multiple of the window size and
+    // filtered down. 1 when disabled
+    pub(crate) ss_factor: u32,
+    // Side of the square terrain textures, fixed once they're created
+    pub(crate) terrain_resolution: u32,
     pub(crate) params: Params,
+    // Last values written to the view/ray params buffers
+    pub(crate) uploaded_view_params: ViewParams,
+    pub(crate) uploaded_ray_params: RayParams,
     pub(crate) buffers: Buffers,
     pub(crate) bind_groups: BindGroups,
+    pub(crate) textures: Textures,
     pub(crate) pipelines: Pipelines,
     pub(crate) controls: KeyboardState,
-    pub(crate) app_time: std::time::Instant,
-    // Keep window at the bottom,
-    // must be dropped after surface
-    pub(crate) window: std::sync::Arc<winit::window::Window>,
+    pub(crate) mouse: MouseState,
+    pub(crate) text_overlay: TextOverlay,
+    pub(crate) show_param_overlay: bool,
+    pub(crate) show_frame_stats: bool,
+    pub(crate) frame_stats: FrameStats,
+    // None when the adapter doesn't support timestamp queries
+    pub(crate) gpu_timer: Option<GpuTimer>,
+    // DEBUG mode buffer dump waiting to be mapped
+    pub(crate) debug_readback: Option<PendingReadback>,
+    // Pixel the generic debug buffer reads, in window pixels. None for the screen center
+    pub(crate) debug_pixel: Option<winit::dpi::PhysicalPosition<u32>>,
+    // Last preset view snapped to, kept while nudging away from it
+    pub(crate) canonical_view: Option<CanonicalView>,
+    // Views stored with Ctrl+digit, keyed by the digit
+    pub(crate) bookmarks: HashMap<u8, ViewParams>,
+    pub(crate) view_transition: Option<ViewTransition>,
+    pub(crate) camera_velocity: CameraVelocity,
+    // Set when brush_params holds a dab that hasn't been applied yet
+    pub(crate) brush_pending: bool,
+    // Generation is split into terrain_gen_tiles x terrain_gen_tiles tiles, 1 for a
+    // single dispatch
+    pub(crate) terrain_gen_tiles: u32,
+    pub(crate) terrain_gen: GenerationState,
+    // Set once the terrain has been generated or loaded in full. Until then render draws
+    // a loading screen instead of the scene, later regenerations keep the scene up
+    pub(crate) terrain_ready: bool,
+    // The half precision terrain copy is behind the full precision texture
+    pub(crate) terrain_half_stale: bool,
+    // The normal texture and mip levels are behind the terrain
+    pub(crate) terrain_normals_stale: bool,
+    pub(crate) terrain_mips_stale: bool,
+    pub(crate) log_param_changes: bool,
+    pub(crate) custom_sdf: Option<CustomSdf>,
+    pub(crate) surface_error_policy: SurfaceErrorPolicy,
+    // Frames in a row that failed to render, reset on success
+    pub(crate) surface_error_count: u32,
+    // Fixed value for the time uniform, for reproducible captures
+    pub(crate) pinned_time: Option<f32>,
+    pub(crate) recording: Option<Recording>,
+    pub(crate) clock: SceneClock,
+    // Seeded from the clock, for randomizing params at runtime
+    pub(crate) rng: Rng,
+    #[cfg(feature = "gamepad")]
+    pub(crate) gamepad: Option<Gamepad>,
+    // Window size to restore when leaving fullscreen
+    windowed_size: Option<winit::dpi::PhysicalSize<u32>>,
+    // For the idle frame rate throttle
+    pub(crate) last_input: time::Instant,
+    pub(crate) last_frame: time::Instant,
+    // Seconds since the previous frame, for framerate independent movement
+    pub(crate) frame_dt: f32,
+    // Nothing is updated or rendered until the window is restored
+    pub(crate) minimized: bool,
+    // None when rendering headless
+    pub(crate) window_surface: Option<WindowSurface<'a>>,
 }
 
 impl<'a> State<'a> {
-    pub(crate) async fn new(window: Arc<winit::window::Window>) -> Self {
-        let size = window.inner_size();
-
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
-        let app_time = std::time::Instant::now();
-
+    pub(crate) fn new(
+        window: Arc<winit::window::Window>,
+        backends: wgpu::Backends,
+        params: Params,
+        sample_count: u32,
+        terrain_resolution: u32,
+    ) -> Result<Self, InitError> {
         // SURFACE
-        let surface = instance
-            .create_surface(Arc::clone(&window))
-            .expect("surface init should work");
+        let (instance, surface) = create_surface(&window, backends)?;
 
         // ADAPTER
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                force_fallback_adapter: false,
-                compatible_surface: Some(&surface),
-            })
-            .await
-            .expect("get_dev_storage_texture:: adapter should work");
-
-        let limits = adapter.limits();
-
-        // DEVICE/QUEUE
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: Some("dev_storage_texture_capable Device"),
-                    required_features: wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES
-                        | wgpu::Features::FLOAT32_FILTERABLE,
-                    required_limits: limits,
-                },
-                None,
-            )
-            .await
-            .expect("get_dev_storage_texture:: device request should work");
+        // Blocking here rather than making new async, init's validation scopes block
+        // on their own and can't run inside another executor
+        let adapter = futures::executor::block_on(request_adapter(&instance, Some(&surface)))?;
+        let gpu = futures::executor::block_on(request_device(&adapter, terrain_resolution))?;
+
+        Self::with_surface(
+            window,
+            surface,
+            &adapter,
+            gpu,
+            params,
+            sample_count,
+            terrain_resolution,
+        )
+    }
 
-        let surface_caps = surface.get_capabilities(&adapter);
+    // The browser can't block on the adapter and device requests, web::start awaits
+    // this from a spawned future instead. init skips its validation scopes there
+    #[cfg(target_arch = "wasm32")]
+    pub(crate) async fn new_async(
+        window: Arc<winit::window::Window>,
+        backends: wgpu::Backends,
+        params: Params,
+        sample_count: u32,
+        terrain_resolution: u32,
+    ) -> Result<Self, InitError> {
+        let (instance, surface) = create_surface(&window, backends)?;
+        let adapter = request_adapter(&instance, Some(&surface)).await?;
+        let gpu = request_device(&adapter, terrain_resolution).await?;
+
+        Self::with_surface(
+            window,
+            surface,
+            &adapter,
+            gpu,
+            params,
+            sample_count,
+            terrain_resolution,
+        )
+    }
+
+    // Configures the window's surface and builds the rest on top of it
+    fn with_surface(
+        window: Arc<winit::window::Window>,
+        surface: wgpu::Surface<'a>,
+        adapter: &wgpu::Adapter,
+        (device, queue): (wgpu::Device, wgpu::Queue),
+        params: Params,
+        sample_count: u32,
+        terrain_resolution: u32,
+    ) -> Result<Self, InitError> {
+        let size = window.inner_size();
+        let surface_caps = surface.get_capabilities(adapter);
+        // Empty when the adapter can't present to this surface
+        let Some(&first_format) = surface_caps.formats.first() else {
+            return Err(InitError::Present);
+        };
 
         let surface_format = surface_caps
             .formats
             .iter()
             .copied()
-            .filter(|f| f.is_srgb())
-            .next()
-            .unwrap_or(surface_caps.formats[0]);
+            .find(|f| f.is_srgb())
+            .unwrap_or(first_format);
 
+        // Recordings copy frames straight out of the surface where it allows that
         let surface_config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | (surface_caps.usages & wgpu::TextureUsages::COPY_SRC),
             format: surface_format,
             width: size.width,
             height: size.height,
             present_mode: surface_caps.present_modes[0],
             desired_maximum_frame_latency: 1,
-            view_formats: vec![wgpu::TextureFormat::Bgra8UnormSrgb],
+            view_formats: vec![surface_format],
             alpha_mode: surface_caps.alpha_modes[0],
         };
 
         surface.configure(&device, &surface_config);
+        println!(
+            "Surface format {:?}, present mode {:?}",
+            surface_config.format, surface_config.present_mode
+        );
 
-        let shader_modules = init_shader_modules(&device);
-        let params = init_params();
-        let buffers = init_buffers(&device, &params);
-        let textures = init_textures(&device, &queue);
-        let bind_groups = init_bind_groups(&device, &buffers, &textures);
-        let pipelines = init_pipelines(&device, &bind_groups, &shader_modules);
-        let controls = KeyboardState::new();
+        let window_surface = WindowSurface {
+            surface,
+            config: surface_config,
+            present_modes: surface_caps.present_modes.clone(),
+            window,
+        };
 
-        Self {
+        let sample_count = supported_sample_count(adapter, surface_format, sample_count);
+        Self::init(
             device,
             queue,
-            surface,
-            surface_config,
+            adapter.get_info(),
+            size,
+            surface_format,
+            params,
+            sample_count,
+            terrain_resolution,
+            Some(window_surface),
+        )
+    }
+
+    // No window or surface, frames are only rendered offscreen by capture_frame
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn new_headless(
+        backends: wgpu::Backends,
+        size: winit::dpi::PhysicalSize<u32>,
+        params: Params,
+        sample_count: u32,
+        terrain_resolution: u32,
+    ) -> Result<Self, InitError> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends,
+            ..Default::default()
+        });
+        let adapter = futures::executor::block_on(request_adapter(&instance, None))?;
+        let (device, queue) =
+            futures::executor::block_on(request_device(&adapter, terrain_resolution))?;
+
+        // Any format capture_frame can read back
+        let color_format = wgpu::TextureFormat::Bgra8UnormSrgb;
+        let sample_count = supported_sample_count(&adapter, color_format, sample_count);
+        Self::init(
+            device,
+            queue,
+            adapter.get_info(),
             size,
+            color_format,
+            params,
+            sample_count,
+            terrain_resolution,
+            None,
+        )
+    }
+
+    // The GPU resources and app state shared by the windowed and headless paths
+    #[allow(clippy::too_many_arguments)]
+    fn init(
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        adapter_info: wgpu::AdapterInfo,
+        size: winit::dpi::PhysicalSize<u32>,
+        color_format: wgpu::TextureFormat,
+        mut params: Params,
+        sample_count: u32,
+        terrain_resolution: u32,
+        window_surface: Option<WindowSurface<'a>>,
+    ) -> Result<Self, InitError> {
+        let app_time = time::Instant::now();
+        log_adapter_info(&adapter_info);
+        // The render pipeline and MSAA target are built for color_format
+        if let Some(window_surface) = &window_surface {
+            debug_assert_eq!(window_surface.config.format, color_format);
+        }
+
+        // The window may not have been created at the requested size
+        params.screen_params = ScreenParams::new(size.width.max(1), size.height.max(1));
+        params.tone_params.encode_srgb = !color_format.is_srgb() as u32;
+        let Resources {
+            buffers,
+            textures,
+            bind_groups,
             pipelines,
+        } = build_resources(
+            &device,
+            &params,
+            size,
+            color_format,
+            sample_count,
+            terrain_resolution,
+        )
+        .map_err(InitError::Resources)?;
+        let controls = KeyboardState::new();
+        let mouse = MouseState::new();
+        let text_overlay = TextOverlay::new(&device, &queue, color_format);
+        let gpu_timer = GpuTimer::new(&device, &queue);
+
+        Ok(Self {
+            device: Arc::new(device),
+            queue,
+            adapter_info,
+            color_format,
+            size,
+            sample_count,
+            ss_factor: 1,
+            terrain_resolution,
+            pipelines,
+            uploaded_view_params: params.view_params,
+            uploaded_ray_params: params.ray_params,
             params,
             buffers,
             bind_groups,
+            textures,
             controls,
-            app_time,
-            // Keep at bottom, must be dropped after surface
-            // and declared after it
-            window,
-        }
+            mouse,
+            text_overlay,
+            show_param_overlay: false,
+            show_frame_stats: false,
+            frame_stats: FrameStats::default(),
+            gpu_timer,
+            debug_readback: None,
+            debug_pixel: None,
+            canonical_view: None,
+            bookmarks: HashMap::new(),
+            view_transition: None,
+            camera_velocity: CameraVelocity::default(),
+            brush_pending: false,
+            terrain_gen_tiles: DEFAULT_TERRAIN_GEN_TILES,
+            terrain_gen: GenerationState::Pending,
+            terrain_ready: false,
+            terrain_half_stale: true,
+            terrain_normals_stale: true,
+            terrain_mips_stale: true,
+            log_param_changes: false,
+            custom_sdf: None,
+            surface_error_policy: SurfaceErrorPolicy::interactive(),
+            surface_error_count: 0,
+            pinned_time: None,
+            recording: None,
+            clock: SceneClock::new(TimeSource::Realtime),
+            #[cfg(feature = "gamepad")]
+            gamepad: Gamepad::new(),
+            rng: Rng::new(
+                time::SystemTime::now()
+                    .duration_since(time::SystemTime::UNIX_EPOCH)
+                    .map(|d| d.as_nanos() as u64)
+                    .unwrap_or_default(),
+            ),
+            windowed_size: None,
+            last_input: app_time,
+            last_frame: app_time,
+            frame_dt: 0.0,
+            minimized: false,
+            window_surface,
+        })
     }
 
     pub(crate) fn update(&mut self) {
+        self.frame_stats.record(self.frame_dt);
+        self.advance_time();
+        if let Some(timer) = &mut self.gpu_timer {
+            timer.poll(&self.device);
+        }
+        self.poll_debug_readback();
+        self.poll_custom_sdf();
         update_controls(self);
-        update_view_params_buffer(self);
-        update_cpu_read_buffers(self);
+        #[cfg(feature = "gamepad")]
+        gamepad_controls(self);
+        integrate_camera_velocity(self);
+        // The CPU readable copies can't be written while a dump has them mapped
+        if self.debug_readback.is_none() {
+            update_cpu_read_buffers(self);
+        }
     }
 
     pub(crate) fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let output = self.surface.get_current_texture()?;
+        let Some(window_surface) = &self.window_surface else {
+            return Ok(());
+        };
+        let output = window_surface.surface.get_current_texture()?;
         let view = output
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
@@ -136,48 +434,767 @@ impl<'a> State<'a> {
                 label: Some("Render Encoder"),
             });
 
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                        store: wgpu::StoreOp::Store,
+        self.encode_terrain_generation(&mut encoder);
+        self.encode_terrain_brush(&mut encoder);
+        self.encode_terrain_normals(&mut encoder);
+        self.encode_terrain_mips(&mut encoder);
+        self.encode_terrain_conversion(&mut encoder);
+
+        if self.terrain_ready {
+            self.encode_render_pass(&mut encoder, &view);
+            self.encode_text_overlay(&mut encoder, &view);
+            if let Some(timer) = &mut self.gpu_timer {
+                timer.resolve(&mut encoder);
+            }
+        } else {
+            self.encode_loading_screen(&mut encoder, &view);
+        }
+        self.encode_recording(&mut encoder, &output.texture);
+
+        self.queue.submit(Some(encoder.finish()));
+        if let Some(timer) = &mut self.gpu_timer {
+            timer.submitted();
+        }
+        output.present();
+        self.record_frame();
+        self.text_overlay.trim();
+        self.surface_error_count = 0;
+
+        Ok(())
+    }
+
+    pub(crate) fn encode_render_pass(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+    ) {
+        // With supersampling the scene goes to the larger target, and the downsample
+        // pass below filters it into `view`
+        let scene_view = self.textures.ss_view.as_ref().unwrap_or(view);
+        let (color_view, resolve_target) = match &self.textures.msaa_view {
+            Some(msaa_view) => (msaa_view, Some(scene_view)),
+            None => (scene_view, None),
+        };
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            timestamp_writes: self.gpu_timer.as_ref().and_then(GpuTimer::render_writes),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: color_view,
+                resolve_target,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    // Only the resolved image is needed after the pass
+                    store: if resolve_target.is_some() {
+                        wgpu::StoreOp::Discard
+                    } else {
+                        wgpu::StoreOp::Store
                     },
-                })],
-                ..Default::default()
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.textures.depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            ..Default::default()
+        });
+
+        self.draw_scene(&mut render_pass, &self.pipelines.render);
+        drop(render_pass);
+
+        if let Some(downsample_bg) = &self.bind_groups.downsample_bg {
+            self.encode_downsample(encoder, view, downsample_bg);
+        }
+    }
+
+    // The scene's bind groups and fullscreen quad, drawn with `pipeline`
+    pub(crate) fn draw_scene<'p>(
+        &'p self,
+        render_pass: &mut wgpu::RenderPass<'p>,
+        pipeline: &'p wgpu::RenderPipeline,
+    ) {
+        render_pass.set_pipeline(pipeline);
+
+        render_pass.set_bind_group(0, &self.bind_groups.uniform_bg, &[]);
+        render_pass.set_bind_group(1, &self.bind_groups.frag_bg, &[]);
+        let sampled_texture_bg = if self.params.precision_params.render_half != 0 {
+            &self.bind_groups.sampled_texture_half_bg
+        } else {
+            &self.bind_groups.sampled_texture_bg
+        };
+        render_pass.set_bind_group(2, sampled_texture_bg, &[]);
+        render_pass.set_bind_group(3, &self.bind_groups.debug_bg, &[]);
+        render_pass.set_vertex_buffer(0, self.buffers.vertex.slice(..));
+
+        let vertex_range = 0..VERTICES.len() as u32;
+        let instance_range = 0..1;
+        render_pass.draw(vertex_range, instance_range);
+    }
+
+    fn encode_downsample(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        downsample_bg: &wgpu::BindGroup,
+    ) {
+        let mut downsample_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Downsample Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    // Every pixel is overwritten
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+
+        downsample_pass.set_pipeline(&self.pipelines.downsample);
+        downsample_pass.set_bind_group(0, downsample_bg, &[]);
+        downsample_pass.draw(0..3, 0..1);
+    }
+
+    // Shown while the terrain is first generated, the render pass would only show the
+    // tiles done so far
+    fn encode_loading_screen(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+    ) {
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Loading Screen Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(LOADING_CLEAR_COLOR),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+
+        let text = format!("Generating terrain {}%", self.terrain_gen_percent());
+        let panel = TextPanel {
+            text: &text,
+            left: (self.size.width as f32 / 2.0 - 90.0).max(0.0),
+            top: (self.size.height as f32 / 2.0 - 9.0).max(0.0),
+        };
+        self.text_overlay
+            .prepare(&self.device, &self.queue, self.size, &[panel]);
+        self.text_overlay.encode(encoder, view);
+    }
+
+    pub(crate) fn encode_text_overlay(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+    ) {
+        let param_text = self
+            .show_param_overlay
+            .then(|| param_overlay_text(&self.params));
+
+        let view_text = self.canonical_view.map(|view| {
+            let nudged = view.snapped(&self.params.view_params) != self.params.view_params;
+            format!("{}{}", view.name(), if nudged { " (nudged)" } else { "" })
+        });
+
+        let stats_text = self.show_frame_stats.then(|| {
+            let gpu = self.gpu_timer.as_ref().and_then(GpuTimer::last);
+            let adapter = self.adapter_info();
+            format!(
+                "{}\n{} ({:?})",
+                self.frame_stats.text(gpu),
+                adapter.name,
+                adapter.backend
+            )
+        });
+
+        let mut panels = Vec::new();
+        if let Some(text) = &param_text {
+            panels.push(TextPanel {
+                text,
+                left: 10.0,
+                top: 10.0,
+            });
+        }
+        if let Some(text) = &view_text {
+            panels.push(TextPanel {
+                text,
+                left: 10.0,
+                top: self.size.height as f32 - 28.0,
+            });
+        }
+        if let Some(text) = &stats_text {
+            panels.push(TextPanel {
+                text,
+                left: (self.size.width as f32 - 200.0).max(0.0),
+                top: 10.0,
             });
+        }
 
-            render_pass.set_pipeline(&self.pipelines.render);
+        if panels.is_empty() {
+            return;
+        }
 
-            render_pass.set_bind_group(0, &self.bind_groups.uniform_bg, &[]);
-            render_pass.set_bind_group(1, &self.bind_groups.frag_bg, &[]);
-            render_pass.set_bind_group(2, &self.bind_groups.sampled_texture_bg, &[]);
-            render_pass.set_vertex_buffer(0, self.buffers.vertex.slice(..));
+        self.text_overlay
+            .prepare(&self.device, &self.queue, self.size, &panels);
+        self.text_overlay.encode(encoder, view);
+    }
 
-            let vertex_range = 0..VERTICES.len() as u32;
-            let instance_range = 0..1;
-            render_pass.draw(vertex_range, instance_range);
+    // Restart tiled generation, e.g. after the terrain params change.
+    // Animated terrain already regenerates every frame
+    pub(crate) fn regenerate_terrain(&mut self) {
+        if !self.params.terrain_anim_params.animated {
+            println!("Regenerating terrain");
+            self.terrain_gen = GenerationState::Pending;
         }
+    }
 
-        self.queue.submit(Some(encoder.finish()));
-        output.present();
+    // Generation is normally spread across frames, dispatch the remaining tiles now.
+    // Each tile needs its own submit so it sees its own offset, and waiting for each
+    // keeps them from queueing up into one long stretch of GPU work
+    pub(crate) fn finish_terrain_generation(&mut self) {
+        while self.terrain_gen != GenerationState::Done {
+            let mut encoder = self
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Terrain Generation Encoder"),
+                });
+            self.encode_terrain_generation(&mut encoder);
+            self.queue.submit(Some(encoder.finish()));
+            self.device.poll(wgpu::Maintain::Wait);
+        }
+    }
 
-        Ok(())
+    pub(crate) fn encode_terrain_generation(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if self.params.terrain_anim_params.animated {
+            // Animated terrain regenerates the whole texture every frame
+            if std::mem::replace(&mut self.terrain_gen, GenerationState::Done)
+                != GenerationState::Done
+            {
+                self.set_title(WINDOW_TITLE);
+            }
+            self.terrain_ready = true;
+            let groups = dispatch_size(self.terrain_resolution);
+            self.dispatch_terrain(encoder, [0, 0], [groups, groups]);
+            return;
+        }
+
+        let Some(tile) = self.terrain_gen.next_tile() else {
+            return;
+        };
+
+        let groups = dispatch_size(self.terrain_resolution);
+        let tile_groups = terrain_gen_tile_groups(self.terrain_resolution, self.terrain_gen_tiles);
+        let per_side = self.terrain_gen_tiles_per_side();
+        let first = [
+            (tile % per_side) * tile_groups,
+            (tile / per_side) * tile_groups,
+        ];
+        let size = [
+            tile_groups.min(groups - first[0]),
+            tile_groups.min(groups - first[1]),
+        ];
+        self.dispatch_terrain(encoder, first, size);
+
+        let done = tile + 1;
+        let tile_count = per_side * per_side;
+        if done < tile_count {
+            self.terrain_gen = GenerationState::InProgress(done);
+            self.set_title(&format!(
+                "{} - generating terrain {}%",
+                WINDOW_TITLE,
+                self.terrain_gen_percent()
+            ));
+        } else {
+            println!("Generated terrain in {} tiles", tile_count);
+            self.set_title(WINDOW_TITLE);
+            self.terrain_gen = GenerationState::Done;
+            self.terrain_ready = true;
+        }
+    }
+
+    fn terrain_gen_tiles_per_side(&self) -> u32 {
+        terrain_gen_tiles_per_side(self.terrain_resolution, self.terrain_gen_tiles)
+    }
+
+    // Share of the tiles dispatched so far
+    pub(crate) fn terrain_gen_percent(&self) -> u32 {
+        let per_side = self.terrain_gen_tiles_per_side();
+        match self.terrain_gen {
+            GenerationState::Pending => 0,
+            GenerationState::InProgress(tile) => tile * 100 / (per_side * per_side),
+            GenerationState::Done => 100,
+        }
     }
 
+    pub(crate) fn encode_terrain_brush(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if !std::mem::take(&mut self.brush_pending) {
+            return;
+        }
+        // Edits would be overwritten by strips still to come or by the next animated frame
+        if self.terrain_gen != GenerationState::Done || self.params.terrain_anim_params.animated {
+            return;
+        }
+
+        self.terrain_changed();
+
+        // Only dispatch over the brush's bounding square
+        let brush = self.params.brush_params;
+        let side = (brush.radius * 2.0).ceil() as u32 + 1;
+        let workgroups = side.div_ceil(BRUSH_WORKGROUP_SIZE);
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Terrain Brush Pass"),
+            timestamp_writes: None,
+        });
+
+        compute_pass.set_pipeline(&self.pipelines.terrain_brush);
+        compute_pass.set_bind_group(0, &self.bind_groups.uniform_bg, &[]);
+        compute_pass.set_bind_group(1, &self.bind_groups.compute_bg, &[]);
+        compute_pass.set_bind_group(2, &self.bind_groups.texture_bg, &[]);
+        compute_pass.set_bind_group(3, &self.bind_groups.debug_bg, &[]);
+        compute_pass.dispatch_workgroups(workgroups, workgroups, 1);
+    }
+
+    // Everything derived from the full precision terrain needs redoing
+    pub(crate) fn terrain_changed(&mut self) {
+        self.terrain_half_stale = true;
+        self.terrain_normals_stale = true;
+        self.terrain_mips_stale = true;
+    }
+
+    // Downsample each mip level from the one above, in order
+    pub(crate) fn encode_terrain_mips(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if !std::mem::take(&mut self.terrain_mips_stale) {
+            return;
+        }
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Terrain Mips Pass"),
+            timestamp_writes: None,
+        });
+
+        compute_pass.set_pipeline(&self.pipelines.terrain_mips);
+        for (level, bind_group) in (1..).zip(&self.bind_groups.terrain_mip_bgs) {
+            let groups = (self.terrain_resolution >> level).div_ceil(TERRAIN_MIP_WORKGROUP_SIZE);
+            compute_pass.set_bind_group(0, bind_group, &[]);
+            compute_pass.dispatch_workgroups(groups, groups, 1);
+        }
+    }
+
+    pub(crate) fn encode_terrain_normals(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if !std::mem::take(&mut self.terrain_normals_stale) {
+            return;
+        }
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Terrain Normals Pass"),
+            timestamp_writes: None,
+        });
+
+        compute_pass.set_pipeline(&self.pipelines.terrain_normals);
+        compute_pass.set_bind_group(0, &self.bind_groups.uniform_bg, &[]);
+        compute_pass.set_bind_group(1, &self.bind_groups.compute_bg, &[]);
+        compute_pass.set_bind_group(2, &self.bind_groups.texture_bg, &[]);
+        compute_pass.set_bind_group(3, &self.bind_groups.debug_bg, &[]);
+        let groups = dispatch_size(self.terrain_resolution);
+        compute_pass.dispatch_workgroups(groups, groups, 1);
+    }
+
+    // Refresh the half precision copy, only when it's being rendered
+    pub(crate) fn encode_terrain_conversion(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if !self.terrain_half_stale || self.params.precision_params.render_half == 0 {
+            return;
+        }
+        self.terrain_half_stale = false;
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Convert Terrain Pass"),
+            timestamp_writes: None,
+        });
+
+        compute_pass.set_pipeline(&self.pipelines.convert_terrain);
+        compute_pass.set_bind_group(0, &self.bind_groups.uniform_bg, &[]);
+        compute_pass.set_bind_group(1, &self.bind_groups.compute_bg, &[]);
+        compute_pass.set_bind_group(2, &self.bind_groups.texture_bg, &[]);
+        compute_pass.set_bind_group(3, &self.bind_groups.debug_bg, &[]);
+        let groups = dispatch_size(self.terrain_resolution);
+        compute_pass.dispatch_workgroups(groups, groups, 1);
+    }
+
+    // `first` and `groups` are in workgroups, the shader gets the texel offset
+    fn dispatch_terrain(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        first: [u32; 2],
+        groups: [u32; 2],
+    ) {
+        let anim = self.params.terrain_anim_params;
+        let terrain_gen = TerrainGen {
+            x_offset: first[0] * TERRAIN_WORKGROUP_SIZE,
+            y_offset: first[1] * TERRAIN_WORKGROUP_SIZE,
+            anim_speed: if anim.animated { anim.speed } else { 0.0 },
+            _padding: 0.0,
+        };
+        self.terrain_changed();
+        self.queue.write_buffer(
+            &self.buffers.terrain_gen,
+            0,
+            bytemuck::cast_slice(&[terrain_gen]),
+        );
+
+        let timestamp_writes = self
+            .gpu_timer
+            .as_mut()
+            .and_then(GpuTimer::terrain_gen_writes);
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Generate Terrain Pass"),
+            timestamp_writes,
+        });
+
+        compute_pass.set_pipeline(&self.pipelines.generate_terrain);
+        compute_pass.set_bind_group(0, &self.bind_groups.uniform_bg, &[]);
+        compute_pass.set_bind_group(1, &self.bind_groups.compute_bg, &[]);
+        compute_pass.set_bind_group(2, &self.bind_groups.texture_bg, &[]);
+        compute_pass.set_bind_group(3, &self.bind_groups.debug_bg, &[]);
+        compute_pass.dispatch_workgroups(groups[0], groups[1], 1);
+    }
+
+    // Everything sized to the window works in physical pixels: the surface config, the
+    // render targets, ScreenParams and so the aspect ratio, and the text overlay.
+    // winit reports physical sizes in Resized and inner_size, and --width/--height are
+    // requested as physical too. The logical size, physical / scale_factor, is left to
+    // the window system
     pub(crate) fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
-        if new_size.width > 0 && new_size.height > 0 {
+        // A minimized window reports a zero size, the surface keeps its old one
+        self.minimized = new_size.width == 0 || new_size.height == 0;
+        if !self.minimized {
             self.size = new_size;
-            self.surface_config.width = new_size.width;
-            self.surface_config.height = new_size.height;
-            self.surface.configure(&self.device, &self.surface_config);
+            if let Some(ws) = &mut self.window_surface {
+                ws.config.width = new_size.width;
+                ws.config.height = new_size.height;
+                ws.surface.configure(&self.device, &ws.config);
+            }
+            self.init_render_targets();
         }
     }
 
+    // Switch supersampling to `factor` times the window size, 1 turns it off. Falls back
+    // to 1 if the render size would pass the device's texture size limit
+    pub(crate) fn set_ss_factor(&mut self, factor: u32) {
+        self.ss_factor = factor;
+        self.init_render_targets();
+        println!("Supersampling: {}x", self.ss_factor);
+    }
+
+    // Size of the depth, MSAA and supersampled targets the scene is rendered at
+    pub(crate) fn render_size(&self) -> winit::dpi::PhysicalSize<u32> {
+        winit::dpi::PhysicalSize::new(
+            self.size.width.max(1) * self.ss_factor,
+            self.size.height.max(1) * self.ss_factor,
+        )
+    }
+
+    // Recreate everything sized to the render size, after a resize or ss_factor change.
+    // The shaders see the render size through ScreenParams
+    fn init_render_targets(&mut self) {
+        let max_dimension = self.device.limits().max_texture_dimension_2d;
+        let render_size = self.render_size();
+        if self.ss_factor > 1 && render_size.width.max(render_size.height) > max_dimension {
+            eprintln!(
+                "{}x supersampling would pass the {} texture size limit, disabling it",
+                self.ss_factor, max_dimension
+            );
+            self.ss_factor = 1;
+        }
+        let render_size = self.render_size();
+
+        let (depth_tex, depth_view) = init_depth_texture(
+            &self.device,
+            render_size.width,
+            render_size.height,
+            self.sample_count,
+        );
+        self.textures.depth_tex = depth_tex;
+        self.textures.depth_view = depth_view;
+        self.textures.msaa_view = init_msaa_texture(
+            &self.device,
+            self.color_format,
+            render_size.width,
+            render_size.height,
+            self.sample_count,
+        );
+        self.textures.ss_view = init_ss_texture(
+            &self.device,
+            self.color_format,
+            self.size.width.max(1),
+            self.size.height.max(1),
+            self.ss_factor,
+        );
+        self.bind_groups.downsample_bg = init_downsample_bind_group(
+            &self.device,
+            &self.bind_groups.downsample_bgl,
+            &self.textures,
+        );
+
+        self.params.screen_params = ScreenParams::new(render_size.width, render_size.height);
+        update_screen_params_buffer(self);
+        self.set_debug_pixel(self.debug_pixel);
+    }
+
+    pub(crate) fn adapter_info(&self) -> &wgpu::AdapterInfo {
+        &self.adapter_info
+    }
+
+    pub(crate) fn window(&self) -> Option<&winit::window::Window> {
+        self.window_surface.as_ref().map(|ws| ws.window.as_ref())
+    }
+
+    fn set_title(&self, title: &str) {
+        if let Some(window) = self.window() {
+            window.set_title(title);
+        }
+    }
+
+    pub(crate) fn cycle_present_mode(&mut self) {
+        let Some(ws) = &self.window_surface else {
+            return;
+        };
+        let current = ws
+            .present_modes
+            .iter()
+            .position(|m| *m == ws.config.present_mode)
+            .unwrap_or(0);
+        let next = ws.present_modes[(current + 1) % ws.present_modes.len()];
+        self.set_present_mode(next);
+    }
+
+    // Falls back to Fifo, which every surface supports, if the adapter lacks `mode`
+    pub(crate) fn set_present_mode(&mut self, mode: wgpu::PresentMode) {
+        let Some(ws) = &mut self.window_surface else {
+            return;
+        };
+        let mode = if ws.present_modes.contains(&mode) {
+            mode
+        } else {
+            eprintln!("Present mode {:?} not supported, using Fifo", mode);
+            wgpu::PresentMode::Fifo
+        };
+
+        ws.config.present_mode = mode;
+        ws.surface.configure(&self.device, &ws.config);
+        println!("Present mode: {:?}", mode);
+    }
+
+    // Borderless fullscreen on the current monitor, restoring the old window size on exit
+    pub(crate) fn toggle_fullscreen(&mut self) {
+        let Some(window) = self
+            .window_surface
+            .as_ref()
+            .map(|ws| Arc::clone(&ws.window))
+        else {
+            return;
+        };
+        if window.fullscreen().is_some() {
+            window.set_fullscreen(None);
+            if let Some(size) = self.windowed_size.take() {
+                let _ = window.request_inner_size(size);
+            }
+        } else {
+            self.windowed_size = Some(window.inner_size());
+            window.set_fullscreen(Some(winit::window::Fullscreen::Borderless(None)));
+        }
+        // Platforms that resize synchronously won't send a Resized event
+        self.resize(window.inner_size());
+    }
+
+    // Anything that needs frames to keep coming at the full rate
+    pub(crate) fn is_active(&self) -> bool {
+        self.last_input.elapsed().as_secs_f64() < IDLE_DELAY_SECS
+            || !self.controls.get_keys().is_empty()
+            || self.mouse.is_pressed()
+            || self.params.terrain_anim_params.animated
+            || self.terrain_gen != GenerationState::Done
+            || self.brush_pending
+            || self.view_transition.is_some()
+            || self.camera_velocity.is_moving()
+            || self.recording.is_some()
+    }
+
+    // Already scaled by time_modifier, so the time uniform every shader reads runs at
+    // the modified rate
     pub(crate) fn get_time(&self) -> f32 {
-        self.app_time.elapsed().as_secs_f32()
+        self.clock.time()
+    }
+
+    // The one place time_modifier reaches the clock, picked up before each step
+    pub(crate) fn advance_time(&mut self) {
+        self.clock.set_rate(self.params.view_params.time_modifier);
+        self.clock.advance();
+    }
+
+    pub(crate) fn toggle_pause(&mut self) {
+        println!("Paused: {}", self.clock.toggle_pause());
+    }
+
+    pub(crate) fn set_time(&mut self, time: f32) {
+        self.clock.set_time(time);
+    }
+
+    // Time written to the time uniform, the pinned value if one is set, then the
+    // recording's fixed step time
+    pub(crate) fn frame_time(&self) -> f32 {
+        if let Some(time) = self.pinned_time {
+            return time;
+        }
+        match &self.recording {
+            Some(recording) => recording.time(),
+            None => self.get_time(),
+        }
+    }
+}
+
+async fn request_adapter(
+    instance: &wgpu::Instance,
+    compatible_surface: Option<&wgpu::Surface<'_>>,
+) -> Result<wgpu::Adapter, InitError> {
+    instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            force_fallback_adapter: false,
+            compatible_surface,
+        })
+        .await
+        .ok_or(InitError::Adapter)
+}
+
+fn create_surface(
+    window: &Arc<winit::window::Window>,
+    backends: wgpu::Backends,
+) -> Result<(wgpu::Instance, wgpu::Surface<'static>), InitError> {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends,
+        ..Default::default()
+    });
+    let surface = instance
+        .create_surface(Arc::clone(window))
+        .map_err(InitError::Surface)?;
+    Ok((instance, surface))
+}
+
+// Limits::default() raised only where the app needs more, rather than everything the
+// adapter has. What the pipelines use:
+// - 4 bind groups, group 3 is the debug group shared by render and compute
+// - 9 uniform buffers in the fragment stage, the time uniform and 8 param uniforms, under
+//   the default 12. Add to ShadingParams rather than binding another
+// - 1024 invocations per workgroup, the terrain compute shaders use 32x32 workgroups
+// - 4 storage buffers in compute, the terrain params and the three debug buffers, which
+//   are 16 bytes per entry and 8 KiB at the default DEBUG_ARRAY_LEN of 512
+// - 3 storage textures in compute, the terrain, its half precision copy and normals
+// - terrain_resolution sized textures, 2048 by default, under the default 8192
+// - export_terrain's readback of the Rgba32Float level 0, 64 MiB at 2048
+fn required_limits(terrain_resolution: u32) -> wgpu::Limits {
+    let defaults = wgpu::Limits::default();
+    let terrain_bytes = terrain_resolution as u64 * terrain_resolution as u64 * 16;
+    wgpu::Limits {
+        max_compute_invocations_per_workgroup: TERRAIN_WORKGROUP_SIZE * TERRAIN_WORKGROUP_SIZE,
+        max_texture_dimension_2d: defaults.max_texture_dimension_2d.max(terrain_resolution),
+        max_buffer_size: defaults.max_buffer_size.max(terrain_bytes),
+        ..defaults
+    }
+}
+
+async fn request_device(
+    adapter: &wgpu::Adapter,
+    terrain_resolution: u32,
+) -> Result<(wgpu::Device, wgpu::Queue), InitError> {
+    let max_resolution = adapter.limits().max_texture_dimension_2d;
+    if !(MIN_TERRAIN_RESOLUTION..=max_resolution).contains(&terrain_resolution) {
+        return Err(InitError::TerrainResolution {
+            requested: terrain_resolution,
+            min: MIN_TERRAIN_RESOLUTION,
+            max: max_resolution,
+        });
+    }
+
+    let limits = required_limits(terrain_resolution);
+    let mut exceeded = Vec::new();
+    limits.check_limits_with_fail_fn(&adapter.limits(), false, |name, needed, allowed| {
+        exceeded.push(format!("{} needs {}, has {}", name, needed, allowed));
+    });
+    if !exceeded.is_empty() {
+        return Err(InitError::Limits(exceeded));
+    }
+
+    // Only what the adapter has is requested. Timestamp queries are optional, GPU timing
+    // is disabled without them, and without FLOAT32_FILTERABLE the terrain is sampled
+    // with a nearest sampler, see init_textures
+    let wanted = wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES
+        | wgpu::Features::FLOAT32_FILTERABLE
+        | wgpu::Features::TIMESTAMP_QUERY;
+    let features = wanted & adapter.features();
+    if !features.contains(wgpu::Features::FLOAT32_FILTERABLE) {
+        eprintln!(
+            "Float32 filtering isn't supported by this adapter, sampling the terrain unfiltered"
+        );
+    }
+    adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("dev_storage_texture_capable Device"),
+                required_features: features,
+                required_limits: limits,
+            },
+            None,
+        )
+        .await
+        .map_err(InitError::Device)
+}
+
+// Which GPU and backend rendering differences come from
+fn log_adapter_info(info: &wgpu::AdapterInfo) {
+    // Either can be empty, GL reports neither
+    let driver = format!("{} {}", info.driver, info.driver_info);
+    let driver = match driver.trim() {
+        "" => "unknown",
+        driver => driver,
+    };
+    println!(
+        "Adapter: {} ({:?}, {:?}), driver {}",
+        info.name, info.backend, info.device_type, driver
+    );
+}
+
+// The color targets of the render and motion blur pipelines and the depth buffer all
+// need to support it
+fn supported_sample_count(
+    adapter: &wgpu::Adapter,
+    color_format: wgpu::TextureFormat,
+    sample_count: u32,
+) -> u32 {
+    let supported = [color_format, ACCUMULATION_FORMAT, DEPTH_FORMAT]
+        .iter()
+        .all(|f| {
+            adapter
+                .get_texture_format_features(*f)
+                .flags
+                .sample_count_supported(sample_count)
+        });
+    if supported {
+        sample_count
+    } else {
+        eprintln!(
+            "{}x MSAA isn't supported by this adapter, disabling it",
+            sample_count
+        );
+        1
     }
 }
@@ -0,0 +1,51 @@
+use super::state::State;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SurfaceErrorPolicy {
+    // Consecutive failed frames tolerated before exiting, None keeps trying forever
+    pub(crate) max_consecutive_errors: Option<u32>,
+}
+
+impl SurfaceErrorPolicy {
+    pub(crate) fn interactive() -> Self {
+        Self {
+            max_consecutive_errors: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ControlDirective {
+    Continue,
+    Exit(i32),
+}
+
+impl<'a> State<'a> {
+    pub(crate) fn handle_surface_error(&mut self, err: wgpu::SurfaceError) -> ControlDirective {
+        self.surface_error_count += 1;
+
+        match err {
+            // The system is out of memory, quit
+            wgpu::SurfaceError::OutOfMemory => {
+                eprintln!("Surface error: {:?}, exiting", err);
+                return ControlDirective::Exit(1);
+            }
+            // Reconfigure the surface if lost or outdated
+            wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated => self.resize(self.size),
+            // Timeout -> resolve by the next frame
+            wgpu::SurfaceError::Timeout => {}
+        }
+        eprintln!("Surface error: {:?}", err);
+
+        match self.surface_error_policy.max_consecutive_errors {
+            Some(max) if self.surface_error_count > max => {
+                eprintln!(
+                    "{} consecutive surface errors, giving up",
+                    self.surface_error_count
+                );
+                ControlDirective::Exit(1)
+            }
+            _ => ControlDirective::Continue,
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use glyphon::{
+    Attrs, Buffer, Color, Family, FontSystem, Metrics, Resolution, Shaping, SwashCache, TextArea,
+    TextAtlas, TextBounds, TextRenderer,
+};
+
+const FONT_SIZE: f32 = 14.0;
+const LINE_HEIGHT: f32 = 18.0;
+
+// A block of text drawn with its top left corner at left/top, in pixels
+pub(crate) struct TextPanel<'a> {
+    pub(crate) text: &'a str,
+    pub(crate) left: f32,
+    pub(crate) top: f32,
+}
+
+// Draws text on top of the rendered frame, in its own render pass
+pub(crate) struct TextOverlay {
+    font_system: FontSystem,
+    cache: SwashCache,
+    atlas: TextAtlas,
+    renderer: TextRenderer,
+    // One buffer per panel, reused between frames
+    buffers: Vec<Buffer>,
+}
+
+impl std::fmt::Debug for TextOverlay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TextOverlay")
+            .field("panels", &self.buffers.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl TextOverlay {
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        let font_system = FontSystem::new();
+        let cache = SwashCache::new();
+        let mut atlas = TextAtlas::new(device, queue, format);
+        let renderer =
+            TextRenderer::new(&mut atlas, device, wgpu::MultisampleState::default(), None);
+
+        Self {
+            font_system,
+            cache,
+            atlas,
+            renderer,
+            buffers: Vec::new(),
+        }
+    }
+
+    // Lay out and upload the text for this frame
+    pub(crate) fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        size: winit::dpi::PhysicalSize<u32>,
+        panels: &[TextPanel],
+    ) {
+        let metrics = Metrics::new(FONT_SIZE, LINE_HEIGHT);
+        while self.buffers.len() < panels.len() {
+            self.buffers
+                .push(Buffer::new(&mut self.font_system, metrics));
+        }
+
+        for (buffer, panel) in self.buffers.iter_mut().zip(panels) {
+            buffer.set_size(
+                &mut self.font_system,
+                size.width as f32 - panel.left,
+                size.height as f32 - panel.top,
+            );
+            buffer.set_text(
+                &mut self.font_system,
+                panel.text,
+                Attrs::new().family(Family::Monospace),
+                Shaping::Basic,
+            );
+            buffer.shape_until_scroll(&mut self.font_system);
+        }
+
+        let text_areas = self
+            .buffers
+            .iter()
+            .zip(panels)
+            .map(|(buffer, panel)| TextArea {
+                buffer,
+                left: panel.left,
+                top: panel.top,
+                scale: 1.0,
+                bounds: TextBounds {
+                    left: 0,
+                    top: 0,
+                    right: size.width as i32,
+                    bottom: size.height as i32,
+                },
+                default_color: Color::rgb(255, 255, 255),
+            });
+
+        if let Err(e) = self.renderer.prepare(
+            device,
+            queue,
+            &mut self.font_system,
+            &mut self.atlas,
+            Resolution {
+                width: size.width,
+                height: size.height,
+            },
+            text_areas,
+            &mut self.cache,
+        ) {
+            eprintln!("Error preparing text overlay: {}", e);
+        }
+    }
+
+    pub(crate) fn encode(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Text Overlay Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+
+        if let Err(e) = self.renderer.render(&self.atlas, &mut render_pass) {
+            eprintln!("Error rendering text overlay: {}", e);
+        }
+    }
+
+    // Free glyphs that weren't used this frame
+    pub(crate) fn trim(&mut self) {
+        self.atlas.trim();
+    }
+}
@@ -0,0 +1,59 @@
+use anyhow::{bail, Context};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use bytemuck::Zeroable;
+
+use crate::collections::structs::ViewParams;
+
+// ViewParams fields before z_rot
+const OLDEST_TOKEN_FIELDS: usize = 10;
+
+// Compact shareable form of the camera, ViewParams bytes as url-safe base64
+pub(crate) fn encode_view_token(view_params: &ViewParams) -> String {
+    URL_SAFE_NO_PAD.encode(bytemuck::bytes_of(view_params))
+}
+
+pub(crate) fn decode_view_token(token: &str) -> anyhow::Result<ViewParams> {
+    let bytes = URL_SAFE_NO_PAD
+        .decode(token.trim())
+        .context("view token is not valid base64")?;
+
+    // Tokens from before z_rot and ortho were added are short, the missing fields are 0.0
+    let size = std::mem::size_of::<ViewParams>();
+    let oldest = OLDEST_TOKEN_FIELDS * std::mem::size_of::<f32>();
+    if !(oldest..=size).contains(&bytes.len()) || bytes.len() % std::mem::size_of::<f32>() != 0 {
+        bail!("view token has {} bytes, expected {}", bytes.len(), size);
+    }
+
+    let mut view_params = ViewParams::zeroed();
+    bytemuck::bytes_of_mut(&mut view_params)[..bytes.len()].copy_from_slice(&bytes);
+    Ok(view_params)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn copy_to_clipboard(text: String) {
+    // On X11/Wayland the clipboard is only served while its owner is alive,
+    // so hand it to a thread that waits until something else takes ownership
+    std::thread::spawn(move || {
+        let result = arboard::Clipboard::new().and_then(|mut clipboard| {
+            #[cfg(target_os = "linux")]
+            {
+                use arboard::SetExtLinux;
+                clipboard.set().wait().text(text)
+            }
+            #[cfg(not(target_os = "linux"))]
+            {
+                clipboard.set_text(text)
+            }
+        });
+
+        if let Err(e) = result {
+            eprintln!("Could not copy view token to clipboard: {:?}", e);
+        }
+    });
+}
+
+// No clipboard access from the browser build, the token is logged instead
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn copy_to_clipboard(text: String) {
+    web_sys::console::log_1(&text.into());
+}
@@ -1,16 +1,207 @@
+pub(crate) const WINDOW_TITLE: &str = "winit window";
+// Loaded at startup if present, written by F5
+pub(crate) const CONFIG_PATH: &str = "config.toml";
+
+// Default window size, the shaders read the actual one from ScreenParams
 pub(crate) const SCREEN_WIDTH: u32 = 1376;
 pub(crate) const SCREEN_HEIGHT: u32 = 768;
-pub(crate) const ASPECT: f32 = SCREEN_WIDTH as f32 / SCREEN_HEIGHT as f32;
 
-pub(crate) const TERRAIN_TEXTURE_WIDTH: u32 = 2048;
-pub(crate) const TERRAIN_TEXTURE_HEIGHT: u32 = 2048;
+// The app drops to the config's idle_fps once nothing has changed for IDLE_DELAY_SECS,
+// DEFAULT_IDLE_FPS without one. Idle rates outside MIN_IDLE_FPS..=MAX_IDLE_FPS are ignored
+pub(crate) const DEFAULT_IDLE_FPS: f64 = 4.0;
+pub(crate) const MIN_IDLE_FPS: f64 = 0.1;
+pub(crate) const MAX_IDLE_FPS: f64 = 1000.0;
+// Rate the shader time advances at between --headless frames
+pub(crate) const HEADLESS_FPS: f32 = 60.0;
+// --motion-blur sums its sub-frames in ACCUMULATION_FORMAT, linear and unclamped, before
+// the tone curve. Half floats are blendable and filterable everywhere, but their
+// rounding adds up, which is what caps the sample count
+pub(crate) const ACCUMULATION_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+pub(crate) const MAX_MOTION_BLUR_SAMPLES: u32 = 64;
+// Each sub-frame is added at the blend constant, set to 1 / samples
+pub(crate) const ACCUMULATE_BLEND: wgpu::BlendState = wgpu::BlendState {
+    color: wgpu::BlendComponent {
+        src_factor: wgpu::BlendFactor::Constant,
+        dst_factor: wgpu::BlendFactor::One,
+        operation: wgpu::BlendOperation::Add,
+    },
+    alpha: wgpu::BlendComponent {
+        src_factor: wgpu::BlendFactor::Constant,
+        dst_factor: wgpu::BlendFactor::One,
+        operation: wgpu::BlendOperation::Add,
+    },
+};
+// Fraction of the frame interval the shutter is open for, a 180 degree shutter
+pub(crate) const DEFAULT_SHUTTER: f32 = 0.5;
+// Recorded frame sequences, the time steps at RECORD_FPS whatever the real frame rate.
+// At most RECORD_QUEUE_FRAMES frames wait to be written before rendering blocks
+pub(crate) const RECORD_FPS: f32 = 60.0;
+pub(crate) const RECORD_FRAMES: u32 = 300;
+pub(crate) const RECORD_QUEUE_FRAMES: usize = 8;
+// Readback buffers a recorded frame's copy waits in until it's mapped, so the GPU can
+// run this many frames ahead before recording waits on it
+pub(crate) const RECORD_READBACK_SLOTS: usize = 3;
+pub(crate) const IDLE_DELAY_SECS: f64 = 1.0;
+// --autotune keeps the best preset whose median render pass over AUTOTUNE_FRAMES frames
+// fits the budget, a 60 fps frame with some headroom for the rest of the frame
+pub(crate) const AUTOTUNE_FRAMES: u32 = 5;
+pub(crate) const AUTOTUNE_FRAME_BUDGET_MS: f64 = 12.0;
+
+// Radians of rotation per pixel of mouse movement in mouse-look
+pub(crate) const MOUSE_SENSITIVITY: f32 = 0.003;
+
+// Zoom changes by this fraction of itself per key repeat or wheel line,
+// and stays within MIN_ZOOM..=MAX_ZOOM since the pan math divides by it
+pub(crate) const ZOOM_STEP: f32 = 0.1;
+pub(crate) const MIN_ZOOM: f32 = 0.01;
+pub(crate) const MAX_ZOOM: f32 = 1000.0;
+// Touchpads scroll in pixels, convert to roughly one wheel line
+pub(crate) const PIXELS_PER_LINE: f32 = 20.0;
+
+// Animation speed multiplier range, stepped multiplicatively so it stays positive
+pub(crate) const TIME_MODIFIER_STEP: f32 = 1.25;
+pub(crate) const MIN_TIME_MODIFIER: f32 = 0.01;
+pub(crate) const MAX_TIME_MODIFIER: f32 = 100.0;
+
+// Ray epsilon is scaled by this per frame the key is held, it spans orders of
+// magnitude so an additive step is either too coarse or too slow
+pub(crate) const EPSILON_STEP: f32 = 1.05;
+pub(crate) const MIN_EPSILON: f32 = 1e-6;
+pub(crate) const MAX_EPSILON: f32 = 1.0;
+// Added to epsilon_scale per frame the key is held
+pub(crate) const EPSILON_SCALE_STEP: f32 = 0.00002;
+pub(crate) const MAX_EPSILON_SCALE: f32 = 0.01;
+// Fewer steps never runs the march loop, max_dist has epsilon as its floor
+pub(crate) const MIN_MAX_STEPS: f32 = 1.0;
+
+// Fly camera speed in world units per second
+pub(crate) const FLY_SPEED: f32 = 100.0;
+// Cap on the frame time used for movement, so a long idle frame doesn't jump the camera
+pub(crate) const MAX_MOVE_DT: f32 = 0.1;
+// VIEW mode arrow/X/Z rates per second, matching the old per-frame steps at 60 fps.
+// Pan is divided and zoom multiplied by the current zoom
+pub(crate) const PAN_SPEED: f32 = 0.6;
+pub(crate) const ROTATE_SPEED: f32 = 6.0;
+// Q/E roll, in radians per second
+pub(crate) const ROLL_SPEED: f32 = 1.0;
+pub(crate) const ZOOM_SPEED: f32 = 6.0;
+// VIEW mode movement eases in and coasts out. Velocities are fractions of the speeds
+// above, a held key adds CAMERA_ACCELERATION per second and CAMERA_DAMPING is the
+// per second decay, so top speed is their ratio and 1 / CAMERA_DAMPING the time constant
+pub(crate) const CAMERA_ACCELERATION: f32 = 8.0;
+pub(crate) const CAMERA_DAMPING: f32 = 8.0;
+// Below this the camera is snapped to rest, so a coast doesn't keep frames coming
+pub(crate) const CAMERA_REST_SPEED: f32 = 0.01;
+// LIGHT mode rates per second, and the sun's elevation range in radians. Just
+// below the horizon is allowed for dusk, straight up is avoided so azimuth stays defined
+pub(crate) const SUN_ROTATE_SPEED: f32 = 1.0;
+pub(crate) const AMBIENT_SPEED: f32 = 0.1;
+pub(crate) const MIN_SUN_ELEVATION: f32 = -0.2;
+pub(crate) const MAX_SUN_ELEVATION: f32 = 1.55;
+// LIGHT mode fog rates per second
+pub(crate) const FOG_DENSITY_SPEED: f32 = 1.0;
+pub(crate) const MAX_FOG_DENSITY: f32 = 50.0;
+pub(crate) const FOG_START_SPEED: f32 = 0.2;
+pub(crate) const HUE_ROTATE_SPEED: f32 = 1.0;
+pub(crate) const HORIZON_BLEND_SPEED: f32 = 0.3;
+// Exposure changes in stops per second, so it steps evenly at any brightness
+pub(crate) const EXPOSURE_SPEED: f32 = 1.0;
+pub(crate) const MIN_EXPOSURE: f32 = 1.0 / 16.0;
+pub(crate) const MAX_EXPOSURE: f32 = 16.0;
+// Limits and steps of the terrain noise layer controls
+pub(crate) const MIN_OCTAVES: i32 = 1;
+pub(crate) const MAX_OCTAVES: i32 = 12;
+pub(crate) const MIN_TERRAIN_FREQ: f32 = 0.01;
+pub(crate) const MAX_TERRAIN_FREQ: f32 = 1000.0;
+pub(crate) const MAX_TERRAIN_AMP: f32 = 100.0;
+pub(crate) const TERRAIN_FREQ_STEP: f32 = 1.1;
+pub(crate) const TERRAIN_AMP_STEP: f32 = 0.05;
+pub(crate) const TERRAIN_ANIM_SPEED_STEP: f32 = 0.005;
+// Stick and trigger travel ignored around rest, and the right stick's turn rate
+#[cfg(feature = "gamepad")]
+pub(crate) const GAMEPAD_DEAD_ZONE: f32 = 0.15;
+#[cfg(feature = "gamepad")]
+pub(crate) const GAMEPAD_LOOK_SPEED: f32 = 2.0;
+// Time taken to glide to a recalled camera bookmark
+pub(crate) const BOOKMARK_TRANSITION_SECS: f32 = 0.35;
+
+// Side of the square terrain textures in texels, --terrain-resolution picks another
+// power of two at startup. The selftest reads a 64x64 block from the middle
+pub(crate) const DEFAULT_TERRAIN_RESOLUTION: u32 = 2048;
+pub(crate) const MIN_TERRAIN_RESOLUTION: u32 = 64;
+
+// generate_terrain.wgsl, convert_terrain.wgsl and terrain_normals.wgsl use 32x32 workgroups
+pub(crate) const TERRAIN_WORKGROUP_SIZE: u32 = 32;
+
+// Workgroups needed to cover `texels`, the shaders bounds check the partial last group
+pub(crate) const fn dispatch_size(texels: u32) -> u32 {
+    texels.div_ceil(TERRAIN_WORKGROUP_SIZE)
+}
+
+const _: () = assert!(dispatch_size(2048) == 64);
+const _: () = assert!(dispatch_size(2047) == 64);
+const _: () = assert!(dispatch_size(2049) == 65);
+
+// Depth attachment of the render pass, the fullscreen quad sits on the far plane
+pub(crate) const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+// Full mip chain of the terrain texture, down to 1x1
+pub(crate) const fn terrain_mip_levels(resolution: u32) -> u32 {
+    resolution.ilog2() + 1
+}
+
+const _: () = assert!(terrain_mip_levels(DEFAULT_TERRAIN_RESOLUTION) == 12);
+
+// terrain_mips.wgsl uses 8x8 workgroups
+pub(crate) const TERRAIN_MIP_WORKGROUP_SIZE: u32 = 8;
+
+// Terrain generation is split into square tiles, this many a side by default, with one
+// tile dispatched per frame so no single dispatch runs long enough to trip a GPU watchdog
+pub(crate) const DEFAULT_TERRAIN_GEN_TILES: u32 = 4;
+
+// Workgroups along a side of each tile when the terrain is split into `tiles` a side.
+// The last row and column of tiles may be narrower
+pub(crate) const fn terrain_gen_tile_groups(resolution: u32, tiles: u32) -> u32 {
+    dispatch_size(resolution).div_ceil(tiles)
+}
+
+// Tiles a side that are actually dispatched, fewer than asked for when rounding the tile
+// size up leaves nothing for the last ones
+pub(crate) const fn terrain_gen_tiles_per_side(resolution: u32, tiles: u32) -> u32 {
+    dispatch_size(resolution).div_ceil(terrain_gen_tile_groups(resolution, tiles))
+}
+
+const _: () = assert!(terrain_gen_tile_groups(2048, 4) == 16);
+const _: () = assert!(terrain_gen_tiles_per_side(2048, 4) == 4);
+const _: () = assert!(terrain_gen_tiles_per_side(2048, 48) == 32);
+
+// Background of the loading screen drawn during the first terrain generation
+pub(crate) const LOADING_CLEAR_COLOR: wgpu::Color = wgpu::Color {
+    r: 0.02,
+    g: 0.03,
+    b: 0.05,
+    a: 1.0,
+};
+
+// Entries in debug_array1 and debug_array2, the shaders size their writes with arrayLength
+pub(crate) const DEBUG_ARRAY_LEN: usize = 512;
+pub(crate) const DEBUG_ARRAY_SIZE: wgpu::BufferAddress =
+    (DEBUG_ARRAY_LEN * std::mem::size_of::<[f32; 4]>()) as wgpu::BufferAddress;
+pub(crate) const DEBUG_ARRAY_BINDING_SIZE: Option<wgpu::BufferSize> =
+    wgpu::BufferSize::new(DEBUG_ARRAY_SIZE);
+const _: () = assert!(match DEBUG_ARRAY_BINDING_SIZE {
+    Some(size) => size.get() == DEBUG_ARRAY_SIZE,
+    None => false,
+});
+
+// vec4 slots in the generic debug buffer, write_debug in frag.wgsl fills them for the
+// debug pixel: ray origin and hit distance, hit point and steps, then the final color
+pub(crate) const GENERIC_DEBUG_LEN: usize = 3;
+pub(crate) const GENERIC_DEBUG_SIZE: wgpu::BufferAddress =
+    (GENERIC_DEBUG_LEN * std::mem::size_of::<[f32; 4]>()) as wgpu::BufferAddress;
 
-pub(crate) const TERRAIN_TEX_DISPATCH_SIZE_X: u32 =
-    ((TERRAIN_TEXTURE_WIDTH).saturating_add(32)) / 32;
-pub(crate) const TERRAIN_TEX_DISPATCH_SIZE_Y: u32 =
-    ((TERRAIN_TEXTURE_HEIGHT).saturating_add(32)) / 32;
+// TextureViewParams::debug_pixel while none is picked, the shader uses the screen center
+pub(crate) const DEBUG_PIXEL_CENTER: u32 = u32::MAX;
 
-pub(crate) const TERRAIN_TEX_BUF_SIZE: usize = TERRAIN_TEXTURE_WIDTH as usize
-    * TERRAIN_TEXTURE_HEIGHT as usize
-    * 4
-    * (std::mem::size_of::<f32>());
+// Must match @workgroup_size in terrain_brush.wgsl
+pub(crate) const BRUSH_WORKGROUP_SIZE: u32 = 8;
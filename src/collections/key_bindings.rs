@@ -0,0 +1,210 @@
+use std::collections::BTreeMap;
+
+use winit::keyboard::KeyCode;
+
+// Everything the keyboard controls can do. Actions in different modes may share a
+// key, Shift and Ctrl stay fixed as modifiers
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+pub(crate) enum Action {
+    // Global
+    ModeDebug,
+    ModeTerrain,
+    ModeView,
+    ModeRay,
+    ModePaint,
+    ModeLight,
+    ModePrint,
+    TogglePause,
+    ToggleFullscreen,
+    Screenshot,
+    Record,
+    SaveConfig,
+    CyclePresentMode,
+    ToggleGuides,
+    ToggleParamOverlay,
+    ToggleFrameStats,
+    ToggleSupersampling,
+    CycleSdf,
+    ResetParams,
+    // Pan in VIEW mode, step or move the selected value in the other modes
+    Up,
+    Down,
+    Left,
+    Right,
+    // DEBUG
+    ToggleTextureView,
+    ToggleColormap,
+    ToggleStepHeatmap,
+    ExportTerrain,
+    DumpGeneric,
+    DumpArray1,
+    DumpArray2,
+    DumpInterleaved,
+    // RAY, held while stepping with Up/Down
+    Epsilon,
+    MaxSteps,
+    MaxDist,
+    EpsilonScale,
+    SdfParam,
+    // TERRAIN
+    ToggleTerrainAnimation,
+    ToggleClip,
+    ToggleLossView,
+    LossThreshold,
+    ClipHeight,
+    NextSeed,
+    RandomSeed,
+    AnimationSpeed,
+    SelectLayer1,
+    SelectLayer2,
+    SelectLayer3,
+    LayerFrequency,
+    LayerAmplitude,
+    // PAINT
+    BrushRadius,
+    BrushStrength,
+    // VIEW
+    CopyViewToken,
+    ViewTop,
+    ViewFront,
+    ViewSide,
+    ViewIsometric,
+    ToggleMouseLook,
+    SlowerTime,
+    FasterTime,
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    ZoomIn,
+    ZoomOut,
+    RollLeft,
+    RollRight,
+    ToggleOrtho,
+    Bookmark6,
+    Bookmark7,
+    Bookmark8,
+    Bookmark9,
+    Bookmark0,
+    // LIGHT
+    Ambient,
+    FogDensity,
+    FogStart,
+    FogHue,
+    // Left/Right rotate the hue, Up/Down move where the horizon meets the zenith
+    SkyHorizon,
+    SkyZenith,
+    Exposure,
+    CycleTonemap,
+}
+
+// Loaded from the [key_bindings] table of the config, actions it leaves out keep
+// their default key
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(from = "BTreeMap<Action, KeyCode>")]
+pub(crate) struct KeyBindings(BTreeMap<Action, KeyCode>);
+
+impl KeyBindings {
+    pub(crate) fn key(&self, action: Action) -> KeyCode {
+        self.0[&action]
+    }
+}
+
+impl From<BTreeMap<Action, KeyCode>> for KeyBindings {
+    fn from(overrides: BTreeMap<Action, KeyCode>) -> Self {
+        let mut bindings = Self::default();
+        bindings.0.extend(overrides);
+        bindings
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        use Action::*;
+        Self(BTreeMap::from([
+            (ModeDebug, KeyCode::KeyD),
+            (ModeTerrain, KeyCode::Digit1),
+            (ModeView, KeyCode::Digit2),
+            (ModeRay, KeyCode::Digit3),
+            (ModePaint, KeyCode::Digit4),
+            (ModeLight, KeyCode::Digit5),
+            (ModePrint, KeyCode::KeyP),
+            (TogglePause, KeyCode::Space),
+            (ToggleFullscreen, KeyCode::F11),
+            (Screenshot, KeyCode::F12),
+            (Record, KeyCode::F9),
+            (SaveConfig, KeyCode::F5),
+            (CyclePresentMode, KeyCode::KeyV),
+            (ToggleGuides, KeyCode::KeyG),
+            (ToggleParamOverlay, KeyCode::F3),
+            (ToggleFrameStats, KeyCode::F2),
+            (ToggleSupersampling, KeyCode::F4),
+            (CycleSdf, KeyCode::KeyM),
+            (ResetParams, KeyCode::Home),
+            (Up, KeyCode::ArrowUp),
+            (Down, KeyCode::ArrowDown),
+            (Left, KeyCode::ArrowLeft),
+            (Right, KeyCode::ArrowRight),
+            (ToggleTextureView, KeyCode::KeyT),
+            (ToggleColormap, KeyCode::KeyC),
+            (ToggleStepHeatmap, KeyCode::KeyH),
+            (ExportTerrain, KeyCode::KeyE),
+            (DumpGeneric, KeyCode::KeyS),
+            (DumpArray1, KeyCode::Digit1),
+            (DumpArray2, KeyCode::Digit2),
+            (DumpInterleaved, KeyCode::Digit3),
+            (Epsilon, KeyCode::KeyE),
+            (MaxSteps, KeyCode::KeyS),
+            (MaxDist, KeyCode::KeyW),
+            (EpsilonScale, KeyCode::KeyA),
+            (SdfParam, KeyCode::KeyB),
+            (ToggleTerrainAnimation, KeyCode::KeyA),
+            (ToggleClip, KeyCode::KeyK),
+            (ToggleLossView, KeyCode::KeyL),
+            (LossThreshold, KeyCode::KeyT),
+            (ClipHeight, KeyCode::KeyH),
+            (NextSeed, KeyCode::KeyN),
+            (RandomSeed, KeyCode::KeyR),
+            (AnimationSpeed, KeyCode::KeyS),
+            (SelectLayer1, KeyCode::Digit1),
+            (SelectLayer2, KeyCode::Digit2),
+            (SelectLayer3, KeyCode::Digit3),
+            (LayerFrequency, KeyCode::KeyF),
+            (LayerAmplitude, KeyCode::KeyE),
+            (BrushRadius, KeyCode::KeyR),
+            (BrushStrength, KeyCode::KeyF),
+            (CopyViewToken, KeyCode::KeyC),
+            (ViewTop, KeyCode::KeyT),
+            (ViewFront, KeyCode::KeyF),
+            (ViewSide, KeyCode::KeyR),
+            (ViewIsometric, KeyCode::KeyI),
+            (ToggleMouseLook, KeyCode::KeyL),
+            (SlowerTime, KeyCode::Comma),
+            (FasterTime, KeyCode::Period),
+            (MoveForward, KeyCode::KeyW),
+            (MoveBack, KeyCode::KeyS),
+            (MoveLeft, KeyCode::KeyA),
+            (MoveRight, KeyCode::KeyD),
+            (ZoomIn, KeyCode::KeyZ),
+            (ZoomOut, KeyCode::KeyX),
+            (RollLeft, KeyCode::KeyQ),
+            (RollRight, KeyCode::KeyE),
+            (ToggleOrtho, KeyCode::KeyO),
+            (Bookmark6, KeyCode::Digit6),
+            (Bookmark7, KeyCode::Digit7),
+            (Bookmark8, KeyCode::Digit8),
+            (Bookmark9, KeyCode::Digit9),
+            (Bookmark0, KeyCode::Digit0),
+            (Ambient, KeyCode::KeyA),
+            (FogDensity, KeyCode::KeyF),
+            (FogStart, KeyCode::KeyS),
+            (FogHue, KeyCode::KeyC),
+            (SkyHorizon, KeyCode::KeyH),
+            (SkyZenith, KeyCode::KeyZ),
+            (Exposure, KeyCode::KeyX),
+            (CycleTonemap, KeyCode::KeyT),
+        ]))
+    }
+}
@@ -1,3 +1,4 @@
 pub(crate) mod consts;
+pub(crate) mod key_bindings;
 pub(crate) mod structs;
 pub(crate) mod vertices;
@@ -1,21 +1,49 @@
+use std::sync::Arc;
+
+use super::consts::DEFAULT_IDLE_FPS;
+use super::key_bindings::KeyBindings;
+
 #[repr(C)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub(crate) struct TimeUniform {
     pub(crate) time: f32,
 }
 
+// Must match the single f32 the shaders bind as time
+const _: () = assert!(std::mem::size_of::<TimeUniform>() == 4);
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct TerrainGen {
+    pub(crate) x_offset: u32,
+    pub(crate) y_offset: u32,
+    pub(crate) anim_speed: f32,
+    pub(crate) _padding: f32,
+}
+
 #[derive(Debug)]
 pub(crate) struct Buffers {
     pub(crate) vertex: wgpu::Buffer,
     pub(crate) time_uniform: wgpu::Buffer,
+    pub(crate) terrain_gen: wgpu::Buffer,
     pub(crate) view_params: wgpu::Buffer,
     pub(crate) ray_params: wgpu::Buffer,
+    pub(crate) terrain_params: wgpu::Buffer,
+    pub(crate) sdf_params: wgpu::Buffer,
+    pub(crate) clip_params: wgpu::Buffer,
+    pub(crate) brush_params: wgpu::Buffer,
+    pub(crate) precision_params: wgpu::Buffer,
+    pub(crate) texture_view_params: wgpu::Buffer,
+    pub(crate) screen_params: wgpu::Buffer,
+    // Light, fog, sky, tone and overlay params, see ShadingParams
+    pub(crate) shading_params: wgpu::Buffer,
     pub(crate) generic_debug: wgpu::Buffer,
-    pub(crate) cpu_read_generic_debug: wgpu::Buffer,
+    // Arc so a pending debug readback can hold them while State is borrowed
+    pub(crate) cpu_read_generic_debug: Arc<wgpu::Buffer>,
     pub(crate) debug_array1: wgpu::Buffer,
-    pub(crate) cpu_read_debug_array1: wgpu::Buffer,
+    pub(crate) cpu_read_debug_array1: Arc<wgpu::Buffer>,
     pub(crate) debug_array2: wgpu::Buffer,
-    pub(crate) cpu_read_debug_array2: wgpu::Buffer,
+    pub(crate) cpu_read_debug_array2: Arc<wgpu::Buffer>,
 }
 
 #[derive(Debug)]
@@ -26,10 +54,35 @@ pub(crate) struct BindGroups {
     pub(crate) frag_bgl: wgpu::BindGroupLayout,
     pub(crate) compute_bg: wgpu::BindGroup,
     pub(crate) compute_bgl: wgpu::BindGroupLayout,
+    pub(crate) debug_bg: wgpu::BindGroup,
+    pub(crate) debug_bgl: wgpu::BindGroupLayout,
     pub(crate) texture_bg: wgpu::BindGroup,
     pub(crate) texture_bgl: wgpu::BindGroupLayout,
     pub(crate) sampled_texture_bg: wgpu::BindGroup,
+    pub(crate) sampled_texture_half_bg: wgpu::BindGroup,
     pub(crate) sampled_texture_bgl: wgpu::BindGroupLayout,
+    // One per terrain mip level after the first, reading the level above it
+    pub(crate) terrain_mip_bgs: Vec<wgpu::BindGroup>,
+    pub(crate) terrain_mip_bgl: wgpu::BindGroupLayout,
+    // Reads the supersampled target, None at ss_factor 1
+    pub(crate) downsample_bg: Option<wgpu::BindGroup>,
+    pub(crate) downsample_bgl: wgpu::BindGroupLayout,
+    #[cfg(feature = "validate-bindings")]
+    pub(crate) layout_entries: BindGroupLayoutEntries,
+}
+
+// Copies of the entries each layout was created from, for startup validation
+#[cfg(feature = "validate-bindings")]
+#[derive(Debug)]
+pub(crate) struct BindGroupLayoutEntries {
+    pub(crate) uniform: Vec<wgpu::BindGroupLayoutEntry>,
+    pub(crate) frag: Vec<wgpu::BindGroupLayoutEntry>,
+    pub(crate) compute: Vec<wgpu::BindGroupLayoutEntry>,
+    pub(crate) debug: Vec<wgpu::BindGroupLayoutEntry>,
+    pub(crate) texture: Vec<wgpu::BindGroupLayoutEntry>,
+    pub(crate) sampled_texture: Vec<wgpu::BindGroupLayoutEntry>,
+    pub(crate) terrain_mip: Vec<wgpu::BindGroupLayoutEntry>,
+    pub(crate) downsample: Vec<wgpu::BindGroupLayoutEntry>,
 }
 
 #[derive(Debug)]
@@ -37,18 +90,58 @@ pub(crate) struct ShaderModules {
     pub(crate) v_shader: wgpu::ShaderModule,
     pub(crate) f_shader: wgpu::ShaderModule,
     pub(crate) generate_terrain: wgpu::ShaderModule,
+    pub(crate) terrain_brush: wgpu::ShaderModule,
+    pub(crate) convert_terrain: wgpu::ShaderModule,
+    pub(crate) terrain_normals: wgpu::ShaderModule,
+    pub(crate) terrain_mips: wgpu::ShaderModule,
+    pub(crate) downsample: wgpu::ShaderModule,
 }
 
 #[derive(Debug)]
 pub(crate) struct Pipelines {
     pub(crate) render: wgpu::RenderPipeline,
+    // The render pipeline drawing into ACCUMULATION_FORMAT, adding each motion blur
+    // sub-frame on top of the last
+    pub(crate) accumulate: wgpu::RenderPipeline,
     pub(crate) generate_terrain: wgpu::ComputePipeline,
+    pub(crate) terrain_brush: wgpu::ComputePipeline,
+    pub(crate) convert_terrain: wgpu::ComputePipeline,
+    pub(crate) terrain_normals: wgpu::ComputePipeline,
+    pub(crate) terrain_mips: wgpu::ComputePipeline,
+    // Draws the supersampled target into the frame
+    pub(crate) downsample: wgpu::RenderPipeline,
+}
+
+// Everything build_resources creates, none of it tied to a window
+#[derive(Debug)]
+pub(crate) struct Resources {
+    pub(crate) buffers: Buffers,
+    pub(crate) textures: Textures,
+    pub(crate) bind_groups: BindGroups,
+    pub(crate) pipelines: Pipelines,
 }
 
 #[derive(Debug)]
 pub(crate) struct Textures {
+    pub(crate) terrain_tex: wgpu::Texture,
     pub(crate) terrain_sampler: wgpu::Sampler,
+    // Level 0 only, for the compute passes to write
     pub(crate) terrain_view: wgpu::TextureView,
+    // Every mip level, for the fragment shader to sample
+    pub(crate) terrain_sampled_view: wgpu::TextureView,
+    pub(crate) terrain_half_view: wgpu::TextureView,
+    // Same size as terrain_tex, rewritten by terrain_normals.wgsl whenever it changes
+    pub(crate) terrain_normal_tex: wgpu::Texture,
+    pub(crate) terrain_normal_view: wgpu::TextureView,
+    // Window sized, recreated on resize
+    pub(crate) depth_tex: wgpu::Texture,
+    pub(crate) depth_view: wgpu::TextureView,
+    // Multisampled color target resolved into the frame, None without MSAA
+    pub(crate) msaa_view: Option<wgpu::TextureView>,
+    // ss_factor times the window size, the render pass draws here and the downsample
+    // pass filters it into the frame. None at ss_factor 1
+    pub(crate) ss_view: Option<wgpu::TextureView>,
+    pub(crate) ss_sampler: wgpu::Sampler,
 }
 
 // PARAMETERS
@@ -57,32 +150,328 @@ pub(crate) struct Params {
     pub(crate) ray_params: RayParams,
     pub(crate) view_params: ViewParams,
     pub(crate) terrain_params: TerrainParams,
+    pub(crate) terrain_anim_params: TerrainAnimParams,
+    pub(crate) overlay_params: OverlayParams,
+    pub(crate) sdf_params: SdfParams,
+    pub(crate) clip_params: ClipParams,
+    pub(crate) brush_params: BrushParams,
+    pub(crate) precision_params: PrecisionParams,
+    pub(crate) texture_view_params: TextureViewParams,
+    pub(crate) screen_params: ScreenParams,
+    pub(crate) light_params: LightParams,
+    pub(crate) fog_params: FogParams,
+    pub(crate) sky_params: SkyParams,
+    pub(crate) tone_params: ToneParams,
+    pub(crate) key_bindings: KeyBindings,
+    pub(crate) frame_rate: FrameRateParams,
+    // Preset --autotune picked, saved with the config so it only runs once
+    pub(crate) quality_preset: Option<String>,
+}
+
+// Frame rate throttle from the config's [frame_rate] table. Without an active_fps,
+// active frames aren't capped and only the present mode paces them
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub(crate) struct FrameRateParams {
+    pub(crate) idle_fps: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) active_fps: Option<f64>,
+}
+
+impl Default for FrameRateParams {
+    fn default() -> Self {
+        Self {
+            idle_fps: DEFAULT_IDLE_FPS,
+            active_fps: None,
+        }
+    }
 }
 
 #[repr(C)]
-#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    bytemuck::Pod,
+    bytemuck::Zeroable,
+    serde::Serialize,
+    serde::Deserialize,
+)]
 pub(crate) struct RayParams {
     pub(crate) epsilon: f32,
     pub(crate) max_dist: f32,
     pub(crate) max_steps: f32,
+    // The hit threshold grows by this per unit marched, configs saved before it
+    // existed load with a fixed epsilon
+    #[serde(default)]
+    pub(crate) epsilon_scale: f32,
 }
 
+// Bound as a uniform, matches RayParams in frag.wgsl field for field, four f32 with no padding
+const _: () = assert!(std::mem::size_of::<RayParams>().is_multiple_of(16));
+
 #[repr(C)]
-#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    bytemuck::Pod,
+    bytemuck::Zeroable,
+    serde::Serialize,
+    serde::Deserialize,
+)]
 pub(crate) struct ViewParams {
     pub(crate) x_shift: f32,
     pub(crate) y_shift: f32,
     pub(crate) zoom: f32,
     pub(crate) x_rot: f32,
     pub(crate) y_rot: f32,
+    // Rate the scene clock runs at. SceneClock applies it to the time uniform,
+    // the shaders don't scale tu.time again
     pub(crate) time_modifier: f32,
     pub(crate) fov_degrees: f32,
+    // World space offset of the camera and its target, moved by the fly controls
+    pub(crate) cam_pos: [f32; 3],
+    // Roll around the view direction. Fields added after cam_pos go at the end so
+    // older view tokens still decode
+    #[serde(default)]
+    pub(crate) z_rot: f32,
+    // 1.0 for parallel rays, 0.0 for perspective
+    #[serde(default)]
+    pub(crate) ortho: f32,
 }
 
+// Bound as a uniform, matches ViewParams in frag.wgsl, twelve f32 with no padding.
+// cam_pos is three scalars there: a vec3 would be aligned to 16 and shift everything
+// after it, and a uniform array<f32, 3> needs a 16 byte stride. New fields go in
+// fours, or with explicit padding, to keep a 16 byte multiple
+const _: () = assert!(std::mem::size_of::<ViewParams>().is_multiple_of(16));
+
+// Fields missing from a config file take their Default value
 #[repr(C)]
-#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(
+    Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable, serde::Serialize, serde::Deserialize,
+)]
+#[serde(default)]
 pub(crate) struct TerrainParams {
     pub(crate) f1_octaves: i32,
     pub(crate) f2_octaves: i32,
     pub(crate) f3_octaves: i32,
+    // Hashed into the noise lattice in generate_terrain.wgsl, 0 is the original terrain
+    #[serde(deserialize_with = "crate::app::config::wrapping_seed")]
+    pub(crate) seed: u32,
+    // Per layer sample frequency and base amplitude, see generate_terrain_map
+    pub(crate) f1_freq: f32,
+    pub(crate) f1_amp: f32,
+    pub(crate) f2_freq: f32,
+    pub(crate) f2_amp: f32,
+    pub(crate) f3_freq: f32,
+    pub(crate) f3_amp: f32,
+}
+
+// Layer 1 alone reproduces the original terrain, layers 2 and 3 start switched off
+impl Default for TerrainParams {
+    fn default() -> Self {
+        Self {
+            f1_octaves: 7,
+            f2_octaves: 7,
+            f3_octaves: 7,
+            seed: 0,
+            f1_freq: 4.0,
+            f1_amp: 1.0,
+            f2_freq: 16.0,
+            f2_amp: 0.0,
+            f3_freq: 64.0,
+            f3_amp: 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct TerrainAnimParams {
+    pub(crate) animated: bool,
+    pub(crate) speed: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct OverlayParams {
+    pub(crate) guide_color: [f32; 4],
+    // Crosshair and rule-of-thirds guides, 0 = hidden
+    pub(crate) show_guides: u32,
+    pub(crate) guide_opacity: f32,
+    pub(crate) _padding: [f32; 2],
+}
+
+// Which distance function the ray marcher renders, see map() in frag.wgsl
+pub(crate) const SDF_MODE_NAMES: [&str; 5] =
+    ["terrain", "mandelbulb", "gyroid", "sphere field", "custom"];
+// Index of "custom" in SDF_MODE_NAMES
+pub(crate) const SDF_MODE_CUSTOM: u32 = 4;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct SdfParams {
+    pub(crate) mode: u32,
+    pub(crate) mandelbulb_power: f32,
+    pub(crate) gyroid_scale: f32,
+    pub(crate) sphere_spacing: f32,
+}
+
+// Horizontal clipping plane, everything above `height` is cut away
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct ClipParams {
+    pub(crate) enabled: u32,
+    pub(crate) height: f32,
+    pub(crate) _padding: [f32; 2],
+}
+
+// Terrain paint brush, center/radius are in terrain texels
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct BrushParams {
+    pub(crate) center: [f32; 2],
+    pub(crate) origin: [u32; 2],
+    pub(crate) radius: f32,
+    pub(crate) strength: f32,
+    // 1.0 raises, -1.0 lowers
+    pub(crate) direction: f32,
+    pub(crate) _padding: f32,
+}
+
+// Terrain is always generated at Rgba32Float, render_half switches rendering
+// to sample an Rgba16Float copy of it
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct PrecisionParams {
+    pub(crate) render_half: u32,
+    // Highlight where the rendered terrain differs from full precision by more than loss_threshold
+    pub(crate) show_loss: u32,
+    pub(crate) loss_threshold: f32,
+    pub(crate) _padding: f32,
+}
+
+// DEBUG mode views drawn instead of the shaded scene, the raw terrain texture or
+// a heatmap of the raymarch steps taken per pixel
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct TextureViewParams {
+    pub(crate) enabled: u32,
+    // 0 grayscale, 1 terrain colors
+    pub(crate) colormap: u32,
+    pub(crate) step_heatmap: u32,
+    pub(crate) _padding: u32,
+    // Render target pixel written to the generic debug buffer, DEBUG_PIXEL_CENTER for
+    // the screen center. A vec2<u32> in frag.wgsl, 8 byte aligned at offset 16
+    pub(crate) debug_pixel: [u32; 2],
+    pub(crate) _padding2: [u32; 2],
+}
+
+const _: () = assert!(std::mem::size_of::<TextureViewParams>().is_multiple_of(16));
+
+// Surface size in pixels, kept in sync with the window on resize
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct ScreenParams {
+    pub(crate) resolution: [f32; 2],
+    pub(crate) aspect: f32,
+    pub(crate) inv_aspect: f32,
+}
+
+impl ScreenParams {
+    pub(crate) fn new(width: u32, height: u32) -> Self {
+        let (w, h) = (width as f32, height as f32);
+        Self {
+            resolution: [w, h],
+            aspect: w / h,
+            inv_aspect: h / w,
+        }
+    }
+}
+
+// Fields ordered so the vec3s pack with the scalars under WGSL's 16 byte vec3 alignment
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct LightParams {
+    // Unit vector pointing towards the sun
+    pub(crate) sun_dir: [f32; 3],
+    pub(crate) ambient: f32,
+    pub(crate) sun_color: [f32; 3],
+    pub(crate) _padding: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct FogParams {
+    pub(crate) fog_color: [f32; 3],
+    pub(crate) fog_density: f32,
+    // Fraction of ray_params.max_dist before the fog begins
+    pub(crate) fog_start: f32,
+    pub(crate) _padding: [f32; 3],
+}
+
+// Background for rays that miss the terrain, a vertical gradient on the ray direction
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct SkyParams {
+    pub(crate) horizon_color: [f32; 3],
+    // Ray elevation, as the y of the unit direction, where the zenith color is reached
+    pub(crate) horizon_blend: f32,
+    pub(crate) zenith_color: [f32; 3],
+    pub(crate) _padding: f32,
+}
+
+// Tone curve applied to the shaded scene, see tone_map in tone.wgsl
+pub(crate) const TONEMAP_NAMES: [&str; 3] = ["none", "reinhard", "aces"];
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct ToneParams {
+    // Linear scale on the scene color before the tone curve
+    pub(crate) exposure: f32,
+    // Index into TONEMAP_NAMES
+    pub(crate) tonemap: u32,
+    // Set when the color target isn't an sRGB format, so the shader applies the sRGB
+    // curve itself. On an sRGB target the hardware encodes on write
+    pub(crate) encode_srgb: u32,
+    // Set only while --motion-blur sub-frames are rendered, the scene color is then
+    // written untouched for the resolve pass to average and tone map
+    pub(crate) linear_output: u32,
+}
+
+// The params applied to the marched color, bound as one uniform so the fragment stage
+// stays within the default uniform buffer limit. Matches ShadingParams in frag.wgsl,
+// where every member starts on a 16 byte boundary. Each is still kept and written
+// separately, at its offset, by the update_*_params_buffer functions
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct ShadingParams {
+    pub(crate) light: LightParams,
+    pub(crate) fog: FogParams,
+    pub(crate) sky: SkyParams,
+    pub(crate) tone: ToneParams,
+    pub(crate) overlay: OverlayParams,
+}
+
+const _: () = {
+    assert!(std::mem::size_of::<LightParams>().is_multiple_of(16));
+    assert!(std::mem::size_of::<FogParams>().is_multiple_of(16));
+    assert!(std::mem::size_of::<SkyParams>().is_multiple_of(16));
+    assert!(std::mem::size_of::<ToneParams>().is_multiple_of(16));
+    assert!(std::mem::size_of::<OverlayParams>().is_multiple_of(16));
+};
+
+impl ShadingParams {
+    pub(crate) fn new(params: &Params) -> Self {
+        Self {
+            light: params.light_params,
+            fog: params.fog_params,
+            sky: params.sky_params,
+            tone: params.tone_params,
+            overlay: params.overlay_params,
+        }
+    }
 }
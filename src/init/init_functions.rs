@@ -1,41 +1,131 @@
-use wgpu::util::DeviceExt;
+use std::sync::Arc;
+
+use anyhow::bail;
+use nalgebra::Vector3;
+
+#[cfg(feature = "validate-bindings")]
+use crate::collections::structs::BindGroupLayoutEntries;
+#[cfg(feature = "validate-bindings")]
+use crate::init::validate_bindings::validate_bindings;
 
 use crate::collections::{
-    consts::{TERRAIN_TEXTURE_HEIGHT, TERRAIN_TEXTURE_WIDTH, TERRAIN_TEX_BUF_SIZE},
+    consts::{
+        terrain_mip_levels, ACCUMULATE_BLEND, ACCUMULATION_FORMAT, DEBUG_ARRAY_BINDING_SIZE,
+        DEBUG_ARRAY_SIZE, DEBUG_PIXEL_CENTER, DEPTH_FORMAT, GENERIC_DEBUG_SIZE, SCREEN_HEIGHT,
+        SCREEN_WIDTH,
+    },
+    key_bindings::KeyBindings,
     structs::{
-        BindGroups, Buffers, Params, Pipelines, RayParams, ShaderModules, TerrainParams, Textures,
-        TimeUniform, ViewParams,
+        BindGroups, BrushParams, Buffers, ClipParams, FogParams, FrameRateParams, LightParams,
+        OverlayParams, Params, Pipelines, PrecisionParams, RayParams, Resources, ScreenParams,
+        SdfParams, ShaderModules, ShadingParams, SkyParams, TerrainAnimParams, TerrainGen,
+        TerrainParams, TextureViewParams, Textures, TimeUniform, ToneParams, ViewParams,
     },
     vertices::{vertices_as_bytes, VERTICES},
 };
 
-pub(crate) fn init_shader_modules(device: &wgpu::Device) -> ShaderModules {
-    let vdesc = wgpu::ShaderModuleDescriptor {
-        label: Some("Vertex Shader"),
-        source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/v2.wgsl").into()),
-    };
-    let v_shader = device.create_shader_module(vdesc);
+// Run `f` in a validation error scope, so a WGSL typo or a bad pipeline is returned
+// as an error naming `label` instead of reaching the uncaptured error handler
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn validation_scope<T>(
+    device: &wgpu::Device,
+    label: &str,
+    f: impl FnOnce() -> T,
+) -> anyhow::Result<T> {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let value = f();
+    match futures::executor::block_on(device.pop_error_scope()) {
+        Some(e) => bail!("{}: {}", label, e),
+        None => Ok(value),
+    }
+}
 
-    let fdesc = wgpu::ShaderModuleDescriptor {
-        label: Some("Fragment Shader"),
-        source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/frag.wgsl").into()),
-    };
-    let f_shader = device.create_shader_module(fdesc);
+// The browser can't block on the scope, errors go to the uncaptured error handler and
+// the console instead
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn validation_scope<T>(
+    _device: &wgpu::Device,
+    _label: &str,
+    f: impl FnOnce() -> T,
+) -> anyhow::Result<T> {
+    Ok(f())
+}
 
-    let generate_terrain_desc = wgpu::ShaderModuleDescriptor {
-        label: Some("Generate Terrain Shader"),
-        source: wgpu::ShaderSource::Wgsl(
-            include_str!("../shaders/compute/generate_terrain.wgsl").into(),
-        ),
-    };
+// frag.wgsl and motion_blur.wgsl with the tone curve they share appended
+pub(crate) const FRAG_SOURCE: &str = concat!(
+    include_str!("../shaders/frag.wgsl"),
+    include_str!("../shaders/tone.wgsl")
+);
+pub(crate) const MOTION_BLUR_SOURCE: &str = concat!(
+    include_str!("../shaders/motion_blur.wgsl"),
+    include_str!("../shaders/tone.wgsl")
+);
+
+pub(crate) fn create_shader_module(
+    device: &wgpu::Device,
+    label: &str,
+    source: &str,
+) -> anyhow::Result<wgpu::ShaderModule> {
+    validation_scope(device, label, || {
+        device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        })
+    })
+}
+
+pub(crate) fn init_shader_modules(device: &wgpu::Device) -> anyhow::Result<ShaderModules> {
+    let v_shader =
+        create_shader_module(device, "Vertex Shader", include_str!("../shaders/v2.wgsl"))?;
 
-    let generate_terrain = device.create_shader_module(generate_terrain_desc);
+    let f_shader = create_shader_module(device, "Fragment Shader", FRAG_SOURCE)?;
 
-    ShaderModules {
+    let generate_terrain = create_shader_module(
+        device,
+        "Generate Terrain Shader",
+        include_str!("../shaders/compute/generate_terrain.wgsl"),
+    )?;
+
+    let terrain_brush = create_shader_module(
+        device,
+        "Terrain Brush Shader",
+        include_str!("../shaders/compute/terrain_brush.wgsl"),
+    )?;
+
+    let convert_terrain = create_shader_module(
+        device,
+        "Convert Terrain Shader",
+        include_str!("../shaders/compute/convert_terrain.wgsl"),
+    )?;
+
+    let terrain_normals = create_shader_module(
+        device,
+        "Terrain Normals Shader",
+        include_str!("../shaders/compute/terrain_normals.wgsl"),
+    )?;
+
+    let terrain_mips = create_shader_module(
+        device,
+        "Terrain Mips Shader",
+        include_str!("../shaders/compute/terrain_mips.wgsl"),
+    )?;
+
+    let downsample = create_shader_module(
+        device,
+        "Downsample Shader",
+        include_str!("../shaders/downsample.wgsl"),
+    )?;
+
+    Ok(ShaderModules {
         v_shader,
         f_shader,
         generate_terrain,
-    }
+        terrain_brush,
+        convert_terrain,
+        terrain_normals,
+        terrain_mips,
+        downsample,
+    })
 }
 
 pub(crate) fn init_params() -> Params {
@@ -43,6 +133,7 @@ pub(crate) fn init_params() -> Params {
         epsilon: 0.01,
         max_dist: 1500.0,
         max_steps: 2500.0,
+        epsilon_scale: 0.0002,
     };
 
     let view_params = ViewParams {
@@ -53,18 +144,113 @@ pub(crate) fn init_params() -> Params {
         y_rot: 0.0,
         time_modifier: 1.0,
         fov_degrees: 90.0,
+        cam_pos: [0.0; 3],
+        z_rot: 0.0,
+        ortho: 0.0,
     };
 
-    let terrain_params = TerrainParams {
-        f1_octaves: 7,
-        f2_octaves: 7,
-        f3_octaves: 7,
+    let terrain_params = TerrainParams::default();
+
+    let terrain_anim_params = TerrainAnimParams {
+        animated: false,
+        speed: 0.05,
+    };
+
+    let overlay_params = OverlayParams {
+        guide_color: [1.0, 1.0, 1.0, 1.0],
+        show_guides: 0,
+        guide_opacity: 0.5,
+        _padding: [0.0; 2],
+    };
+
+    let sdf_params = SdfParams {
+        mode: 0,
+        mandelbulb_power: 8.0,
+        gyroid_scale: 0.1,
+        sphere_spacing: 40.0,
+    };
+
+    let clip_params = ClipParams {
+        enabled: 0,
+        height: 0.0,
+        _padding: [0.0; 2],
+    };
+
+    let brush_params = BrushParams {
+        center: [0.0; 2],
+        origin: [0; 2],
+        radius: 32.0,
+        strength: 0.01,
+        direction: 1.0,
+        _padding: 0.0,
+    };
+
+    let precision_params = PrecisionParams {
+        render_half: 0,
+        show_loss: 0,
+        loss_threshold: 0.001,
+        _padding: 0.0,
+    };
+
+    let texture_view_params = TextureViewParams {
+        enabled: 0,
+        colormap: 0,
+        step_heatmap: 0,
+        _padding: 0,
+        debug_pixel: [DEBUG_PIXEL_CENTER; 2],
+        _padding2: [0; 2],
+    };
+
+    let light_params = LightParams {
+        // Where the old fixed point light sat as seen from the origin
+        sun_dir: Vector3::new(40.0, 100.0, -300.0).normalize().into(),
+        ambient: 0.05,
+        sun_color: [1.0; 3],
+        _padding: 0.0,
+    };
+
+    let fog_params = FogParams {
+        fog_color: [0.55, 0.62, 0.7],
+        fog_density: 2.0,
+        fog_start: 0.1,
+        _padding: [0.0; 3],
+    };
+
+    let sky_params = SkyParams {
+        // The fog color, so distant terrain fades into the sky
+        horizon_color: [0.55, 0.62, 0.7],
+        horizon_blend: 0.5,
+        zenith_color: [0.18, 0.32, 0.58],
+        _padding: 0.0,
+    };
+
+    let tone_params = ToneParams {
+        exposure: 1.0,
+        tonemap: 2,
+        // Set for the actual color target in State::init
+        encode_srgb: 0,
+        linear_output: 0,
     };
 
     Params {
         ray_params,
         view_params,
         terrain_params,
+        terrain_anim_params,
+        overlay_params,
+        sdf_params,
+        clip_params,
+        brush_params,
+        precision_params,
+        texture_view_params,
+        screen_params: ScreenParams::new(SCREEN_WIDTH, SCREEN_HEIGHT),
+        light_params,
+        fog_params,
+        sky_params,
+        tone_params,
+        key_bindings: KeyBindings::default(),
+        frame_rate: FrameRateParams::default(),
+        quality_preset: None,
     }
 }
 
@@ -82,7 +268,14 @@ pub(crate) fn init_buffers(device: &wgpu::Device, params: &Params) -> Buffers {
     // UNIFORM BUFFERS
     let time_uniform = device.create_buffer(&wgpu::BufferDescriptor {
         label: Some("Time Uniform Buffer"),
-        size: std::mem::size_of::<f32>() as wgpu::BufferAddress,
+        size: std::mem::size_of::<TimeUniform>() as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let terrain_gen = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Terrain Generation Uniform Buffer"),
+        size: std::mem::size_of::<TerrainGen>() as wgpu::BufferAddress,
         usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         mapped_at_creation: false,
     });
@@ -91,37 +284,97 @@ pub(crate) fn init_buffers(device: &wgpu::Device, params: &Params) -> Buffers {
     let ray_params = wgpu::util::DeviceExt::create_buffer_init(
         device,
         &wgpu::util::BufferInitDescriptor {
-            label: Some("Ray Marching Parameters Storage Buffer"),
-            contents: bytemuck::cast_slice(&[
-                params.ray_params.epsilon,
-                params.ray_params.max_dist,
-                params.ray_params.max_steps,
-            ]),
-            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            label: Some("Ray Marching Parameters Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[params.ray_params]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         },
     );
 
     let view_params = wgpu::util::DeviceExt::create_buffer_init(
         device,
         &wgpu::util::BufferInitDescriptor {
-            label: Some("Ray Marching Parameters Storage Buffer"),
-            contents: bytemuck::cast_slice(&[
-                params.view_params.x_shift,
-                params.view_params.y_shift,
-                params.view_params.zoom,
-                params.view_params.x_rot,
-                params.view_params.y_rot,
-                params.view_params.time_modifier,
-                params.view_params.fov_degrees,
-            ]),
+            label: Some("View Parameters Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[params.view_params]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        },
+    );
+
+    let terrain_params = wgpu::util::DeviceExt::create_buffer_init(
+        device,
+        &wgpu::util::BufferInitDescriptor {
+            label: Some("Terrain Parameters Storage Buffer"),
+            contents: bytemuck::cast_slice(&[params.terrain_params]),
             usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
         },
     );
 
+    let sdf_params = wgpu::util::DeviceExt::create_buffer_init(
+        device,
+        &wgpu::util::BufferInitDescriptor {
+            label: Some("SDF Parameters Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[params.sdf_params]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        },
+    );
+
+    let clip_params = wgpu::util::DeviceExt::create_buffer_init(
+        device,
+        &wgpu::util::BufferInitDescriptor {
+            label: Some("Clip Plane Parameters Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[params.clip_params]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        },
+    );
+
+    let brush_params = wgpu::util::DeviceExt::create_buffer_init(
+        device,
+        &wgpu::util::BufferInitDescriptor {
+            label: Some("Terrain Brush Parameters Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[params.brush_params]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        },
+    );
+
+    let precision_params = wgpu::util::DeviceExt::create_buffer_init(
+        device,
+        &wgpu::util::BufferInitDescriptor {
+            label: Some("Terrain Precision Parameters Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[params.precision_params]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        },
+    );
+
+    let screen_params = wgpu::util::DeviceExt::create_buffer_init(
+        device,
+        &wgpu::util::BufferInitDescriptor {
+            label: Some("Screen Parameters Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[params.screen_params]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        },
+    );
+
+    let shading_params = wgpu::util::DeviceExt::create_buffer_init(
+        device,
+        &wgpu::util::BufferInitDescriptor {
+            label: Some("Shading Parameters Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[ShadingParams::new(params)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        },
+    );
+
+    let texture_view_params = wgpu::util::DeviceExt::create_buffer_init(
+        device,
+        &wgpu::util::BufferInitDescriptor {
+            label: Some("Terrain Texture View Parameters Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[params.texture_view_params]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        },
+    );
+
     // STORAGE/CPU-READABLE BUFFER PAIRS
     let generic_debug = device.create_buffer(&wgpu::BufferDescriptor {
         label: Some("Debug Shaders Buffer"),
-        size: (std::mem::size_of::<[f32; 4]>()) as wgpu::BufferAddress,
+        size: GENERIC_DEBUG_SIZE,
         usage: wgpu::BufferUsages::STORAGE
             | wgpu::BufferUsages::COPY_SRC
             | wgpu::BufferUsages::COPY_DST,
@@ -130,14 +383,14 @@ pub(crate) fn init_buffers(device: &wgpu::Device, params: &Params) -> Buffers {
 
     let cpu_read_generic_debug = device.create_buffer(&wgpu::BufferDescriptor {
         label: Some("CPU Readable Buffer - Debug Shaders"),
-        size: (std::mem::size_of::<[f32; 4]>()) as wgpu::BufferAddress,
+        size: GENERIC_DEBUG_SIZE,
         usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
         mapped_at_creation: false,
     });
 
     let debug_array1 = device.create_buffer(&wgpu::BufferDescriptor {
         label: Some("Debug Shaders Buffer 1"),
-        size: (std::mem::size_of::<[[f32; 4]; 512]>()) as wgpu::BufferAddress,
+        size: DEBUG_ARRAY_SIZE,
         usage: wgpu::BufferUsages::STORAGE
             | wgpu::BufferUsages::COPY_SRC
             | wgpu::BufferUsages::COPY_DST,
@@ -146,14 +399,14 @@ pub(crate) fn init_buffers(device: &wgpu::Device, params: &Params) -> Buffers {
 
     let cpu_read_debug_array1 = device.create_buffer(&wgpu::BufferDescriptor {
         label: Some("CPU Readable Buffer 1 - Debug Shaders"),
-        size: (std::mem::size_of::<[[f32; 4]; 512]>()) as wgpu::BufferAddress,
+        size: DEBUG_ARRAY_SIZE,
         usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
         mapped_at_creation: false,
     });
 
     let debug_array2 = device.create_buffer(&wgpu::BufferDescriptor {
         label: Some("Debug Shaders Buffer 2"),
-        size: (std::mem::size_of::<[[f32; 4]; 512]>()) as wgpu::BufferAddress,
+        size: DEBUG_ARRAY_SIZE,
         usage: wgpu::BufferUsages::STORAGE
             | wgpu::BufferUsages::COPY_SRC
             | wgpu::BufferUsages::COPY_DST,
@@ -162,7 +415,7 @@ pub(crate) fn init_buffers(device: &wgpu::Device, params: &Params) -> Buffers {
 
     let cpu_read_debug_array2 = device.create_buffer(&wgpu::BufferDescriptor {
         label: Some("CPU Readable Buffer 2 - Debug Shaders"),
-        size: (std::mem::size_of::<[[f32; 4]; 512]>()) as wgpu::BufferAddress,
+        size: DEBUG_ARRAY_SIZE,
         usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
         mapped_at_creation: false,
     });
@@ -170,14 +423,23 @@ pub(crate) fn init_buffers(device: &wgpu::Device, params: &Params) -> Buffers {
     Buffers {
         vertex,
         time_uniform,
+        terrain_gen,
         view_params,
         ray_params,
+        terrain_params,
+        sdf_params,
+        clip_params,
+        brush_params,
+        precision_params,
+        texture_view_params,
+        screen_params,
+        shading_params,
         generic_debug,
-        cpu_read_generic_debug,
+        cpu_read_generic_debug: Arc::new(cpu_read_generic_debug),
         debug_array1,
-        cpu_read_debug_array1,
+        cpu_read_debug_array1: Arc::new(cpu_read_debug_array1),
         debug_array2,
-        cpu_read_debug_array2,
+        cpu_read_debug_array2: Arc::new(cpu_read_debug_array2),
     }
 }
 
@@ -186,17 +448,19 @@ pub(crate) fn init_bind_groups(
     buffers: &Buffers,
     textures: &Textures,
 ) -> BindGroups {
+    let uniform_bgl_entries = [wgpu::BindGroupLayoutEntry {
+        binding: 0,
+        visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<TimeUniform>() as _),
+        },
+        count: None,
+    }];
+
     let uniform_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        entries: &[wgpu::BindGroupLayoutEntry {
-            binding: 0,
-            visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
-            ty: wgpu::BindingType::Buffer {
-                ty: wgpu::BufferBindingType::Uniform,
-                has_dynamic_offset: false,
-                min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<TimeUniform>() as _),
-            },
-            count: None,
-        }],
+        entries: &uniform_bgl_entries,
         label: Some("uniform_bind_group_layout"),
     });
 
@@ -209,63 +473,93 @@ pub(crate) fn init_bind_groups(
         label: Some("uniforms_bind_group"),
     });
 
-    let frag_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        entries: &[
-            wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::COMPUTE | wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Storage { read_only: false },
-                    has_dynamic_offset: false,
-                    min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<RayParams>() as _),
-                },
-                count: None,
-            },
-            wgpu::BindGroupLayoutEntry {
-                binding: 1,
-                visibility: wgpu::ShaderStages::COMPUTE | wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Storage { read_only: false },
-                    has_dynamic_offset: false,
-                    min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<ViewParams>() as _),
-                },
-                count: None,
-            },
-            wgpu::BindGroupLayoutEntry {
-                binding: 7,
-                visibility: wgpu::ShaderStages::COMPUTE | wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Storage { read_only: false },
-                    has_dynamic_offset: false,
-                    min_binding_size: wgpu::BufferSize::new(
-                        std::mem::size_of::<[[f32; 4]; 512]>() as _
-                    ),
-                },
-                count: None,
-            },
-            wgpu::BindGroupLayoutEntry {
-                binding: 8,
-                visibility: wgpu::ShaderStages::COMPUTE | wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Storage { read_only: false },
-                    has_dynamic_offset: false,
-                    min_binding_size: wgpu::BufferSize::new(
-                        std::mem::size_of::<[[f32; 4]; 512]>() as _
-                    ),
-                },
-                count: None,
-            },
-            wgpu::BindGroupLayoutEntry {
-                binding: 9,
-                visibility: wgpu::ShaderStages::COMPUTE | wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Storage { read_only: false },
-                    has_dynamic_offset: false,
-                    min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<[f32; 4]>() as _),
-                },
-                count: None,
+    let frag_bgl_entries = [
+        wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::COMPUTE | wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<RayParams>() as _),
             },
-        ],
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility: wgpu::ShaderStages::COMPUTE | wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<ViewParams>() as _),
+            },
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: 2,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<ShadingParams>() as _),
+            },
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: 3,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<SdfParams>() as _),
+            },
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: 4,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<ClipParams>() as _),
+            },
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: 5,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<PrecisionParams>() as _),
+            },
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: 6,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: wgpu::BufferSize::new(
+                    std::mem::size_of::<TextureViewParams>() as _
+                ),
+            },
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: 7,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<ScreenParams>() as _),
+            },
+            count: None,
+        },
+    ];
+
+    let frag_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &frag_bgl_entries,
         label: Some("fragment_bind_group_layout"),
     });
 
@@ -281,58 +575,68 @@ pub(crate) fn init_bind_groups(
                 resource: buffers.view_params.as_entire_binding(),
             },
             wgpu::BindGroupEntry {
-                binding: 7,
-                resource: buffers.debug_array1.as_entire_binding(),
+                binding: 2,
+                resource: buffers.shading_params.as_entire_binding(),
             },
             wgpu::BindGroupEntry {
-                binding: 8,
-                resource: buffers.debug_array2.as_entire_binding(),
+                binding: 3,
+                resource: buffers.sdf_params.as_entire_binding(),
             },
             wgpu::BindGroupEntry {
-                binding: 9,
-                resource: buffers.generic_debug.as_entire_binding(),
+                binding: 4,
+                resource: buffers.clip_params.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: buffers.precision_params.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 6,
+                resource: buffers.texture_view_params.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 7,
+                resource: buffers.screen_params.as_entire_binding(),
             },
         ],
-        label: Some("compute_bind_group"),
+        label: Some("fragment_bind_group"),
     });
 
-    let compute_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        entries: &[
-            wgpu::BindGroupLayoutEntry {
-                binding: 7,
-                visibility: wgpu::ShaderStages::COMPUTE | wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Storage { read_only: false },
-                    has_dynamic_offset: false,
-                    min_binding_size: wgpu::BufferSize::new(
-                        std::mem::size_of::<[[f32; 4]; 512]>() as _
-                    ),
-                },
-                count: None,
-            },
-            wgpu::BindGroupLayoutEntry {
-                binding: 8,
-                visibility: wgpu::ShaderStages::COMPUTE | wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Storage { read_only: false },
-                    has_dynamic_offset: false,
-                    min_binding_size: wgpu::BufferSize::new(
-                        std::mem::size_of::<[[f32; 4]; 512]>() as _
-                    ),
-                },
-                count: None,
-            },
-            wgpu::BindGroupLayoutEntry {
-                binding: 9,
-                visibility: wgpu::ShaderStages::COMPUTE | wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Storage { read_only: false },
-                    has_dynamic_offset: false,
-                    min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<[f32; 4]>() as _),
-                },
-                count: None,
+    let compute_bgl_entries = [
+        wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<TerrainGen>() as _),
             },
-        ],
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<BrushParams>() as _),
+            },
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: 2,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<TerrainParams>() as _),
+            },
+            count: None,
+        },
+    ];
+
+    let compute_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &compute_bgl_entries,
         label: Some("compute_bind_group_layout"),
     });
 
@@ -340,23 +644,82 @@ pub(crate) fn init_bind_groups(
         layout: &compute_bgl,
         entries: &[
             wgpu::BindGroupEntry {
-                binding: 7,
+                binding: 0,
+                resource: buffers.terrain_gen.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: buffers.brush_params.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: buffers.terrain_params.as_entire_binding(),
+            },
+        ],
+        label: Some("compute_bind_group"),
+    });
+
+    // The DEBUG mode readback buffers, bound to group 3 of both the render and the
+    // compute pipelines
+    let debug_bgl_entries = [
+        wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::COMPUTE | wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: DEBUG_ARRAY_BINDING_SIZE,
+            },
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility: wgpu::ShaderStages::COMPUTE | wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: DEBUG_ARRAY_BINDING_SIZE,
+            },
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: 2,
+            visibility: wgpu::ShaderStages::COMPUTE | wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: wgpu::BufferSize::new(GENERIC_DEBUG_SIZE),
+            },
+            count: None,
+        },
+    ];
+
+    let debug_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &debug_bgl_entries,
+        label: Some("debug_bind_group_layout"),
+    });
+
+    let debug_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &debug_bgl,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
                 resource: buffers.debug_array1.as_entire_binding(),
             },
             wgpu::BindGroupEntry {
-                binding: 8,
+                binding: 1,
                 resource: buffers.debug_array2.as_entire_binding(),
             },
             wgpu::BindGroupEntry {
-                binding: 9,
+                binding: 2,
                 resource: buffers.generic_debug.as_entire_binding(),
             },
         ],
-        label: Some("compute_bind_group"),
+        label: Some("debug_bind_group"),
     });
 
-    let texture_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        entries: &[wgpu::BindGroupLayoutEntry {
+    let texture_bgl_entries = [
+        wgpu::BindGroupLayoutEntry {
             binding: 0,
             visibility: wgpu::ShaderStages::COMPUTE,
             ty: wgpu::BindingType::StorageTexture {
@@ -365,56 +728,237 @@ pub(crate) fn init_bind_groups(
                 view_dimension: wgpu::TextureViewDimension::D2,
             },
             count: None,
-        }],
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::WriteOnly,
+                format: wgpu::TextureFormat::Rgba16Float,
+                view_dimension: wgpu::TextureViewDimension::D2,
+            },
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: 2,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::WriteOnly,
+                format: wgpu::TextureFormat::Rgba32Float,
+                view_dimension: wgpu::TextureViewDimension::D2,
+            },
+            count: None,
+        },
+    ];
+
+    let texture_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &texture_bgl_entries,
         label: Some("texture_bgl"),
     });
 
     let texture_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
         layout: &texture_bgl,
-        entries: &[wgpu::BindGroupEntry {
-            binding: 0,
-            resource: wgpu::BindingResource::TextureView(&textures.terrain_view),
-        }],
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&textures.terrain_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(&textures.terrain_half_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(&textures.terrain_normal_view),
+            },
+        ],
         label: Some("texture_bg"),
     });
 
+    let sample_type = wgpu::TextureSampleType::Float {
+        filterable: float32_filterable(device),
+    };
+    let sampler_type = if float32_filterable(device) {
+        wgpu::SamplerBindingType::Filtering
+    } else {
+        wgpu::SamplerBindingType::NonFiltering
+    };
+
+    let sampled_texture_bgl_entries = [
+        wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(sampler_type),
+            count: None,
+        },
+        // Always the full precision terrain, for the precision loss view
+        wgpu::BindGroupLayoutEntry {
+            binding: 2,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: 3,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        },
+    ];
+
     let sampled_texture_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &sampled_texture_bgl_entries,
+        label: Some("sampled_texture_bgl"),
+    });
+
+    let sampled_texture_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &sampled_texture_bgl,
         entries: &[
-            wgpu::BindGroupLayoutEntry {
+            wgpu::BindGroupEntry {
                 binding: 0,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Texture {
-                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                    view_dimension: wgpu::TextureViewDimension::D2,
-                    multisampled: false,
-                },
-                count: None,
-            },
-            wgpu::BindGroupLayoutEntry {
+                resource: wgpu::BindingResource::TextureView(&textures.terrain_sampled_view),
+            },
+            wgpu::BindGroupEntry {
                 binding: 1,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                count: None,
+                resource: wgpu::BindingResource::Sampler(&textures.terrain_sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(&textures.terrain_sampled_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::TextureView(&textures.terrain_normal_view),
             },
         ],
-        label: Some("sampled_texture_bgl"),
+        label: Some("sampled_texture_bg"),
     });
 
-    let sampled_texture_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
+    // Same as sampled_texture_bg, but rendering samples the half precision copy
+    let sampled_texture_half_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
         layout: &sampled_texture_bgl,
         entries: &[
             wgpu::BindGroupEntry {
                 binding: 0,
-                resource: wgpu::BindingResource::TextureView(&textures.terrain_view),
+                resource: wgpu::BindingResource::TextureView(&textures.terrain_half_view),
             },
             wgpu::BindGroupEntry {
                 binding: 1,
                 resource: wgpu::BindingResource::Sampler(&textures.terrain_sampler),
             },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(&textures.terrain_sampled_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::TextureView(&textures.terrain_normal_view),
+            },
         ],
-        label: Some("sampled_texture_bg"),
+        label: Some("sampled_texture_half_bg"),
     });
 
+    let terrain_mip_bgl_entries = [
+        wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                sample_type,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::WriteOnly,
+                format: wgpu::TextureFormat::Rgba32Float,
+                view_dimension: wgpu::TextureViewDimension::D2,
+            },
+            count: None,
+        },
+    ];
+
+    let terrain_mip_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &terrain_mip_bgl_entries,
+        label: Some("terrain_mip_bgl"),
+    });
+
+    let mip_view = |level: u32| {
+        textures
+            .terrain_tex
+            .create_view(&wgpu::TextureViewDescriptor {
+                label: Some("terrain - Mip Level View"),
+                base_mip_level: level,
+                mip_level_count: Some(1),
+                ..Default::default()
+            })
+    };
+    let terrain_mip_bgs = (1..textures.terrain_tex.mip_level_count())
+        .map(|level| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &terrain_mip_bgl,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&mip_view(level - 1)),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&mip_view(level)),
+                    },
+                ],
+                label: Some("terrain_mip_bg"),
+            })
+        })
+        .collect();
+
+    let downsample_bgl_entries = [
+        wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        },
+    ];
+
+    let downsample_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &downsample_bgl_entries,
+        label: Some("downsample_bgl"),
+    });
+
+    let downsample_bg = init_downsample_bind_group(device, &downsample_bgl, textures);
+
     BindGroups {
         uniform_bg,
         uniform_bgl,
@@ -422,57 +966,144 @@ pub(crate) fn init_bind_groups(
         frag_bgl,
         compute_bg,
         compute_bgl,
+        debug_bg,
+        debug_bgl,
         texture_bg,
         texture_bgl,
         sampled_texture_bg,
+        sampled_texture_half_bg,
         sampled_texture_bgl,
+        terrain_mip_bgs,
+        terrain_mip_bgl,
+        downsample_bg,
+        downsample_bgl,
+        #[cfg(feature = "validate-bindings")]
+        layout_entries: BindGroupLayoutEntries {
+            uniform: uniform_bgl_entries.to_vec(),
+            frag: frag_bgl_entries.to_vec(),
+            compute: compute_bgl_entries.to_vec(),
+            debug: debug_bgl_entries.to_vec(),
+            texture: texture_bgl_entries.to_vec(),
+            sampled_texture: sampled_texture_bgl_entries.to_vec(),
+            terrain_mip: terrain_mip_bgl_entries.to_vec(),
+            downsample: downsample_bgl_entries.to_vec(),
+        },
     }
 }
 
+// Recreated with the supersampled target on resize
+pub(crate) fn init_downsample_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    textures: &Textures,
+) -> Option<wgpu::BindGroup> {
+    let ss_view = textures.ss_view.as_ref()?;
+    Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(ss_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&textures.ss_sampler),
+            },
+        ],
+        label: Some("downsample_bg"),
+    }))
+}
+
 pub(crate) fn init_pipelines(
     device: &wgpu::Device,
     bind_groups: &BindGroups,
     shader_modules: &ShaderModules,
-) -> Pipelines {
-    let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-        label: Some("Render Pipeline Layout"),
-        bind_group_layouts: &[
-            &bind_groups.uniform_bgl,
-            &bind_groups.frag_bgl,
-            &bind_groups.sampled_texture_bgl,
-        ],
-        push_constant_ranges: &[],
-    });
+    color_format: wgpu::TextureFormat,
+    sample_count: u32,
+) -> anyhow::Result<Pipelines> {
+    #[cfg(feature = "validate-bindings")]
+    {
+        let entries = &bind_groups.layout_entries;
+        validate_bindings(
+            "Fragment Shader",
+            FRAG_SOURCE,
+            &[
+                &entries.uniform,
+                &entries.frag,
+                &entries.sampled_texture,
+                &entries.debug,
+            ],
+        );
+        validate_bindings(
+            "Generate Terrain Shader",
+            include_str!("../shaders/compute/generate_terrain.wgsl"),
+            &[
+                &entries.uniform,
+                &entries.compute,
+                &entries.texture,
+                &entries.debug,
+            ],
+        );
+        validate_bindings(
+            "Terrain Brush Shader",
+            include_str!("../shaders/compute/terrain_brush.wgsl"),
+            &[
+                &entries.uniform,
+                &entries.compute,
+                &entries.texture,
+                &entries.debug,
+            ],
+        );
+        validate_bindings(
+            "Convert Terrain Shader",
+            include_str!("../shaders/compute/convert_terrain.wgsl"),
+            &[
+                &entries.uniform,
+                &entries.compute,
+                &entries.texture,
+                &entries.debug,
+            ],
+        );
+        validate_bindings(
+            "Terrain Normals Shader",
+            include_str!("../shaders/compute/terrain_normals.wgsl"),
+            &[
+                &entries.uniform,
+                &entries.compute,
+                &entries.texture,
+                &entries.debug,
+            ],
+        );
+        validate_bindings(
+            "Terrain Mips Shader",
+            include_str!("../shaders/compute/terrain_mips.wgsl"),
+            &[&entries.terrain_mip],
+        );
+        validate_bindings(
+            "Downsample Shader",
+            include_str!("../shaders/downsample.wgsl"),
+            &[&entries.downsample],
+        );
+    }
 
-    let render = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-        label: Some("Render Pipeline"),
-        layout: Some(&render_pipeline_layout),
-        vertex: wgpu::VertexState {
-            module: &shader_modules.v_shader,
-            entry_point: "main",
-            buffers: &[wgpu::VertexBufferLayout {
-                array_stride: 8, // 2 * 4byte float
-                step_mode: wgpu::VertexStepMode::Vertex,
-                attributes: &wgpu::vertex_attr_array![
-                    0 => Float32x2,
-                    1 => Float32x2,
-                ],
-            }],
-        },
-        fragment: Some(wgpu::FragmentState {
-            module: &shader_modules.f_shader,
-            entry_point: "main",
-            targets: &[Some(wgpu::ColorTargetState {
-                format: wgpu::TextureFormat::Bgra8UnormSrgb,
-                blend: Some(wgpu::BlendState::REPLACE),
-                write_mask: wgpu::ColorWrites::ALL,
-            })],
-        }),
-        primitive: wgpu::PrimitiveState::default(),
-        depth_stencil: None,
-        multisample: wgpu::MultisampleState::default(),
-        multiview: None,
-    });
+    let render = init_render_pipeline(
+        device,
+        bind_groups,
+        &shader_modules.v_shader,
+        &shader_modules.f_shader,
+        color_format,
+        sample_count,
+        wgpu::BlendState::REPLACE,
+    )?;
+    let accumulate = init_render_pipeline(
+        device,
+        bind_groups,
+        &shader_modules.v_shader,
+        &shader_modules.f_shader,
+        ACCUMULATION_FORMAT,
+        sample_count,
+        ACCUMULATE_BLEND,
+    )?;
 
     let compute_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
         label: Some("Compute Pipeline Layout"),
@@ -480,24 +1111,266 @@ pub(crate) fn init_pipelines(
             &bind_groups.uniform_bgl,
             &bind_groups.compute_bgl,
             &bind_groups.texture_bgl,
+            &bind_groups.debug_bgl,
         ],
         push_constant_ranges: &[],
     });
 
-    let generate_terrain = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-        label: Some("Generate Terrain Pipeline"),
-        layout: Some(&compute_pipeline_layout),
-        module: &shader_modules.generate_terrain,
-        entry_point: "generate_terrain_map",
+    let generate_terrain = validation_scope(device, "Generate Terrain Pipeline", || {
+        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Generate Terrain Pipeline"),
+            layout: Some(&compute_pipeline_layout),
+            module: &shader_modules.generate_terrain,
+            entry_point: "generate_terrain_map",
+        })
+    })?;
+
+    let terrain_brush = validation_scope(device, "Terrain Brush Pipeline", || {
+        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Terrain Brush Pipeline"),
+            layout: Some(&compute_pipeline_layout),
+            module: &shader_modules.terrain_brush,
+            entry_point: "apply_brush",
+        })
+    })?;
+
+    let convert_terrain = validation_scope(device, "Convert Terrain Pipeline", || {
+        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Convert Terrain Pipeline"),
+            layout: Some(&compute_pipeline_layout),
+            module: &shader_modules.convert_terrain,
+            entry_point: "convert_terrain",
+        })
+    })?;
+
+    let terrain_normals = validation_scope(device, "Terrain Normals Pipeline", || {
+        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Terrain Normals Pipeline"),
+            layout: Some(&compute_pipeline_layout),
+            module: &shader_modules.terrain_normals,
+            entry_point: "terrain_normals",
+        })
+    })?;
+
+    let terrain_mips_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Terrain Mips Pipeline Layout"),
+        bind_group_layouts: &[&bind_groups.terrain_mip_bgl],
+        push_constant_ranges: &[],
     });
 
-    Pipelines {
+    let terrain_mips = validation_scope(device, "Terrain Mips Pipeline", || {
+        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Terrain Mips Pipeline"),
+            layout: Some(&terrain_mips_layout),
+            module: &shader_modules.terrain_mips,
+            entry_point: "downsample",
+        })
+    })?;
+
+    let downsample_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Downsample Pipeline Layout"),
+        bind_group_layouts: &[&bind_groups.downsample_bgl],
+        push_constant_ranges: &[],
+    });
+
+    // Draws straight into the frame, after any MSAA resolve into the supersampled target
+    let downsample = validation_scope(device, "Downsample Pipeline", || {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Downsample Pipeline"),
+            layout: Some(&downsample_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_modules.downsample,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_modules.downsample,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    })?;
+
+    Ok(Pipelines {
         render,
+        accumulate,
         generate_terrain,
+        terrain_brush,
+        convert_terrain,
+        terrain_normals,
+        terrain_mips,
+        downsample,
+    })
+}
+
+// Also used to rebuild the render pipelines when a custom SDF is reloaded. `color_format`
+// has to be the format of the surface or offscreen target it draws into
+pub(crate) fn init_render_pipeline(
+    device: &wgpu::Device,
+    bind_groups: &BindGroups,
+    v_shader: &wgpu::ShaderModule,
+    f_shader: &wgpu::ShaderModule,
+    color_format: wgpu::TextureFormat,
+    sample_count: u32,
+    blend: wgpu::BlendState,
+) -> anyhow::Result<wgpu::RenderPipeline> {
+    let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Render Pipeline Layout"),
+        bind_group_layouts: &[
+            &bind_groups.uniform_bgl,
+            &bind_groups.frag_bgl,
+            &bind_groups.sampled_texture_bgl,
+            &bind_groups.debug_bgl,
+        ],
+        push_constant_ranges: &[],
+    });
+
+    validation_scope(device, "Render Pipeline", || {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Render Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: v_shader,
+                entry_point: "main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: 8, // 2 * 4byte float
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![
+                        0 => Float32x2,
+                        1 => Float32x2,
+                    ],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: f_shader,
+                entry_point: "main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(blend),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+        })
+    })
+}
+
+pub(crate) fn init_depth_texture(
+    device: &wgpu::Device,
+    width: u32,
+    height: u32,
+    sample_count: u32,
+) -> (wgpu::Texture, wgpu::TextureView) {
+    let depth_tex = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Depth Texture"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count,
+        dimension: wgpu::TextureDimension::D2,
+        format: DEPTH_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+    let depth_view = depth_tex.create_view(&wgpu::TextureViewDescriptor::default());
+    (depth_tex, depth_view)
+}
+
+pub(crate) fn init_msaa_texture(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    sample_count: u32,
+) -> Option<wgpu::TextureView> {
+    if sample_count == 1 {
+        return None;
     }
+
+    let msaa_tex = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("MSAA Color Texture"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+    Some(msaa_tex.create_view(&wgpu::TextureViewDescriptor::default()))
 }
 
-pub(crate) fn init_textures(device: &wgpu::Device, queue: &wgpu::Queue) -> Textures {
+// `width` and `height` are the window size, the target is ss_factor times it
+pub(crate) fn init_ss_texture(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    ss_factor: u32,
+) -> Option<wgpu::TextureView> {
+    if ss_factor == 1 {
+        return None;
+    }
+
+    let ss_tex = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Supersampled Color Texture"),
+        size: wgpu::Extent3d {
+            width: width * ss_factor,
+            height: height * ss_factor,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+    Some(ss_tex.create_view(&wgpu::TextureViewDescriptor::default()))
+}
+
+// Rgba32Float terrain textures can only be linearly filtered with FLOAT32_FILTERABLE,
+// which request_device leaves out when the adapter lacks it
+fn float32_filterable(device: &wgpu::Device) -> bool {
+    device
+        .features()
+        .contains(wgpu::Features::FLOAT32_FILTERABLE)
+}
+
+pub(crate) fn init_textures(
+    device: &wgpu::Device,
+    size: winit::dpi::PhysicalSize<u32>,
+    color_format: wgpu::TextureFormat,
+    sample_count: u32,
+    terrain_resolution: u32,
+) -> Textures {
     let terrain_view_desc = wgpu::TextureViewDescriptor {
         label: Some("terrain - View Descriptor"),
         format: Some(wgpu::TextureFormat::Rgba32Float),
@@ -510,43 +1383,139 @@ pub(crate) fn init_textures(device: &wgpu::Device, queue: &wgpu::Queue) -> Textu
     };
 
     let terrain_tex_extent = wgpu::Extent3d {
-        width: TERRAIN_TEXTURE_WIDTH,
-        height: TERRAIN_TEXTURE_HEIGHT,
+        width: terrain_resolution,
+        height: terrain_resolution,
         depth_or_array_layers: 1,
     };
 
-    let terrain_tex = device.create_texture_with_data(
-        queue,
-        &wgpu::TextureDescriptor {
-            label: Some("terrain - Read-Write Storage Texture"),
-            size: terrain_tex_extent,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba32Float,
-            usage: wgpu::TextureUsages::STORAGE_BINDING
-                | wgpu::TextureUsages::TEXTURE_BINDING
-                | wgpu::TextureUsages::COPY_DST
-                | wgpu::TextureUsages::COPY_SRC,
-            view_formats: &[wgpu::TextureFormat::Rgba32Float],
-        },
-        wgpu::util::TextureDataOrder::default(),
-        &[0; TERRAIN_TEX_BUF_SIZE],
-    );
+    // Starts zeroed, level 0 is generated and the rest downsampled from it
+    let terrain_tex = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("terrain - Read-Write Storage Texture"),
+        size: terrain_tex_extent,
+        mip_level_count: terrain_mip_levels(terrain_resolution),
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba32Float,
+        usage: wgpu::TextureUsages::STORAGE_BINDING
+            | wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_DST
+            | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[wgpu::TextureFormat::Rgba32Float],
+    });
 
     let terrain_view = terrain_tex.create_view(&terrain_view_desc);
+    let terrain_sampled_view = terrain_tex.create_view(&wgpu::TextureViewDescriptor {
+        label: Some("terrain - Sampled View Descriptor"),
+        mip_level_count: None,
+        ..terrain_view_desc
+    });
+
+    let terrain_half_tex = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("terrain - Half Precision Texture"),
+        size: terrain_tex_extent,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba16Float,
+        usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[wgpu::TextureFormat::Rgba16Float],
+    });
+
+    let terrain_half_view = terrain_half_tex.create_view(&wgpu::TextureViewDescriptor {
+        label: Some("terrain - Half Precision View Descriptor"),
+        format: Some(wgpu::TextureFormat::Rgba16Float),
+        ..terrain_view_desc
+    });
+
+    let terrain_normal_tex = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("terrain - Normal Texture"),
+        size: terrain_tex_extent,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba32Float,
+        usage: wgpu::TextureUsages::STORAGE_BINDING
+            | wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[wgpu::TextureFormat::Rgba32Float],
+    });
+
+    let terrain_normal_view = terrain_normal_tex.create_view(&wgpu::TextureViewDescriptor {
+        label: Some("terrain - Normal View Descriptor"),
+        ..terrain_view_desc
+    });
+
+    let terrain_sampler = if float32_filterable(device) {
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("terrain - Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            anisotropy_clamp: 2,
+            ..Default::default()
+        })
+    } else {
+        // Nearest filtering, and so no anisotropy, is all a non-filtering binding allows
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("terrain - Nearest Sampler"),
+            ..Default::default()
+        })
+    };
 
-    let terrain_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-        label: Some("terrain - Sampler"),
+    let (width, height) = (size.width.max(1), size.height.max(1));
+    let (depth_tex, depth_view) = init_depth_texture(device, width, height, sample_count);
+    let msaa_view = init_msaa_texture(device, color_format, width, height, sample_count);
+
+    // Supersampling starts off, State::set_ss_factor creates the target
+    let ss_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("Supersampled Color Sampler"),
         mag_filter: wgpu::FilterMode::Linear,
         min_filter: wgpu::FilterMode::Linear,
-        mipmap_filter: wgpu::FilterMode::Linear,
-        anisotropy_clamp: 2,
         ..Default::default()
     });
 
     Textures {
+        terrain_tex,
         terrain_sampler,
         terrain_view,
+        terrain_sampled_view,
+        terrain_half_view,
+        terrain_normal_tex,
+        terrain_normal_view,
+        depth_tex,
+        depth_view,
+        msaa_view,
+        ss_view: None,
+        ss_sampler,
     }
 }
+
+// Shaders, buffers, textures, bind groups and pipelines for rendering `size` frames of
+// `color_format`, so the windowed and headless paths build them the same way
+pub(crate) fn build_resources(
+    device: &wgpu::Device,
+    params: &Params,
+    size: winit::dpi::PhysicalSize<u32>,
+    color_format: wgpu::TextureFormat,
+    sample_count: u32,
+    terrain_resolution: u32,
+) -> anyhow::Result<Resources> {
+    let shader_modules = init_shader_modules(device)?;
+    let buffers = init_buffers(device, params);
+    let textures = init_textures(device, size, color_format, sample_count, terrain_resolution);
+    let bind_groups = init_bind_groups(device, &buffers, &textures);
+    let pipelines = init_pipelines(
+        device,
+        &bind_groups,
+        &shader_modules,
+        color_format,
+        sample_count,
+    )?;
+
+    Ok(Resources {
+        buffers,
+        textures,
+        bind_groups,
+        pipelines,
+    })
+}
@@ -1 +1,3 @@
 pub(crate) mod init_functions;
+#[cfg(feature = "validate-bindings")]
+pub(crate) mod validate_bindings;
@@ -0,0 +1,76 @@
+// Startup check that every bind group layout entry matches what the WGSL declares,
+// so size/type mismatches fail fast with the shader and binding named
+// instead of surfacing as a pipeline validation error.
+
+pub(crate) fn validate_bindings(
+    shader_label: &str,
+    source: &str,
+    group_layouts: &[&[wgpu::BindGroupLayoutEntry]],
+) {
+    let module = naga::front::wgsl::parse_str(source)
+        .unwrap_or_else(|e| panic!("{}: {}", shader_label, e.emit_to_string(source)));
+
+    let mut errors = Vec::new();
+
+    for (_, var) in module.global_variables.iter() {
+        let Some(binding) = &var.binding else {
+            continue;
+        };
+        let name = var.name.as_deref().unwrap_or("<unnamed>");
+        let location = format!(
+            "{} `{}` @group({}) @binding({})",
+            shader_label, name, binding.group, binding.binding
+        );
+
+        let Some(entry) = group_layouts
+            .get(binding.group as usize)
+            .and_then(|entries| entries.iter().find(|e| e.binding == binding.binding))
+        else {
+            errors.push(format!("{}: no matching bind group layout entry", location));
+            continue;
+        };
+
+        match (var.space, entry.ty) {
+            (
+                naga::AddressSpace::Uniform,
+                wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    min_binding_size,
+                    ..
+                },
+            )
+            | (
+                naga::AddressSpace::Storage { .. },
+                wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { .. },
+                    min_binding_size,
+                    ..
+                },
+            ) => {
+                // Runtime sized arrays report the size of a single element
+                let required = module.types[var.ty].inner.size(module.to_ctx()) as u64;
+                let declared = min_binding_size.map_or(0, |s| s.get());
+                if declared < required {
+                    errors.push(format!(
+                        "{}: min_binding_size is {} bytes but the shader requires {}",
+                        location, declared, required
+                    ));
+                }
+            }
+            (naga::AddressSpace::Handle, wgpu::BindingType::Texture { .. })
+            | (naga::AddressSpace::Handle, wgpu::BindingType::StorageTexture { .. })
+            | (naga::AddressSpace::Handle, wgpu::BindingType::Sampler(_)) => {}
+            (space, ty) => errors.push(format!(
+                "{}: shader declares {:?} but the layout entry is {:?}",
+                location, space, ty
+            )),
+        }
+    }
+
+    if !errors.is_empty() {
+        panic!(
+            "bind group layout validation failed:\n{}",
+            errors.join("\n")
+        );
+    }
+}
@@ -1,72 +1,571 @@
+// The browser build skips the CLI, headless and selftest paths
+#![cfg_attr(target_arch = "wasm32", allow(dead_code, unused_imports))]
+
 mod app;
 mod init;
 mod updates;
-use app::state::State;
+mod util;
+#[cfg(target_arch = "wasm32")]
+mod web;
+use std::path::PathBuf;
+
+use app::{
+    clock::TimeSource, config::load_params, controls::set_mouse_look, custom_sdf::CustomSdf,
+    init_error::InitError, state::State, surface_error::ControlDirective,
+    view_token::decode_view_token,
+};
 mod collections;
-use collections::consts::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use anyhow::Context;
+use clap::Parser;
+use collections::consts::{
+    CONFIG_PATH, DEFAULT_SHUTTER, DEFAULT_TERRAIN_GEN_TILES, DEFAULT_TERRAIN_RESOLUTION,
+    MAX_MOTION_BLUR_SAMPLES, MIN_MAX_STEPS, MIN_TERRAIN_RESOLUTION, SCREEN_HEIGHT, SCREEN_WIDTH,
+    WINDOW_TITLE,
+};
+use collections::structs::{TimeUniform, SDF_MODE_CUSTOM};
+use init::init_functions::init_params;
+use updates::param_updates::{
+    update_precision_params_buffer, update_sdf_params_buffer, update_view_params_buffer,
+};
+use util::time;
 
 use winit::{
     dpi::PhysicalSize,
-    event::{Event, WindowEvent},
-    event_loop::{ControlFlow, EventLoop},
+    event::{DeviceEvent, Event, WindowEvent},
+    event_loop::{ControlFlow, EventLoop, EventLoopWindowTarget},
     window::WindowBuilder,
 };
 
+#[derive(Parser, Debug)]
+#[command(about = "Raymarched terrain and water renderer")]
+struct Args {
+    /// Initial window width in pixels
+    #[arg(long, default_value_t = SCREEN_WIDTH, value_parser = clap::value_parser!(u32).range(1..))]
+    width: u32,
+    /// Initial window height in pixels
+    #[arg(long, default_value_t = SCREEN_HEIGHT, value_parser = clap::value_parser!(u32).range(1..))]
+    height: u32,
+    /// Field of view in degrees, 1 to 179
+    #[arg(long, value_parser = parse_fov)]
+    fov: Option<f32>,
+    /// Raymarch step limit
+    #[arg(long, value_parser = parse_max_steps)]
+    max_steps: Option<f32>,
+    /// Params file to load instead of ./config.toml
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// Side of the square terrain texture in texels, a power of two from 64 up to the
+    /// GPU's texture size limit
+    #[arg(long, default_value_t = DEFAULT_TERRAIN_RESOLUTION, value_parser = parse_terrain_resolution)]
+    terrain_resolution: u32,
+    /// Generate the terrain in N x N tiles, one per frame, so slower GPUs don't hit a
+    /// watchdog timeout. 1 generates it in a single dispatch
+    #[arg(long, default_value_t = DEFAULT_TERRAIN_GEN_TILES, value_parser = clap::value_parser!(u32).range(1..))]
+    terrain_tiles: u32,
+    /// MSAA samples for the render pass, 1, 2 or 4
+    #[arg(long, default_value_t = 1, value_parser = parse_sample_count)]
+    msaa: u32,
+    /// Render the scene at 1 or 2 times the window size and filter it down, smoothing
+    /// the raymarched edges at the cost of fill rate
+    #[arg(long, default_value_t = 1, value_parser = parse_ss_factor)]
+    supersample: u32,
+    /// Comma separated wgpu backends, e.g. vulkan,gl. Defaults to all native backends
+    #[arg(long)]
+    backend: Option<String>,
+    /// fifo (vsync), mailbox or immediate
+    #[arg(long, value_parser = parse_present_mode)]
+    present_mode: Option<wgpu::PresentMode>,
+    /// Mouse-look radians per pixel of mouse movement
+    #[arg(long)]
+    mouse_sensitivity: Option<f32>,
+    /// Multiplier on the VIEW mode camera movement and rotation speed
+    #[arg(long, value_parser = parse_move_speed)]
+    move_speed: Option<f32>,
+    /// Exit non-zero after N failed frames in a row, for headless/CI runs
+    #[arg(long)]
+    surface_retries: Option<u32>,
+    /// Custom SDF shader file, reloaded when it changes
+    #[arg(long)]
+    sdf: Option<PathBuf>,
+    /// Grayscale image to use as the terrain
+    #[arg(long)]
+    heightmap: Option<PathBuf>,
+    /// Freeze the shader time at this value
+    #[arg(long)]
+    pin_time: Option<f32>,
+    /// Advance the shader time by this many seconds per frame instead of following the
+    /// clock, for reproducible output
+    #[arg(long, value_parser = parse_fixed_dt)]
+    fixed_dt: Option<f32>,
+    /// View token copied from a previous session
+    #[arg(long)]
+    import: Option<String>,
+    /// Print every param change
+    #[arg(long)]
+    log_params: bool,
+    /// Render from the half precision copy of the terrain
+    #[arg(long)]
+    terrain_f16: bool,
+    /// Benchmark the ray march quality presets on this GPU and save the best one that
+    /// keeps a 60 fps frame to the config. Only runs while the config has no preset yet
+    #[arg(long)]
+    autotune: bool,
+    /// Run the GPU self test and exit
+    #[arg(long)]
+    selftest: bool,
+    /// Render without opening a window, save the frame to --out and exit
+    #[arg(long)]
+    headless: bool,
+    /// PNG written by --headless
+    #[arg(long, default_value = "frame.png", requires = "headless")]
+    out: PathBuf,
+    /// Frames to run before saving with --headless, the time advances between them
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..), requires = "headless")]
+    frames: u32,
+    /// Average this many sub-frames across the shutter into the --headless frame, for
+    /// motion blur. Summed in linear light before the tone curve
+    #[arg(long, default_value_t = 1, value_parser = parse_motion_blur, requires = "headless")]
+    motion_blur: u32,
+    /// Fraction of the frame interval the shutter is open for with --motion-blur
+    #[arg(long, default_value_t = DEFAULT_SHUTTER, value_parser = parse_shutter, requires = "headless")]
+    shutter: f32,
+}
+
+#[cfg(target_arch = "wasm32")]
+fn main() {
+    web::start();
+}
+
+#[cfg(not(target_arch = "wasm32"))]
 fn main() {
     env_logger::init();
-    let event_loop = EventLoop::new().expect("event loop should init");
+    let args = Args::parse();
+
+    let mut params = match load_params(args.config.as_deref()) {
+        Ok(params) => params,
+        Err(e) if args.config.is_some() => {
+            eprintln!("Error: {:#}", e);
+            std::process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("{:#}, using the default params", e);
+            init_params()
+        }
+    };
+    if let Some(fov) = args.fov {
+        params.view_params.fov_degrees = fov;
+    }
+    if let Some(max_steps) = args.max_steps {
+        params.ray_params.max_steps = max_steps;
+    }
+
+    let backends = args
+        .backend
+        .as_deref()
+        .map(wgpu::util::parse_backends_from_comma_list)
+        .unwrap_or(wgpu::Backends::all());
+
+    let (event_loop, mut state) = match init_state(&args, backends, params) {
+        Ok(init) => init,
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            if let Some(hint) = e.downcast_ref::<InitError>().and_then(init_hint) {
+                eprintln!("{}", hint);
+            }
+            std::process::exit(1);
+        }
+    };
+
+    state.log_param_changes = args.log_params;
+    state.terrain_gen_tiles = args.terrain_tiles;
+
+    if args.terrain_f16 {
+        state.params.precision_params.render_half = 1;
+        update_precision_params_buffer(&mut state);
+    }
+
+    if let Some(mode) = args.present_mode {
+        state.set_present_mode(mode);
+    }
+
+    if args.supersample > 1 {
+        state.set_ss_factor(args.supersample);
+    }
+
+    if let Some(n) = args.surface_retries {
+        state.surface_error_policy.max_consecutive_errors = Some(n);
+    }
+
+    if let Some(sensitivity) = args.mouse_sensitivity {
+        state.mouse.sensitivity = sensitivity;
+    }
+
+    if let Some(speed) = args.move_speed {
+        state.controls.speed = speed;
+    }
+
+    if let Some(path) = args.sdf {
+        state.custom_sdf = Some(CustomSdf::new(path));
+        match state.load_custom_sdf() {
+            Ok(()) => {
+                state.params.sdf_params.mode = SDF_MODE_CUSTOM;
+                update_sdf_params_buffer(&mut state);
+            }
+            // Still watched, so fixing the file picks it up
+            Err(e) => eprintln!("Custom SDF failed to load: {:#}", e),
+        }
+    }
+
+    if let Some(path) = args.heightmap {
+        match state.load_heightmap(&path) {
+            Ok(()) => println!("Loaded heightmap {}", path.display()),
+            Err(e) => eprintln!("Ignoring --heightmap: {:#}", e),
+        }
+    }
+
+    if let Some(time) = args.pin_time {
+        state.pinned_time = Some(time);
+    }
+
+    if let Some(dt) = args.fixed_dt {
+        state.clock.restart(TimeSource::Fixed { dt, frame: 0 });
+    }
+
+    if let Some(token) = args.import {
+        match decode_view_token(&token) {
+            Ok(view_params) => {
+                state.params.view_params = view_params;
+                update_view_params_buffer(&mut state);
+            }
+            Err(e) => eprintln!("Ignoring --import: {:#}", e),
+        }
+    }
+
+    if args.autotune {
+        autotune(&mut state, args.config.as_deref());
+    }
+
+    println!(
+        "{} {}x{}, {}x MSAA, {}x supersampling, fov {}, max_steps {}, params from {}",
+        if args.headless { "Headless" } else { "Window" },
+        state.size.width,
+        state.size.height,
+        state.sample_count,
+        state.ss_factor,
+        state.params.view_params.fov_degrees,
+        state.params.ray_params.max_steps,
+        match &args.config {
+            Some(path) => path.display().to_string(),
+            None if std::path::Path::new(CONFIG_PATH).exists() => CONFIG_PATH.to_string(),
+            None => "defaults".to_string(),
+        }
+    );
+
+    if args.selftest {
+        match state.self_test() {
+            Ok(()) => std::process::exit(0),
+            Err(e) => {
+                eprintln!("{:#}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let Some(event_loop) = event_loop else {
+        let blur = app::motion_blur::MotionBlur {
+            samples: args.motion_blur,
+            shutter: args.shutter,
+        };
+        match state.render_headless(args.frames, blur, &args.out) {
+            Ok(()) => {
+                println!("Saved {}", args.out.display());
+                std::process::exit(0);
+            }
+            Err(e) => {
+                eprintln!("Headless render failed: {:#}", e);
+                std::process::exit(1);
+            }
+        }
+    };
+
+    run(event_loop, state);
+}
+
+// The window and its event loop, or None with --headless, and the state rendering to it.
+// Fails instead of panicking when there's no display, GPU adapter or device to use
+#[cfg(not(target_arch = "wasm32"))]
+fn init_state(
+    args: &Args,
+    backends: wgpu::Backends,
+    params: collections::structs::Params,
+) -> anyhow::Result<(Option<EventLoop<()>>, State<'static>)> {
+    let size = PhysicalSize::new(args.width, args.height);
+    if args.headless {
+        let state =
+            State::new_headless(backends, size, params, args.msaa, args.terrain_resolution)?;
+        return Ok((None, state));
+    }
+
+    let event_loop = EventLoop::new().context("Failed to connect to the display")?;
     event_loop.set_control_flow(ControlFlow::Poll);
 
     let window = WindowBuilder::new()
-        .with_title("winit window")
-        .with_inner_size(PhysicalSize::new(SCREEN_WIDTH, SCREEN_HEIGHT))
+        .with_title(WINDOW_TITLE)
+        .with_inner_size(size)
         .build(&event_loop)
-        .expect("window should open");
-
-    let mut state = futures::executor::block_on(State::new(window.into()));
-
-    event_loop
-        .run(move |event, elwt| match event {
-            Event::WindowEvent { ref event, .. } => match event {
-                WindowEvent::CloseRequested => elwt.exit(),
-                WindowEvent::RedrawRequested => {
-                    let elapsed_time = state.get_time();
-                    let time_bytes = elapsed_time.to_ne_bytes();
-                    state.queue.write_buffer(
-                        &state.buffers.time_uniform,
-                        0,
-                        bytemuck::cast_slice(&[time_bytes]),
-                    );
-
-                    state.update();
-
-                    match state.render() {
-                        Ok(_) => {}
-                        // Reconfigure the surface if lost
-                        Err(wgpu::SurfaceError::Lost) => state.resize(state.size),
-                        // The system is out of memory, quit
-                        Err(wgpu::SurfaceError::OutOfMemory) => {
-                            elwt.exit();
-                        }
-                        // All other errors (Outdated, Timeout) -> resolve by the next frame
-                        Err(e) => eprintln!("{:?}", e),
-                    };
-
-                    state.window.request_redraw();
+        .context("Failed to open a window, use --headless to render without one")?;
+
+    let state = State::new(
+        window.into(),
+        backends,
+        params,
+        args.msaa,
+        args.terrain_resolution,
+    )?;
+    Ok((Some(event_loop), state))
+}
+
+// What to try next when State can't be created
+fn init_hint(err: &InitError) -> Option<&'static str> {
+    match err {
+        InitError::Surface(_) | InitError::Present => {
+            Some("Try another --backend, or --headless to render without a window")
+        }
+        InitError::Adapter | InitError::Device(_) => {
+            Some("Try --backend gl or update your drivers")
+        }
+        InitError::TerrainResolution { .. } => Some("Pick a --terrain-resolution in that range"),
+        InitError::Limits(_) => Some("Try a smaller --terrain-resolution or another --backend"),
+        // Shader and pipeline errors are bugs rather than something to work around
+        InitError::Resources(_) => None,
+    }
+}
+
+// Picks and saves a preset the first time, later launches keep the saved one
+#[cfg(not(target_arch = "wasm32"))]
+fn autotune(state: &mut State, config: Option<&std::path::Path>) {
+    if let Some(preset) = &state.params.quality_preset {
+        println!(
+            "autotune: using the saved {} preset, remove quality_preset from the config to rerun",
+            preset
+        );
+        return;
+    }
+
+    let preset = state.autotune();
+    println!("autotune: picked the {} preset", preset.name);
+    state.params.quality_preset = Some(preset.name.to_string());
+
+    let path = config.unwrap_or(std::path::Path::new(CONFIG_PATH));
+    match state.params.save(path) {
+        Ok(()) => println!("Saved {}", path.display()),
+        Err(e) => eprintln!("Saving the autotune preset failed: {:#}", e),
+    }
+}
+
+// The event loop shared by the desktop and browser builds
+fn run(event_loop: EventLoop<()>, mut state: State<'static>) {
+    let handler = move |event: Event<()>, elwt: &EventLoopWindowTarget<()>| match event {
+        Event::WindowEvent { ref event, .. } => match event {
+            WindowEvent::CloseRequested => elwt.exit(),
+            WindowEvent::Resized(new_size) => state.resize(*new_size),
+            // Moving to a monitor with a different DPI keeps the logical size and changes
+            // the physical one. Most platforms follow up with Resized, this covers the
+            // ones that don't, a repeated size is cheap to reconfigure
+            WindowEvent::ScaleFactorChanged { .. } => {
+                if let Some(size) = state.window().map(|window| window.inner_size()) {
+                    state.resize(size);
                 }
-                WindowEvent::KeyboardInput { event, .. } => {
-                    state.controls.handle_keyboard_input(event);
+            }
+            WindowEvent::RedrawRequested => {
+                let now = time::Instant::now();
+                state.frame_dt = (now - state.last_frame).as_secs_f32();
+                state.last_frame = now;
+
+                // Keeps the idle frame rate throttle ticking until the window is restored
+                if state.minimized {
+                    return;
                 }
-                WindowEvent::Focused(focused) => {
-                    if !focused {
-                        // Clear the keys HashSet when the window loses focus
-                        state.controls.clear_keys();
-                        println!("Window lost focus, cleared keys.");
+
+                let time_uniform = TimeUniform {
+                    time: state.frame_time(),
+                };
+                state.queue.write_buffer(
+                    &state.buffers.time_uniform,
+                    0,
+                    bytemuck::cast_slice(&[time_uniform]),
+                );
+
+                state.update();
+
+                if let Err(e) = state.render() {
+                    if let ControlDirective::Exit(code) = state.handle_surface_error(e) {
+                        std::process::exit(code);
                     }
                 }
-                _ => {}
-            },
+            }
+            WindowEvent::KeyboardInput { event, .. } => {
+                state.last_input = time::Instant::now();
+                state.controls.handle_keyboard_input(event);
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                state.last_input = time::Instant::now();
+                state.mouse.handle_cursor_moved(*position);
+            }
+            WindowEvent::CursorLeft { .. } => state.mouse.handle_cursor_left(),
+            WindowEvent::MouseInput {
+                state: button_state,
+                button,
+                ..
+            } => {
+                state.last_input = time::Instant::now();
+                state.mouse.handle_mouse_input(*button, *button_state);
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                state.last_input = time::Instant::now();
+                state.mouse.handle_mouse_wheel(*delta);
+            }
+            WindowEvent::Focused(false) => {
+                // Clear the keys HashSet when the window loses focus
+                state.controls.clear_keys();
+                state.mouse.clear_buttons();
+                set_mouse_look(&mut state, false);
+                println!("Window lost focus, cleared keys.");
+            }
             _ => {}
-        })
-        .expect("event loop should run");
+        },
+        Event::DeviceEvent {
+            event: DeviceEvent::MouseMotion { delta },
+            ..
+        } => {
+            state.last_input = time::Instant::now();
+            state.mouse.handle_mouse_motion(delta);
+        }
+        Event::AboutToWait => {
+            // Render back to back while active unless the config caps it, sleep between
+            // frames at the idle rate otherwise
+            let frame_rate = state.params.frame_rate;
+            let fps = if state.is_active() && !state.minimized {
+                frame_rate.active_fps
+            } else {
+                Some(frame_rate.idle_fps)
+            };
+            let Some(fps) = fps else {
+                elwt.set_control_flow(ControlFlow::Poll);
+                if let Some(window) = state.window() {
+                    window.request_redraw();
+                }
+                return;
+            };
+            let next_frame = state.last_frame + std::time::Duration::from_secs_f64(1.0 / fps);
+
+            if time::Instant::now() >= next_frame {
+                elwt.set_control_flow(ControlFlow::Poll);
+                if let Some(window) = state.window() {
+                    window.request_redraw();
+                }
+            } else {
+                elwt.set_control_flow(ControlFlow::WaitUntil(next_frame));
+            }
+        }
+        _ => {}
+    };
+
+    #[cfg(not(target_arch = "wasm32"))]
+    event_loop.run(handler).expect("event loop should run");
+    // Returns straight away, the browser drives the loop from here
+    #[cfg(target_arch = "wasm32")]
+    winit::platform::web::EventLoopExtWebSys::spawn(event_loop, handler);
+}
+
+fn parse_fov(s: &str) -> Result<f32, String> {
+    let fov: f32 = s.parse().map_err(|e| format!("{}", e))?;
+    if (1.0..=179.0).contains(&fov) {
+        Ok(fov)
+    } else {
+        Err("expected a value in 1..=179".to_string())
+    }
+}
+
+fn parse_max_steps(s: &str) -> Result<f32, String> {
+    let steps: f32 = s.parse().map_err(|e| format!("{}", e))?;
+    if steps >= MIN_MAX_STEPS && steps.is_finite() {
+        Ok(steps)
+    } else {
+        Err("expected at least 1".to_string())
+    }
+}
+
+fn parse_terrain_resolution(s: &str) -> Result<u32, String> {
+    let resolution: u32 = s.parse().map_err(|e| format!("{}", e))?;
+    if resolution >= MIN_TERRAIN_RESOLUTION && resolution.is_power_of_two() {
+        Ok(resolution)
+    } else {
+        Err(format!(
+            "expected a power of two, at least {}",
+            MIN_TERRAIN_RESOLUTION
+        ))
+    }
+}
+
+fn parse_sample_count(s: &str) -> Result<u32, String> {
+    match s.parse::<u32>().map_err(|e| format!("{}", e))? {
+        n @ (1 | 2 | 4) => Ok(n),
+        _ => Err("expected 1, 2 or 4".to_string()),
+    }
+}
+
+fn parse_ss_factor(s: &str) -> Result<u32, String> {
+    match s.parse::<u32>().map_err(|e| format!("{}", e))? {
+        n @ (1 | 2) => Ok(n),
+        _ => Err("expected 1 or 2".to_string()),
+    }
+}
+
+fn parse_move_speed(s: &str) -> Result<f32, String> {
+    let speed: f32 = s.parse().map_err(|e| format!("{}", e))?;
+    if speed > 0.0 && speed.is_finite() {
+        Ok(speed)
+    } else {
+        Err("expected a positive value".to_string())
+    }
+}
+
+fn parse_fixed_dt(s: &str) -> Result<f32, String> {
+    let dt: f32 = s.parse().map_err(|e| format!("{}", e))?;
+    if dt > 0.0 && dt.is_finite() {
+        Ok(dt)
+    } else {
+        Err("expected a positive number of seconds".to_string())
+    }
+}
+
+fn parse_motion_blur(s: &str) -> Result<u32, String> {
+    let samples: u32 = s.parse().map_err(|e| format!("{}", e))?;
+    if (1..=MAX_MOTION_BLUR_SAMPLES).contains(&samples) {
+        Ok(samples)
+    } else {
+        Err(format!(
+            "expected a value in 1..={}",
+            MAX_MOTION_BLUR_SAMPLES
+        ))
+    }
+}
+
+fn parse_shutter(s: &str) -> Result<f32, String> {
+    let shutter: f32 = s.parse().map_err(|e| format!("{}", e))?;
+    if (0.0..=1.0).contains(&shutter) {
+        Ok(shutter)
+    } else {
+        Err("expected a fraction of the frame in 0..=1".to_string())
+    }
+}
+
+fn parse_present_mode(mode: &str) -> Result<wgpu::PresentMode, String> {
+    match mode.to_ascii_lowercase().as_str() {
+        "fifo" | "vsync" => Ok(wgpu::PresentMode::Fifo),
+        "mailbox" => Ok(wgpu::PresentMode::Mailbox),
+        "immediate" => Ok(wgpu::PresentMode::Immediate),
+        _ => Err("expected fifo, mailbox or immediate".to_string()),
+    }
 }
@@ -0,0 +1,13 @@
+@group(2) @binding(0) var terrain_tex: texture_storage_2d<rgba32float, read_write>;
+@group(2) @binding(1) var terrain_half_tex: texture_storage_2d<rgba16float, write>;
+
+// Copies the full precision terrain into the half precision texture used for rendering
+@compute
+@workgroup_size(32, 32, 1)
+fn convert_terrain(@builtin(global_invocation_id) id: vec3<u32>) {
+  if (any(id.xy >= textureDimensions(terrain_tex))) {
+    return;
+  }
+
+  textureStore(terrain_half_tex, id.xy, textureLoad(terrain_tex, id.xy));
+}
@@ -2,13 +2,6 @@ const PI: f32 = 3.14159265358979323846;
 const MAX_F32: f32 = 0x1.fffffep+127f;
 const MIN_POSITIVE_F32: f32 = 0x1.0p-126f;
 
-const SCREEN_WIDTH: f32 = 1376.0;
-const SCREEN_HEIGHT: f32 = 768.0;
-const I_SCREEN_WIDTH: i32 = 1376;
-const I_SCREEN_HEIGHT: i32 = 768;
-const TERRAIN_TEX_WIDTH: f32 = 2048.0;
-const TERRAIN_TEX_HEIGHT: f32 = 2048.0;
-
 const m2: mat2x2<f32> = mat2x2(
   0.80, 0.60,
   -0.60, 0.80,
@@ -21,16 +14,38 @@ const m2Inv: mat2x2<f32> = mat2x2(
 
 @group(0) @binding(0) var<uniform> tu: TimeUniform;
 
-@group(1) @binding(7) var<storage, read_write> debug_arr1: array<vec4<f32>>;
-@group(1) @binding(8) var<storage, read_write> debug_arr2: array<vec4<f32>>;
-@group(1) @binding(9) var<storage, read_write> debug: vec4<f32>;
+@group(1) @binding(0) var<uniform> gen: TerrainGen;
+@group(1) @binding(2) var<storage, read> tp: TerrainParams;
 
 @group(2) @binding(0) var terrain_tex: texture_storage_2d<rgba32float, read_write>;
 
+@group(3) @binding(0) var<storage, read_write> debug_arr1: array<vec4<f32>>;
+@group(3) @binding(1) var<storage, read_write> debug_arr2: array<vec4<f32>>;
+@group(3) @binding(2) var<storage, read_write> debug: vec4<f32>;
+
 struct TimeUniform {
   time: f32,
 }
 
+struct TerrainParams {
+  f1_octaves: i32,
+  f2_octaves: i32,
+  f3_octaves: i32,
+  seed: u32,
+  f1_freq: f32,
+  f1_amp: f32,
+  f2_freq: f32,
+  f2_amp: f32,
+  f3_freq: f32,
+  f3_amp: f32,
+}
+
+struct TerrainGen {
+  x_offset: u32,
+  y_offset: u32,
+  anim_speed: f32,
+}
+
 // PCG AND SEED
 var<private> seed: u32 = 1234;
 
@@ -47,6 +62,36 @@ fn pcg_f32() -> f32 {
 }
 
 // FBM
+// The seed's hash picks a whole cell shift of the lattice, added before the
+// permutation's mod 289 so it stays exact in f32, and an offset fed through one more
+// permutation so the gradients each cell gets are reshuffled rather than just moved.
+// The lattice still repeats every 289 cells, and seeds only differ up to the 289^3
+// combinations of the three. Seed 0 is the unseeded noise
+fn pcg_hash(v: u32) -> u32 {
+  let state = v * 747796405u + 2891336453u;
+  let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+  return (word >> 22u) ^ word;
+}
+
+fn seed_hash() -> vec3f {
+  if tp.seed == 0u {
+    return vec3f(0.);
+  }
+  let h = pcg_hash(tp.seed);
+  return vec3f(f32(h % 289u), f32((h / 289u) % 289u), f32((h / 83521u) % 289u));
+}
+
+fn seed_shift() -> vec4f {
+  return seed_hash().xyxy;
+}
+
+fn seed_permute(i: vec4f) -> vec4f {
+  if tp.seed == 0u {
+    return i;
+  }
+  return permute4(i + seed_hash().z);
+}
+
 // perlinNoise2 - MIT License. © Stefan Gustavson, Munrocket ------------------------------
 fn permute4(x: vec4f) -> vec4f { return ((x * 34. + 1.) * x) % vec4f(289.); }
 fn fade2(t: vec2f) -> vec2f { return t * t * t * (t * (t * 6. - 15.) + 10.); }
@@ -54,14 +99,14 @@ fn fade2(t: vec2f) -> vec2f { return t * t * t * (t * (t * 6. - 15.) + 10.); }
 fn perlinNoise2(P: vec2f) -> f32 {
     var Pi: vec4f = floor(P.xyxy) + vec4f(0., 0., 1., 1.);
     let Pf = fract(P.xyxy) - vec4f(0., 0., 1., 1.);
-    Pi = Pi % vec4f(289.); // To avoid truncation effects in permutation
+    Pi = (Pi + seed_shift()) % vec4f(289.); // To avoid truncation effects in permutation
 
     let ix = Pi.xzxz;
     let iy = Pi.yyww;
     let fx = Pf.xzxz;
     let fy = Pf.yyww;
 
-    let i = permute4(permute4(ix) + iy);
+    let i = seed_permute(permute4(permute4(ix) + iy));
 
     var gx: vec4f = 2. * fract(i * 0.0243902439) - 1.; // 1/41 = 0.024...
     let gy = abs(gx) - 0.5;
@@ -99,14 +144,14 @@ fn perlinNoise2(P: vec2f) -> f32 {
 fn perlinNoiseGrad3(P: vec2f) -> vec3<f32> {
     var Pi: vec4f = floor(P.xyxy) + vec4f(0., 0., 1., 1.);
     let Pf = fract(P.xyxy) - vec4f(0., 0., 1., 1.);
-    Pi = Pi % vec4f(289.); // To avoid truncation effects in permutation
+    Pi = (Pi + seed_shift()) % vec4f(289.); // To avoid truncation effects in permutation
 
     let ix = Pi.xzxz;
     let iy = Pi.yyww;
     let fx = Pf.xzxz;
     let fy = Pf.yyww;
 
-    let i = permute4(permute4(ix) + iy);
+    let i = seed_permute(permute4(permute4(ix) + iy));
 
     var gx: vec4f = 2. * fract(i * 0.0243902439) - 1.; // 1/41 = 0.024...
     let gy = abs(gx) - 0.5;
@@ -181,15 +226,45 @@ fn fbmD(pos: vec2<f32>, octaves: i32, fraction: f32) -> vec3<f32> {
   return vec3(res, grad);
 }
 
+fn layer(uv: vec2<f32>, freq: f32, octaves: i32, amp: f32, anim_offset: vec2<f32>) -> vec3<f32> {
+  let n = fbmD(uv * freq + anim_offset, octaves, amp);
+  return vec3(n.x, n.yz * (freq / 4.0));
+}
+
 @compute 
 @workgroup_size(32, 32, 1) 
 fn generate_terrain_map(@builtin(global_invocation_id) id: vec3<u32>) {
-  let tx_coord: vec2<u32> = id.xy;
-  let ptx_uv: vec2<f32> = ((2.0 * vec2(f32(tx_coord.x), f32(tx_coord.y))) / vec2(TERRAIN_TEX_WIDTH,
-  TERRAIN_TEX_HEIGHT)) - 1.0;
+  let tx_coord: vec2<u32> = id.xy + vec2(gen.x_offset, gen.y_offset);
+  let dims = textureDimensions(terrain_tex);
+  if (any(tx_coord >= dims)) {
+    return;
+  }
 
-  var ptx = textureLoad(terrain_tex, tx_coord);
-  
+  let ptx_uv: vec2<f32> = ((2.0 * vec2<f32>(tx_coord)) / vec2<f32>(dims)) - 1.0;
 
-  textureStore(terrain_tex, tx_coord, ptx);
+  // Drift the sample position over time, anim_speed is 0.0 unless animated terrain is on
+  let anim_offset = vec2(tu.time * gen.anim_speed, 0.0);
+
+  // The height is the sum of three fbm layers, layer i sampled at ptx_uv * fi_freq with
+  // fi_octaves octaves starting from amplitude fi_amp. Layer 1 at freq 4.0 and amp 1.0 is
+  // the original terrain, layers 2 and 3 add finer detail and are skipped at amp 0.0.
+  // Each layer's gradient is rescaled to the layer 1 default coordinates, ptx_uv * 4.0,
+  // which is what the heightmap import and the readers of the texture assume
+  var terrain = layer(ptx_uv, tp.f1_freq, tp.f1_octaves, tp.f1_amp, anim_offset);
+  if (tp.f2_amp > 0.0) {
+    terrain += layer(ptx_uv + vec2(5.2, 1.3), tp.f2_freq, tp.f2_octaves, tp.f2_amp, anim_offset);
+  }
+  if (tp.f3_amp > 0.0) {
+    terrain += layer(ptx_uv + vec2(-3.7, 8.1), tp.f3_freq, tp.f3_octaves, tp.f3_amp, anim_offset);
+  }
+
+  textureStore(terrain_tex, tx_coord, vec4(terrain, 1.0));
+
+  // DEBUG mode readback of the center row, see write_debug in frag.wgsl
+  let debug_slots = arrayLength(&debug_arr2);
+  let debug_stride = max(dims.x / debug_slots, 1u);
+  if (tx_coord.y == dims.y / 2u && tx_coord.x % debug_stride == 0u
+      && tx_coord.x / debug_stride < debug_slots) {
+    debug_arr2[tx_coord.x / debug_stride] = vec4(terrain, 1.0);
+  }
 }
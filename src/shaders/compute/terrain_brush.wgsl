@@ -0,0 +1,35 @@
+struct BrushParams {
+  center: vec2<f32>,
+  origin: vec2<u32>,
+  radius: f32,
+  strength: f32,
+  direction: f32,
+}
+
+@group(1) @binding(1) var<uniform> brush: BrushParams;
+
+@group(2) @binding(0) var terrain_tex: texture_storage_2d<rgba32float, read_write>;
+
+// Dispatched over the brush's bounding square only, starting at brush.origin.
+// Each invocation reads and writes its own texel, so there is no overlap
+// between invocations to synchronise.
+@compute
+@workgroup_size(8, 8, 1)
+fn apply_brush(@builtin(global_invocation_id) id: vec3<u32>) {
+  let tx_coord: vec2<u32> = id.xy + brush.origin;
+  if (any(tx_coord >= textureDimensions(terrain_tex))) {
+    return;
+  }
+
+  let d = distance(vec2<f32>(tx_coord), brush.center);
+  if (d > brush.radius) {
+    return;
+  }
+
+  let falloff = 1.0 - smoothstep(0.0, brush.radius, d);
+  var tx = textureLoad(terrain_tex, tx_coord);
+  // Only the height is edited, the gradient in yz is left as generated
+  tx.x += brush.strength * brush.direction * falloff;
+
+  textureStore(terrain_tex, tx_coord, tx);
+}
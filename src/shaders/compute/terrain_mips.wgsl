@@ -0,0 +1,19 @@
+@group(0) @binding(0) var src: texture_2d<f32>;
+@group(0) @binding(1) var dst: texture_storage_2d<rgba32float, write>;
+
+// Box filters one terrain mip level into the next, src is the level above dst
+@compute
+@workgroup_size(8, 8, 1)
+fn downsample(@builtin(global_invocation_id) id: vec3<u32>) {
+  if (any(id.xy >= textureDimensions(dst))) {
+    return;
+  }
+
+  let p = vec2<i32>(id.xy) * 2;
+  let sum = textureLoad(src, p, 0)
+    + textureLoad(src, p + vec2(1, 0), 0)
+    + textureLoad(src, p + vec2(0, 1), 0)
+    + textureLoad(src, p + vec2(1, 1), 0);
+
+  textureStore(dst, id.xy, sum * 0.25);
+}
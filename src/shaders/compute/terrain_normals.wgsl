@@ -0,0 +1,25 @@
+@group(2) @binding(0) var terrain_tex: texture_storage_2d<rgba32float, read_write>;
+@group(2) @binding(2) var terrain_normal_tex: texture_storage_2d<rgba32float, write>;
+
+// Height at a texel, clamped to the edges
+fn height(p: vec2<i32>) -> f32 {
+  let max_p = vec2<i32>(textureDimensions(terrain_tex)) - 1;
+  return textureLoad(terrain_tex, clamp(p, vec2(0), max_p)).x;
+}
+
+// Surface normals from central differences of the height, with the slope measured
+// per unit of texture uv
+@compute
+@workgroup_size(32, 32, 1)
+fn terrain_normals(@builtin(global_invocation_id) id: vec3<u32>) {
+  let dims = textureDimensions(terrain_tex);
+  if (any(id.xy >= dims)) {
+    return;
+  }
+
+  let p = vec2<i32>(id.xy);
+  let dx = (height(p + vec2(1, 0)) - height(p - vec2(1, 0))) * 0.5 * f32(dims.x);
+  let dy = (height(p + vec2(0, 1)) - height(p - vec2(0, 1))) * 0.5 * f32(dims.y);
+
+  textureStore(terrain_normal_tex, id.xy, vec4(normalize(vec3(-dx, 1.0, -dy)), 0.0));
+}
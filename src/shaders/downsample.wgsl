@@ -0,0 +1,26 @@
+// Filters the supersampled render target down into the frame. At 2x each frame pixel's
+// center falls on the corner shared by four target texels, so the linear sampler
+// averages exactly those four
+
+struct VertexOutput {
+  @builtin(position) pos: vec4<f32>,
+  @location(0) uv: vec2<f32>,
+};
+
+@group(0) @binding(0) var ss_tex: texture_2d<f32>;
+@group(0) @binding(1) var ss_sampler: sampler;
+
+// One triangle covering the screen, no vertex buffer
+@vertex
+fn vs_main(@builtin(vertex_index) i: u32) -> VertexOutput {
+  let uv = vec2<f32>(f32((i << 1u) & 2u), f32(i & 2u));
+  var out: VertexOutput;
+  out.pos = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+  out.uv = uv;
+  return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+  return textureSampleLevel(ss_tex, ss_sampler, in.uv, 0.0);
+}
@@ -1,11 +1,6 @@
 const PI: f32 = 3.14159265358979323846;
 const MAX_F32: f32 = 0x1.fffffep+127f;
 
-const SCREEN_WIDTH: f32 = 1376.0;
-const SCREEN_HEIGHT: f32 = 768.0;
-const ASPECT: f32 = SCREEN_WIDTH / SCREEN_HEIGHT;
-const INV_ASPECT: f32 = SCREEN_HEIGHT / SCREEN_WIDTH;
-
 const PLANT_REFLECTIVITY: f32 = 1.0;
 const SAND_REFLECTIVITY: f32 = 1.0;
 const ROCK_REFLECTIVITY: f32 = 1.0;
@@ -15,8 +10,22 @@ const PLANT_CLR: vec3<f32> = vec3(1.0);
 const SAND_CLR: vec3<f32> = vec3(1.0);
 const ROCK_CLR: vec3<f32> = vec3(1.0);
 const WATER_CLR: vec3<f32> = vec3(1.0);
-
+const CLIP_CLR: vec3<f32> = vec3(1.0, 0.35, 0.2);
+const PRECISION_LOSS_CLR: vec3<f32> = vec3(1.0, 0.0, 1.0);
+// Pixels
+const STEP_LEGEND_HEIGHT: f32 = 12.0;
 const MAX_STEPS: i32 = 2500;
+// World units moved per unit of view x_shift/y_shift
+const PAN_SCALE: f32 = 100.0;
+// Orthographic view width in world units per unit of screen uv
+const ORTHO_SCALE: f32 = 200.0;
+// Side of the world square the terrain texture covers, centred on the origin
+const TERRAIN_WORLD_SIZE: f32 = 400.0;
+// The soft shadow march was tuned against the old point light at (40, 100, -300),
+// stepping along that unnormalized vector, so the sun direction is scaled to its length
+const SHADOW_RAY_SCALE: f32 = 318.75;
+// TextureViewParams.debug_pixel while none is picked, DEBUG_PIXEL_CENTER on the CPU
+const DEBUG_PIXEL_CENTER: u32 = 0xffffffffu;
 
 const m2: mat2x2<f32> = mat2x2(
   0.80, 0.60,
@@ -35,6 +44,7 @@ struct RayParams {
   epsilon: f32,
   max_dist: f32,
   max_steps: f32,
+  epsilon_scale: f32,
 }
 struct ViewParams {
   x_shift: f32,
@@ -42,32 +52,124 @@ struct ViewParams {
   zoom: f32,
   x_rot: f32,
   y_rot: f32,
+  // Applied on the CPU, tu.time already runs at this rate
   time_modifier: f32,
   fov: f32,
+  // cam_pos, an array<f32, 3> would need a 16 byte stride in the uniform address space
+  cam_x: f32,
+  cam_y: f32,
+  cam_z: f32,
+  z_rot: f32,
+  ortho: f32,
+}
+struct SdfParams {
+  mode: u32,
+  mandelbulb_power: f32,
+  gyroid_scale: f32,
+  sphere_spacing: f32,
+}
+struct ClipParams {
+  enabled: u32,
+  height: f32,
+}
+struct PrecisionParams {
+  render_half: u32,
+  show_loss: u32,
+  loss_threshold: f32,
+}
+struct TextureViewParams {
+  enabled: u32,
+  colormap: u32,
+  step_heatmap: u32,
+  debug_pixel: vec2<u32>,
+}
+struct ScreenParams {
+  resolution: vec2<f32>,
+  aspect: f32,
+  inv_aspect: f32,
+}
+struct LightParams {
+  sun_dir: vec3<f32>,
+  ambient: f32,
+  sun_color: vec3<f32>,
+  _padding: f32,
+}
+struct FogParams {
+  color: vec3<f32>,
+  density: f32,
+  start: f32,
+}
+struct SkyParams {
+  horizon_color: vec3<f32>,
+  horizon_blend: f32,
+  zenith_color: vec3<f32>,
+}
+struct OverlayParams {
+  guide_color: vec4<f32>,
+  show_guides: u32,
+  guide_opacity: f32,
+}
+// Everything applied to the marched color, one uniform so the fragment stage stays
+// within the default uniform buffer limit. Each member starts on a 16 byte boundary.
+// ToneParams, tone_map and encode_output are in tone.wgsl, appended when loading
+struct ShadingParams {
+  light: LightParams,
+  fog: FogParams,
+  sky: SkyParams,
+  tone: ToneParams,
+  overlay: OverlayParams,
 }
 
 // GROUPS AND BINDINGS
 @group(0) @binding(0) var<uniform> tu: TimeUniform;
 
-@group(1) @binding(0) var<storage, read_write> rp: RayParams;
-@group(1) @binding(1) var<storage, read_write> vp: ViewParams;
-@group(1) @binding(7) var<storage, read_write> debug_arr1: array<vec4<f32>>;
-@group(1) @binding(8) var<storage, read_write> debug_arr2: array<vec4<f32>>;
-@group(1) @binding(9) var<storage, read_write> debug: vec4<f32>;
+@group(1) @binding(0) var<uniform> rp: RayParams;
+@group(1) @binding(1) var<uniform> vp: ViewParams;
+@group(1) @binding(2) var<uniform> sh: ShadingParams;
+@group(1) @binding(3) var<uniform> sp: SdfParams;
+@group(1) @binding(4) var<uniform> cp: ClipParams;
+@group(1) @binding(5) var<uniform> pp: PrecisionParams;
+@group(1) @binding(6) var<uniform> tvp: TextureViewParams;
+@group(1) @binding(7) var<uniform> scr: ScreenParams;
 
 @group(2) @binding(0) var terrain_tex: texture_2d<f32>;
 @group(2) @binding(1) var terrain_sampler: sampler;
+// Full precision terrain, the same texture as terrain_tex unless rendering at half precision
+@group(2) @binding(2) var terrain_full_tex: texture_2d<f32>;
+// Precomputed from the full precision terrain by terrain_normals.wgsl
+@group(2) @binding(3) var terrain_normal_tex: texture_2d<f32>;
+
+// Read back by the DEBUG mode dumps, see write_debug. Shared with generate_terrain.wgsl
+@group(3) @binding(0) var<storage, read_write> debug_arr1: array<vec4<f32>>;
+@group(3) @binding(1) var<storage, read_write> debug_arr2: array<vec4<f32>>;
+// Sized by GENERIC_DEBUG_LEN on the CPU
+@group(3) @binding(2) var<storage, read_write> debug: array<vec4<f32>, 3>;
 
 // ASPECT RATIO
 fn scale_aspect(fc: vec2<f32>) -> vec2<f32> {
   // Scale from screen dimensions to 0.0 --> 1.0
-  var uv: vec2<f32> = ((2.0 * fc) / vec2(SCREEN_WIDTH, SCREEN_HEIGHT)) - 1.0;
-  uv.y = -uv.y * INV_ASPECT;
+  var uv: vec2<f32> = ((2.0 * fc) / scr.resolution) - 1.0;
+  uv.y = -uv.y * scr.inv_aspect;
   return uv;
 }
 
+// World x/z -> terrain texture uv, 0.0 -> 1.0 across TERRAIN_WORLD_SIZE
+fn terrain_uv(pos: vec3<f32>) -> vec2<f32> {
+  return pos.xz / TERRAIN_WORLD_SIZE + 0.5;
+}
+
 // LIGHTING
 fn get_normal(pos: vec3<f32>, uv: vec2<f32>) -> vec3<f32> {
+  // The terrain (sdf mode 0) samples its normals under the hit point, except on the
+  // clip plane's cut face
+  if (sp.mode == 0u && !on_clip_plane(pos)) {
+    if (pp.render_half != 0u) {
+      return half_terrain_normal(terrain_uv(pos));
+    }
+    let n = textureSampleLevel(terrain_normal_tex, terrain_sampler, terrain_uv(pos), 0.0);
+    return normalize(n.xyz);
+  }
+
   let e = vec2(rp.epsilon, 0.0);
   let n = vec3(map(pos, uv).dist) - 
   vec3(
@@ -79,6 +181,19 @@ fn get_normal(pos: vec3<f32>, uv: vec2<f32>) -> vec3<f32> {
   return normalize(n);
 }
 
+// terrain_normals.wgsl's central differences, taken from terrain_tex so the half precision
+// render shades the heights it marched rather than the full precision normal texture
+fn half_terrain_normal(uv: vec2<f32>) -> vec3<f32> {
+  let dims = vec2<f32>(textureDimensions(terrain_tex));
+  let tx = vec2(1.0 / dims.x, 0.0);
+  let ty = vec2(0.0, 1.0 / dims.y);
+  let hx = textureSampleLevel(terrain_tex, terrain_sampler, uv + tx, 0.0).x
+    - textureSampleLevel(terrain_tex, terrain_sampler, uv - tx, 0.0).x;
+  let hy = textureSampleLevel(terrain_tex, terrain_sampler, uv + ty, 0.0).x
+    - textureSampleLevel(terrain_tex, terrain_sampler, uv - ty, 0.0).x;
+  return normalize(vec3(-hx * 0.5 * dims.x, 1.0, -hy * 0.5 * dims.y));
+}
+
 fn get_ambient_occlusion(pos: vec3<f32>, normal: vec3<f32>, uv: vec2<f32>) -> f32 {
   var occ = 0.0;
   var weight = 0.4;
@@ -93,13 +208,13 @@ fn get_ambient_occlusion(pos: vec3<f32>, normal: vec3<f32>, uv: vec2<f32>) -> f3
   return 1.0 - clamp(0.6 * occ, 0.0, 1.0);
 }
 
-fn get_soft_shadow(pos: vec3<f32>, light_pos: vec3<f32>, uv: vec2<f32>) -> f32 {
+fn get_soft_shadow(pos: vec3<f32>, light_ray: vec3<f32>, uv: vec2<f32>) -> f32 {
   var res = 1.0;
   var dist = 0.01;
   let light_size = 100.0;
 
   for (var i: i32 = 0; i < 8; i++) {
-    let hit = map(pos + light_pos * dist, uv).dist;
+    let hit = map(pos + light_ray * dist, uv).dist;
     res = min(res, hit / (dist * light_size));
     if (hit < rp.epsilon) { break; }
     dist += hit;
@@ -122,10 +237,8 @@ fn get_light(
   uv: vec2<f32>,
   material: MaterialEnum,
 ) -> vec3<f32> {
-  var light_pos: vec3<f32> = vec3(40.0, 100.0, -300.0);
-  let color: vec3<f32> = vec3(1.0);
-
-  let l: vec3<f32> = normalize(light_pos - pos);
+  // Directional sun, sh.light.sun_dir points towards it
+  let l: vec3<f32> = normalize(sh.light.sun_dir);
   let normal: vec3<f32> = get_normal(pos, uv);
 
   let v: vec3<f32> = -rd;
@@ -133,7 +246,6 @@ fn get_light(
 
   let diff: f32 = 0.70 * max(dot(l, normal), 0.0);
   let specular: f32 = 0.30 * pow(clamp(dot(r, v), 0.0, 1.0), 10.0);
-  let ambient: f32 = 0.05; 
 
   var reflect: f32 = 0.0;
   reflect += material.water*WATER_REFLECTIVITY;
@@ -144,10 +256,23 @@ fn get_light(
   let spec_ref = specular*reflect;
   let diff_ref = diff*reflect;
 
-  let shadow: f32 = get_soft_shadow(pos, light_pos, uv);
+  let shadow: f32 = get_soft_shadow(pos, l * SHADOW_RAY_SCALE, uv);
   let occ: f32 = get_ambient_occlusion(pos, normal, uv);
 
-  return (ambient * occ + (spec_ref * occ + diff_ref) * shadow) * color;
+  return sh.light.ambient * occ + (spec_ref * occ + diff_ref) * shadow * sh.light.sun_color;
+}
+
+// Exponential fog over the part of the march past sh.fog.start * max_dist,
+// misses get the full amount
+fn apply_fog(col: vec3<f32>, dist: f32) -> vec3<f32> {
+  let d = max(min(dist, rp.max_dist) / rp.max_dist - sh.fog.start, 0.0);
+  let f = 1.0 - exp(-sh.fog.density * d);
+  return mix(col, sh.fog.color, f);
+}
+
+fn sky_color(rd: vec3<f32>) -> vec3<f32> {
+  let t = smoothstep(0.0, max(sh.sky.horizon_blend, 0.001), rd.y);
+  return mix(sh.sky.horizon_color, sh.sky.zenith_color, t);
 }
 
 // CAMERA
@@ -166,6 +291,13 @@ fn get_cam(ro: vec3<f32>, look_at: vec3<f32>) -> mat4x4<f32> {
   );
 }
 
+// Roll the camera's right and up around its forward axis
+fn roll_cam(cam: mat4x4<f32>, angle: f32) -> mat4x4<f32> {
+  let c = cos(angle);
+  let s = sin(angle);
+  return mat4x4(c * cam[0] + s * cam[1], c * cam[1] - s * cam[0], cam[2], cam[3]);
+}
+
 fn rotate3d(v: vec3<f32>, angleX: f32, angleY: f32) -> vec3<f32> {
  let saX = sin(angleX);
  let caX = cos(angleX);
@@ -212,7 +344,70 @@ fn planeSDF(p: vec3<f32>, n: vec3<f32>, h: f32) -> f32 {
     return dot(p, n) + h;
 }
 
+// Built in SDFs, sized to sit around the origin the camera orbits
+fn mandelbulbSDF(pos: vec3<f32>, power: f32) -> f32 {
+  let scale = 60.0;
+  let p = pos / scale;
+  var z = p;
+  var dr = 1.0;
+  var r = length(z);
+
+  for (var i: i32 = 0; i < 8; i++) {
+    r = length(z);
+    if (r > 2.0 || r < 1e-6) { break; }
+    let theta = acos(clamp(z.y / r, -1.0, 1.0)) * power;
+    let phi = atan2(z.z, z.x) * power;
+    dr = pow(r, power - 1.0) * power * dr + 1.0;
+    z = pow(r, power) * vec3(sin(theta) * cos(phi), cos(theta), sin(theta) * sin(phi)) + p;
+  }
+
+  return 0.5 * log(max(r, 1e-6)) * r / dr * scale;
+}
+
+fn gyroidSDF(pos: vec3<f32>, scale: f32) -> f32 {
+  // Not an exact distance, so step conservatively, bounded by a sphere
+  let g = abs(dot(sin(pos * scale), cos(pos.zxy * scale))) / scale - 0.5;
+  return max(0.5 * g, length(pos) - 80.0);
+}
+
+fn sphereFieldSDF(pos: vec3<f32>, spacing: f32) -> f32 {
+  // Limited repetition, 5x5x5 spheres
+  let cell = clamp(round(pos / spacing), vec3(-2.0), vec3(2.0));
+  let q = pos - spacing * cell;
+  return length(q) - spacing * 0.25;
+}
+
+// Replaced at runtime by the snippet passed with --sdf, see app/custom_sdf.rs
+// CUSTOM SDF BEGIN
+fn custom_sdf(p: vec3<f32>) -> f32 {
+  return length(p) - 50.0;
+}
+// CUSTOM SDF END
+
 fn map(pos: vec3<f32>, uv: vec2<f32>) -> Terrain {
+  var t = scene(pos, uv);
+  if (cp.enabled != 0u) {
+    // Intersect with the half space below the clip plane
+    t.dist = max(t.dist, pos.y - cp.height);
+  }
+  return t;
+}
+
+fn on_clip_plane(pos: vec3<f32>) -> bool {
+  return cp.enabled != 0u && abs(pos.y - cp.height) < rp.epsilon * 2.0;
+}
+
+fn scene(pos: vec3<f32>, uv: vec2<f32>) -> Terrain {
+  switch (sp.mode) {
+    case 1u: { return Terrain(vec2(0.0), mandelbulbSDF(pos, sp.mandelbulb_power), 0.0); }
+    case 2u: { return Terrain(vec2(0.0), gyroidSDF(pos, sp.gyroid_scale), 0.0); }
+    case 3u: { return Terrain(vec2(0.0), sphereFieldSDF(pos, sp.sphere_spacing), 0.0); }
+    case 4u: { return Terrain(vec2(0.0), custom_sdf(pos), 0.0); }
+    default: { return terrainSDF(pos, uv); }
+  }
+}
+
+fn terrainSDF(pos: vec3<f32>, uv: vec2<f32>) -> Terrain {
   var d1 = planeSDF(pos, vec3(0.0, 1.0, 0.0), 1.0);
   let tx = textureSample(terrain_tex, terrain_sampler, uv);
   //var d0 = d1;
@@ -232,6 +427,7 @@ struct TerrainPos {
   dist: f32,
   water_depth: f32,
   pos: vec3<f32>,
+  steps: i32,
 }
 
 fn ray_march(ro: vec3<f32>, rd: vec3<f32>, uv: vec2<f32>, look_at: vec3<f32>) -> TerrainPos {
@@ -239,9 +435,11 @@ fn ray_march(ro: vec3<f32>, rd: vec3<f32>, uv: vec2<f32>, look_at: vec3<f32>) ->
   var water_depth = 0.0;
   var grad = vec2(0.0);
   var p = vec3(0.0);
+  var steps = 0;
   let max_steps = i32(rp.max_steps);
 
   for (var i: i32 = 0; i < max_steps; i++) {
+    steps = i + 1;
     let pos = ro + dist * rd;
     let t = map(pos, uv);
     let hit = t.dist;
@@ -249,7 +447,9 @@ fn ray_march(ro: vec3<f32>, rd: vec3<f32>, uv: vec2<f32>, look_at: vec3<f32>) ->
     grad = t.grad;
     p = pos;
 
-    if (abs(hit) < rp.epsilon) {
+    // Far hits don't need the precision of near ones, growing the threshold
+    // with distance saves steps on rays that skim the terrain
+    if (abs(hit) < rp.epsilon + rp.epsilon_scale * dist) {
       break;
     }
     dist += hit;
@@ -259,18 +459,165 @@ fn ray_march(ro: vec3<f32>, rd: vec3<f32>, uv: vec2<f32>, look_at: vec3<f32>) ->
     }
   }
 
-  return TerrainPos(grad, dist, water_depth, p);
+  return TerrainPos(grad, dist, water_depth, p, steps);
+}
+
+// OVERLAY
+fn guides(fc: vec2<f32>) -> f32 {
+  let res = scr.resolution;
+  let thirds = res / 3.0;
+  let center = res * 0.5;
+
+  // Rule-of-thirds lines, 1px wide
+  let tx = min(abs(fc.x - thirds.x), abs(fc.x - 2.0 * thirds.x));
+  let ty = min(abs(fc.y - thirds.y), abs(fc.y - 2.0 * thirds.y));
+  var line = f32(min(tx, ty) < 0.5);
+
+  // Center crosshair, 20px arms
+  let d = abs(fc - center);
+  let cross = (d.x < 1.0 && d.y < 10.0) || (d.y < 1.0 && d.x < 10.0);
+  line = max(line, f32(cross));
+
+  return line;
+}
+
+// Largest difference between the rendered and full precision terrain at the terrain uv
+fn precision_loss(uv: vec2<f32>) -> f32 {
+  let rendered = textureSampleLevel(terrain_tex, terrain_sampler, uv, 0.0);
+  let full = textureSampleLevel(terrain_full_tex, terrain_sampler, uv, 0.0);
+  let d = abs(rendered - full);
+  return max(max(d.x, d.y), d.z);
+}
+
+// TERRAIN TEXTURE VIEW
+fn terrain_colormap(h: f32) -> vec3<f32> {
+  let deep = vec3(0.05, 0.15, 0.4);
+  let shallow = vec3(0.2, 0.5, 0.8);
+  let grass = vec3(0.25, 0.55, 0.2);
+  let rock = vec3(0.45, 0.35, 0.25);
+  let snow = vec3(0.95);
+
+  if (h < 0.3) { return mix(deep, shallow, h / 0.3); }
+  if (h < 0.5) { return mix(shallow, grass, (h - 0.3) / 0.2); }
+  if (h < 0.8) { return mix(grass, rock, (h - 0.5) / 0.3); }
+  return mix(rock, snow, (h - 0.8) / 0.2);
+}
+
+// The raw heightfield stretched over the screen, bypassing the raymarch
+fn terrain_texture_view(fc: vec2<f32>) -> vec3<f32> {
+  let suv = fc / scr.resolution;
+  let height = textureSampleLevel(terrain_tex, terrain_sampler, suv, 0.0).x;
+  // fbm heights are roughly -1.0 -> 1.0
+  let h = clamp(height * 0.5 + 0.5, 0.0, 1.0);
+
+  if (tvp.colormap == 1u) {
+    return terrain_colormap(h);
+  }
+  return vec3(h);
+}
+
+// STEP HEATMAP
+// Blue for a single step through cyan, green and yellow to red at max_steps
+fn step_heatmap(t: f32) -> vec3<f32> {
+  let h = clamp(t, 0.0, 1.0);
+  let blue = vec3(0.0, 0.0, 0.6);
+  let cyan = vec3(0.0, 0.8, 1.0);
+  let green = vec3(0.1, 0.9, 0.1);
+  let yellow = vec3(1.0, 0.9, 0.0);
+  let red = vec3(1.0, 0.0, 0.0);
+
+  if (h < 0.25) { return mix(blue, cyan, h / 0.25); }
+  if (h < 0.5) { return mix(cyan, green, (h - 0.25) / 0.25); }
+  if (h < 0.75) { return mix(green, yellow, (h - 0.5) / 0.25); }
+  return mix(yellow, red, (h - 0.75) / 0.25);
+}
+
+// Color scale along the bottom of the screen, 0 steps on the left to max_steps on the right
+fn step_legend(fc: vec2<f32>) -> bool {
+  return fc.y > scr.resolution.y - STEP_LEGEND_HEIGHT;
+}
+
+// DEBUG READBACK
+//   debug[0]       the debug pixel: xyz ray origin, w hit distance
+//   debug[1]       the debug pixel: xyz the point the march stopped at, w steps taken
+//   debug[2]       the debug pixel: rgb the final color, before the output encoding
+//   debug_arr1[i]  pixel i * width / slots of the center row: xyz the point the
+//                  march stopped at, w steps taken. Slot slots / 2 is the center pixel
+//   debug_arr2[i]  written by generate_terrain.wgsl, texel i * width / slots of the terrain's
+//                  center row as stored: height, gradient x, gradient y, 1.0
+// The debug pixel is picked with the mouse in DEBUG mode, the screen center until then.
+// Only the center row writes the array, and only its first pixel in each slot, so every
+// slot has a single writer. Narrower windows leave the slots past their width untouched
+fn write_debug(fc: vec2<f32>, ro: vec3<f32>, terrain: TerrainPos) {
+  let pixel = vec2<u32>(fc);
+  if (all(pixel == debug_pixel())) {
+    debug[0] = vec4(ro, terrain.dist);
+    debug[1] = vec4(terrain.pos, f32(terrain.steps));
+  }
+
+  let center = vec2<u32>(scr.resolution * 0.5);
+  if (pixel.y != center.y) {
+    return;
+  }
+
+  // Sized by DEBUG_ARRAY_LEN on the CPU
+  let slots = arrayLength(&debug_arr1);
+  let width = u32(scr.resolution.x);
+  let slot = pixel.x * slots / width;
+  if (pixel.x == (slot * width + slots - 1u) / slots) {
+    debug_arr1[slot] = vec4(terrain.pos, f32(terrain.steps));
+  }
+}
+
+fn debug_pixel() -> vec2<u32> {
+  if (tvp.debug_pixel.x == DEBUG_PIXEL_CENTER) {
+    return vec2<u32>(scr.resolution * 0.5);
+  }
+  return tvp.debug_pixel;
+}
+
+fn write_debug_color(fc: vec2<f32>, color: vec3<f32>) {
+  if (all(vec2<u32>(fc) == debug_pixel())) {
+    debug[2] = vec4(color, 1.0);
+  }
 }
 
 // RENDERING
-fn render(uv: vec2<f32>) -> vec3<f32> {
+// A pixel's color, and where its ray stopped when it hit something
+struct Rendered {
+  color: vec3<f32>,
+  pos: vec3<f32>,
+  hit: bool,
+}
+
+// The camera setup here is mirrored on the CPU in app/camera.rs, keep them in sync
+fn render(uv: vec2<f32>, fc: vec2<f32>) -> Rendered {
   var ro: vec3<f32> = vec3(0.0, 20.0, -200.0);
   ro = rotate3d(ro, vp.y_rot, vp.x_rot);
 
-  let look_at: vec3<f32> = vec3(0.0, 0.0, 0.0);
-
-  var rd: vec3<f32> = (get_cam(ro, look_at) * normalize(vec4(uv * vp.fov, 1.0, 0.0))).xyz;
+  var look_at: vec3<f32> = vec3(0.0, 0.0, 0.0);
+
+  // Pan along the camera's right/up basis so panning stays screen relative after rotation
+  let cam = roll_cam(get_cam(ro, look_at), vp.z_rot);
+  let pan = (cam[0].xyz * vp.x_shift + cam[1].xyz * vp.y_shift) * PAN_SCALE;
+  // Fly camera offset, in world space
+  let fly = vec3(vp.cam_x, vp.cam_y, vp.cam_z);
+  ro += pan + fly;
+  look_at += pan + fly;
+
+  let view = roll_cam(get_cam(ro, look_at), vp.z_rot);
+  var rd: vec3<f32> = (view * normalize(vec4(uv * vp.fov, 1.0, 0.0))).xyz;
+  // Parallel rays spread over the image plane instead of fanning out from ro
+  if (vp.ortho > 0.5) {
+    ro += (view[0].xyz * uv.x + view[1].xyz * uv.y) * ORTHO_SCALE;
+    rd = view[2].xyz;
+  }
   let terrain = ray_march(ro, rd, uv, look_at);
+  write_debug(fc, ro, terrain);
+  let hit = terrain.dist < rp.max_dist;
+  if (tvp.step_heatmap != 0u) {
+    return Rendered(step_heatmap(f32(terrain.steps) / rp.max_steps), terrain.pos, hit);
+  }
   let dist: f32 = terrain.dist;
   let grad = terrain.grad;
 
@@ -278,29 +625,64 @@ fn render(uv: vec2<f32>) -> vec3<f32> {
   var col: vec3<f32> = vec3(0.0);
   var material = MaterialEnum(0.0, 0.0, 0.0, 0.0);
 
-  if (dist < rp.max_dist) {
-    //let dist_origin: f32 = length(cam_pos);
-    material.rock = 1.0;
+  if (!hit) {
+    return Rendered(sky_color(rd), terrain.pos, false);
+  }
+
+  //let dist_origin: f32 = length(cam_pos);
+  material.rock = 1.0;
+  if (on_clip_plane(cam_pos)) {
+    // Cross section
+    col += get_light(cam_pos, rd, uv, material)*CLIP_CLR;
+  } else {
     col += get_light(cam_pos, rd, uv, material)*ROCK_CLR;
-  } 
+  }
 
-  return col;
+  return Rendered(apply_fog(col, dist), terrain.pos, true);
 }
 
 @fragment
 fn main(@builtin(position) FragCoord: vec4<f32>) -> @location(0) vec4<f32> {
-  let t: f32 = tu.time * vp.time_modifier;
   var uv: vec2<f32> = scale_aspect(FragCoord.xy); // Scale to -1.0 -> 1.0 + fix aspect ratio
   let uv0 = uv;
-  uv.x += vp.x_shift * vp.zoom;
-  uv.y += vp.y_shift * vp.zoom;
   uv /= vp.zoom;
 
   var color = vec3(0.0);
 // -----------------------------------------------------------------------------------------------
 
-  color = render(uv);
+  if (tvp.enabled != 0u) {
+    return encode_output(terrain_texture_view(FragCoord.xy), sh.tone);
+  }
+
+  // The debug views skip the tone curve so their colors map directly to values
+  if (tvp.step_heatmap != 0u && step_legend(FragCoord.xy)) {
+    return encode_output(step_heatmap(FragCoord.x / scr.resolution.x), sh.tone);
+  }
+  let rendered = render(uv, FragCoord.xy);
+  // Summed over the shutter for motion blur, the resolve pass applies the curve once
+  // the sub-frames are averaged. The overlays are left out
+  if (sh.tone.linear_output != 0u) {
+    return vec4(rendered.color, 1.0);
+  }
+  if (tvp.step_heatmap != 0u) {
+    color = rendered.color;
+  } else {
+    color = tone_map(rendered.color, sh.tone);
+  }
+
+  // Compared at the texel the terrain was sampled from under this pixel
+  let on_terrain = rendered.hit && sp.mode == 0u;
+  if (pp.show_loss != 0u && on_terrain
+      && precision_loss(terrain_uv(rendered.pos)) > pp.loss_threshold) {
+    color = mix(color, PRECISION_LOSS_CLR, 0.5);
+  }
+
+  if (sh.overlay.show_guides != 0u) {
+    let g = guides(FragCoord.xy) * sh.overlay.guide_opacity * sh.overlay.guide_color.a;
+    color = mix(color, sh.overlay.guide_color.rgb, g);
+  }
 
 // -----------------------------------------------------------------------------------------------
-  return vec4<f32>(color, 1.0);
+  write_debug_color(FragCoord.xy, color);
+  return encode_output(color, sh.tone);
 }
@@ -0,0 +1,28 @@
+// Resolves the motion blur sub-frames, summed in linear light at 1 / samples each,
+// through the tone curve into the frame. Sampled like downsample.wgsl so a
+// supersampled accumulation target is filtered down the same way
+
+struct VertexOutput {
+  @builtin(position) pos: vec4<f32>,
+  @location(0) uv: vec2<f32>,
+};
+
+@group(0) @binding(0) var accum_tex: texture_2d<f32>;
+@group(0) @binding(1) var accum_sampler: sampler;
+@group(0) @binding(2) var<uniform> tone: ToneParams;
+
+// One triangle covering the screen, no vertex buffer
+@vertex
+fn vs_main(@builtin(vertex_index) i: u32) -> VertexOutput {
+  let uv = vec2<f32>(f32((i << 1u) & 2u), f32(i & 2u));
+  var out: VertexOutput;
+  out.pos = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+  out.uv = uv;
+  return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+  let col = textureSampleLevel(accum_tex, accum_sampler, in.uv, 0.0).rgb;
+  return encode_output(tone_map(col, tone), tone);
+}
@@ -0,0 +1,42 @@
+
+// TONE MAPPING
+// Appended to frag.wgsl and motion_blur.wgsl, so the motion blur resolve applies the
+// same curve as the render pass
+struct ToneParams {
+  exposure: f32,
+  tonemap: u32,
+  encode_srgb: u32,
+  // Set while motion blur sub-frames are rendered, frag.wgsl then writes the linear
+  // color for the resolve pass to tone map
+  linear_output: u32,
+}
+
+// Shading happens in linear light and may exceed 1.0, the curve brings it into
+// display range before the overlays are drawn on top
+fn tone_map(col: vec3<f32>, tone: ToneParams) -> vec3<f32> {
+  let x = max(col * tone.exposure, vec3(0.0));
+  switch tone.tonemap {
+    case 1u: {
+      return x / (1.0 + x);
+    }
+    case 2u: {
+      // Narkowicz's fit of the ACES filmic curve
+      return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), vec3(0.0), vec3(1.0));
+    }
+    default: {
+      return x;
+    }
+  }
+}
+
+// An sRGB color target encodes on write, so this only runs for linear targets.
+// Doing both would apply the gamma curve twice
+fn encode_output(col: vec3<f32>, tone: ToneParams) -> vec4<f32> {
+  if (tone.encode_srgb == 0u) {
+    return vec4(col, 1.0);
+  }
+  let c = clamp(col, vec3(0.0), vec3(1.0));
+  let lo = c * 12.92;
+  let hi = 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055;
+  return vec4(select(hi, lo, c <= vec3(0.0031308)), 1.0);
+}
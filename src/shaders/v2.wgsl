@@ -1,4 +1,5 @@
 @vertex
 fn main(@location(0) pos: vec2<f32>) -> @builtin(position) vec4<f32> {
-  return vec4<f32>(pos, 0.0, 1.0);
+  // Far plane, so anything rasterized on top passes the depth test
+  return vec4<f32>(pos, 1.0, 1.0);
 }
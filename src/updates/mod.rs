@@ -1 +1,2 @@
 pub(crate) mod param_updates;
+pub(crate) mod readback;
@@ -1,37 +1,158 @@
 use crate::{
     app::state::State,
-    collections::structs::{RayParams, ViewParams},
+    collections::{
+        consts::{DEBUG_ARRAY_SIZE, GENERIC_DEBUG_SIZE, MIN_MAX_STEPS},
+        structs::{RayParams, ShadingParams},
+    },
 };
 
+// The view and ray params are written by the controls every frame a key is held,
+// so skip the write when they match what the GPU already has
 pub(crate) fn update_view_params_buffer(state: &mut State) {
-    let new_view_params = ViewParams {
-        x_shift: state.params.view_params.x_shift,
-        y_shift: state.params.view_params.y_shift,
-        zoom: state.params.view_params.zoom,
-        x_rot: state.params.view_params.x_rot,
-        y_rot: state.params.view_params.y_rot,
-        time_modifier: state.params.view_params.time_modifier,
-        fov_degrees: state.params.view_params.fov_degrees,
-    };
+    if state.params.view_params == state.uploaded_view_params {
+        return;
+    }
+    state.uploaded_view_params = state.params.view_params;
 
     state.queue.write_buffer(
         &state.buffers.view_params,
         0,
-        bytemuck::cast_slice(&[new_view_params]),
+        bytemuck::cast_slice(&[state.params.view_params]),
     );
 }
 
 pub(crate) fn update_ray_params_buffer(state: &mut State) {
-    let new_ray_params = RayParams {
-        epsilon: state.params.ray_params.epsilon,
-        max_dist: state.params.ray_params.max_dist,
-        max_steps: state.params.ray_params.max_steps,
-    };
+    state.params.ray_params = clamp_ray_params(state.params.ray_params);
+    if state.params.ray_params == state.uploaded_ray_params {
+        return;
+    }
+    state.uploaded_ray_params = state.params.ray_params;
 
     state.queue.write_buffer(
         &state.buffers.ray_params,
         0,
-        bytemuck::cast_slice(&[new_ray_params]),
+        bytemuck::cast_slice(&[state.params.ray_params]),
+    );
+}
+
+// A max_steps below 1 or a max_dist below epsilon renders a blank screen,
+// so values from anywhere other than the controls are held to the floors too
+fn clamp_ray_params(ray_params: RayParams) -> RayParams {
+    RayParams {
+        max_steps: at_least("ray_params.max_steps", ray_params.max_steps, MIN_MAX_STEPS),
+        max_dist: at_least(
+            "ray_params.max_dist",
+            ray_params.max_dist,
+            ray_params.epsilon,
+        ),
+        ..ray_params
+    }
+}
+
+fn at_least(name: &str, value: f32, min: f32) -> f32 {
+    if value >= min {
+        value
+    } else {
+        eprintln!("{} = {} is below {}, clamping", name, value, min);
+        min
+    }
+}
+
+pub(crate) fn update_terrain_params_buffer(state: &mut State) {
+    state.queue.write_buffer(
+        &state.buffers.terrain_params,
+        0,
+        bytemuck::cast_slice(&[state.params.terrain_params]),
+    );
+}
+
+// The overlay, light, fog, sky and tone params share one buffer, each is written at its
+// offset in ShadingParams
+pub(crate) fn update_overlay_params_buffer(state: &mut State) {
+    state.queue.write_buffer(
+        &state.buffers.shading_params,
+        std::mem::offset_of!(ShadingParams, overlay) as wgpu::BufferAddress,
+        bytemuck::cast_slice(&[state.params.overlay_params]),
+    );
+}
+
+pub(crate) fn update_sdf_params_buffer(state: &mut State) {
+    state.queue.write_buffer(
+        &state.buffers.sdf_params,
+        0,
+        bytemuck::cast_slice(&[state.params.sdf_params]),
+    );
+}
+
+pub(crate) fn update_clip_params_buffer(state: &mut State) {
+    state.queue.write_buffer(
+        &state.buffers.clip_params,
+        0,
+        bytemuck::cast_slice(&[state.params.clip_params]),
+    );
+}
+
+pub(crate) fn update_brush_params_buffer(state: &mut State) {
+    state.queue.write_buffer(
+        &state.buffers.brush_params,
+        0,
+        bytemuck::cast_slice(&[state.params.brush_params]),
+    );
+}
+
+pub(crate) fn update_precision_params_buffer(state: &mut State) {
+    state.queue.write_buffer(
+        &state.buffers.precision_params,
+        0,
+        bytemuck::cast_slice(&[state.params.precision_params]),
+    );
+}
+
+pub(crate) fn update_texture_view_params_buffer(state: &mut State) {
+    state.queue.write_buffer(
+        &state.buffers.texture_view_params,
+        0,
+        bytemuck::cast_slice(&[state.params.texture_view_params]),
+    );
+}
+
+pub(crate) fn update_screen_params_buffer(state: &mut State) {
+    state.queue.write_buffer(
+        &state.buffers.screen_params,
+        0,
+        bytemuck::cast_slice(&[state.params.screen_params]),
+    );
+}
+
+pub(crate) fn update_light_params_buffer(state: &mut State) {
+    state.queue.write_buffer(
+        &state.buffers.shading_params,
+        std::mem::offset_of!(ShadingParams, light) as wgpu::BufferAddress,
+        bytemuck::cast_slice(&[state.params.light_params]),
+    );
+}
+
+pub(crate) fn update_fog_params_buffer(state: &mut State) {
+    state.queue.write_buffer(
+        &state.buffers.shading_params,
+        std::mem::offset_of!(ShadingParams, fog) as wgpu::BufferAddress,
+        bytemuck::cast_slice(&[state.params.fog_params]),
+    );
+}
+
+pub(crate) fn update_sky_params_buffer(state: &mut State) {
+    state.queue.write_buffer(
+        &state.buffers.shading_params,
+        std::mem::offset_of!(ShadingParams, sky) as wgpu::BufferAddress,
+        bytemuck::cast_slice(&[state.params.sky_params]),
+    );
+}
+
+pub(crate) fn update_tone_params_buffer(state: &mut State) {
+    state.queue.write_buffer(
+        &state.buffers.shading_params,
+        std::mem::offset_of!(ShadingParams, tone) as wgpu::BufferAddress,
+        bytemuck::cast_slice(&[state.params.tone_params]),
     );
 }
 
@@ -47,7 +168,7 @@ pub(crate) fn update_cpu_read_buffers(state: &mut State) {
         0,
         &state.buffers.cpu_read_generic_debug,
         0,
-        (std::mem::size_of::<[f32; 4]>()) as wgpu::BufferAddress,
+        GENERIC_DEBUG_SIZE,
     );
 
     encoder.copy_buffer_to_buffer(
@@ -55,7 +176,7 @@ pub(crate) fn update_cpu_read_buffers(state: &mut State) {
         0,
         &state.buffers.cpu_read_debug_array1,
         0,
-        (std::mem::size_of::<[[f32; 4]; 512]>()) as wgpu::BufferAddress,
+        DEBUG_ARRAY_SIZE,
     );
 
     encoder.copy_buffer_to_buffer(
@@ -63,7 +184,7 @@ pub(crate) fn update_cpu_read_buffers(state: &mut State) {
         0,
         &state.buffers.cpu_read_debug_array2,
         0,
-        (std::mem::size_of::<[[f32; 4]; 512]>()) as wgpu::BufferAddress,
+        DEBUG_ARRAY_SIZE,
     );
 
     state.queue.submit(Some(encoder.finish()));
@@ -0,0 +1,70 @@
+use anyhow::Context;
+use futures::channel::oneshot;
+use futures::FutureExt;
+
+pub(crate) type MapReceiver = oneshot::Receiver<Result<(), wgpu::BufferAsyncError>>;
+
+// Requests a read mapping of the whole buffer, the receiver fires from the
+// map callback once the device is polled past the buffer's last use
+pub(crate) fn map_buffer(buffer: &wgpu::Buffer) -> MapReceiver {
+    let (tx, rx) = oneshot::channel();
+    buffer
+        .slice(..)
+        .map_async(wgpu::MapMode::Read, move |result| {
+            let _ = tx.send(result);
+        });
+    rx
+}
+
+// Copies a mapped buffer out and unmaps it
+pub(crate) fn take_mapped<T: bytemuck::Pod>(buffer: &wgpu::Buffer) -> anyhow::Result<Vec<T>> {
+    let data = {
+        let view = buffer.slice(..).get_mapped_range();
+        bytemuck::try_cast_slice::<u8, T>(&view)
+            .map(<[T]>::to_vec)
+            .map_err(|e| anyhow::anyhow!("Error casting gpu data: {:?}", e))
+    };
+    buffer.unmap();
+    data
+}
+
+// Reads the buffer's contents without blocking on the GPU. The map is requested on the
+// first poll, and every poll polls the device once so the map callback can fire. Natively
+// it asks to be polled again until then, so block_on spins rather than hangs, and the
+// render loop's once a frame poll picks it up a frame or two later. The browser resolves
+// maps itself, so there it waits to be woken instead of spinning
+pub(crate) async fn read_buffer<T: bytemuck::Pod>(
+    device: &wgpu::Device,
+    buffer: &wgpu::Buffer,
+) -> anyhow::Result<Vec<T>> {
+    let mut rx = map_buffer(buffer);
+    futures::future::poll_fn(|cx| {
+        device.poll(wgpu::Maintain::Poll);
+        let mapped = rx.poll_unpin(cx);
+        if mapped.is_pending() && cfg!(not(target_arch = "wasm32")) {
+            cx.waker().wake_by_ref();
+        }
+        mapped
+    })
+    .await
+    .context("Readback was cancelled")?
+    .context("Readback failed to map")?;
+    take_mapped(buffer)
+}
+
+// Blocks until the buffer is mapped, for the one-off captures and the selftest
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn map_readback(device: &wgpu::Device, buffer: &wgpu::Buffer) -> anyhow::Result<()> {
+    let rx = map_buffer(buffer);
+    device.poll(wgpu::Maintain::Wait);
+    futures::executor::block_on(rx)
+        .context("Readback was cancelled")?
+        .context("Readback failed to map")
+}
+
+// The browser can't wait on a map, so screenshots, recordings and terrain exports
+// report an error there
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn map_readback(_device: &wgpu::Device, _buffer: &wgpu::Buffer) -> anyhow::Result<()> {
+    anyhow::bail!("Blocking readbacks aren't available in the browser")
+}
@@ -0,0 +1,6 @@
+// std's Instant and SystemTime panic on wasm32-unknown-unknown, web-time backs them with
+// the browser's clock there. Files' modification times stay std::time::SystemTime
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use std::time::{Instant, SystemTime};
+#[cfg(target_arch = "wasm32")]
+pub(crate) use web_time::{Instant, SystemTime};
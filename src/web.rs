@@ -0,0 +1,57 @@
+use wasm_bindgen::JsCast;
+use winit::{
+    dpi::PhysicalSize, event_loop::EventLoop, platform::web::WindowBuilderExtWebSys,
+    window::WindowBuilder,
+};
+
+use crate::app::state::State;
+use crate::collections::consts::{
+    DEFAULT_TERRAIN_RESOLUTION, SCREEN_HEIGHT, SCREEN_WIDTH, WINDOW_TITLE,
+};
+use crate::init::init_functions::init_params;
+
+// Canvas provided by web/index.html, a new one is appended to the body if the page
+// doesn't have it
+const CANVAS_ID: &str = "water-lab";
+
+// main can't block in the browser, the adapter and device are awaited from a spawned
+// future and the event loop is started once they're ready
+pub(crate) fn start() {
+    wasm_bindgen_futures::spawn_local(async {
+        if let Err(e) = run().await {
+            web_sys::console::error_1(&format!("Error: {:#}", e).into());
+        }
+    });
+}
+
+async fn run() -> anyhow::Result<()> {
+    let canvas = web_sys::window()
+        .and_then(|window| window.document())
+        .and_then(|document| document.get_element_by_id(CANVAS_ID))
+        .and_then(|element| element.dyn_into::<web_sys::HtmlCanvasElement>().ok());
+
+    let event_loop = EventLoop::new()?;
+    let window = WindowBuilder::new()
+        .with_title(WINDOW_TITLE)
+        .with_inner_size(PhysicalSize::new(SCREEN_WIDTH, SCREEN_HEIGHT))
+        .with_append(canvas.is_none())
+        .with_canvas(canvas)
+        .build(&event_loop)?;
+
+    let state = State::new_async(
+        window.into(),
+        wgpu::Backends::BROWSER_WEBGPU,
+        init_params(),
+        1,
+        DEFAULT_TERRAIN_RESOLUTION,
+    )
+    .await
+    // The wgpu errors InitError wraps aren't Send in the browser, so anyhow can't hold it
+    .map_err(|e| match std::error::Error::source(&e) {
+        Some(source) => anyhow::anyhow!("{}: {}", e, source),
+        None => anyhow::anyhow!("{}", e),
+    })?;
+
+    crate::run(event_loop, state);
+    Ok(())
+}